@@ -0,0 +1,608 @@
+use std::fmt;
+use std::io;
+
+use flate2::bufread::ZlibDecoder;
+use serde_json::{json, Value};
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+use crate::repair::{IssueKind, ParseIssue};
+use crate::steganalysis::PixelAnalysis;
+use crate::text::{TextChunk, TextKind};
+
+/// Chunk types defined by the PNG specification and its registered extensions.
+const KNOWN_PUBLIC: [&[u8; 4]; 33] = [
+    b"IHDR", b"PLTE", b"IDAT", b"IEND", b"tRNS", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB",
+    b"cICP", b"mDCV", b"cLLI", b"tEXt", b"zTXt", b"iTXt", b"bKGD", b"hIST", b"pHYs", b"sPLT",
+    b"eXIf", b"tIME", b"acTL", b"fcTL", b"fdAT", b"oFFs", b"pCAL", b"sCAL", b"gIFg", b"gIFx",
+    b"gIFt", b"sTER", b"dSIG",
+];
+
+/// Private chunk types written by widespread software.
+const KNOWN_PRIVATE: [&[u8; 4]; 7] = [
+    b"iDOT", b"CgBI", b"vpAg", b"caNv", b"orNT", b"npTc", b"npOl",
+];
+
+/// Chunk types whose data is compressed by design, so high entropy is expected.
+const COMPRESSED: [&[u8; 4]; 3] = [b"zTXt", b"iCCP", b"fdAT"];
+
+/// Text longer than this, in bytes, is unusual outside of XMP and raw profiles.
+pub const MAX_TEXT_LENGTH: usize = 4096;
+
+/// Ancillary payloads of at least this many bytes are checked for high entropy.
+pub const MIN_ENTROPY_LENGTH: usize = 256;
+
+/// Entropy in bits per byte above which data looks compressed or encrypted.
+pub const HIGH_ENTROPY: f64 = 7.0;
+
+/// Share of LSBs below which pixel statistics are considered noise.
+pub const MIN_PAYLOAD_FRACTION: f64 = 0.05;
+
+/// Shannon entropy of `data` in bits per byte, from 0 to 8.
+pub fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let total = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Size and entropy of one chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkStats {
+    pub chunk_type: String,
+    /// Offset of the chunk's length field from the start of the file.
+    pub offset: usize,
+    pub length: usize,
+    pub entropy: f64,
+}
+
+/// Something that may hide data, with the points it adds to the suspicion score.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub offset: Option<usize>,
+    pub chunk_type: Option<String>,
+    pub message: String,
+    pub score: u32,
+}
+
+/// Steganalysis report for one image, based on its chunk structure and,
+/// optionally, on the statistics of its pixels.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub chunks: Vec<ChunkStats>,
+    pub findings: Vec<Finding>,
+    /// The pixel statistics, or why the image's pixels could not be analyzed.
+    pub pixels: Option<Result<PixelAnalysis, String>>,
+}
+
+impl Analysis {
+    pub fn for_png(png: &Png) -> Self {
+        let mut chunks = Vec::new();
+        let mut findings = Vec::new();
+        let mut offset = png.header().len();
+
+        for chunk in png.chunks() {
+            let stats = ChunkStats {
+                chunk_type: chunk.chunk_type().to_string(),
+                offset,
+                length: chunk.data().len(),
+                entropy: entropy(chunk.data()),
+            };
+            let mut flag = |message: String, score: u32| {
+                findings.push(Finding {
+                    offset: Some(offset),
+                    chunk_type: Some(stats.chunk_type.clone()),
+                    message,
+                    score,
+                })
+            };
+            check_chunk(chunk, &stats, &mut flag);
+            offset += 12 + chunk.data().len();
+            chunks.push(stats);
+        }
+
+        let trailer = png.trailer();
+        if !trailer.is_empty() {
+            findings.push(Finding {
+                offset: Some(offset),
+                chunk_type: None,
+                message: format!(
+                    "{} bytes of data after IEND (entropy {:.2})",
+                    trailer.len(),
+                    entropy(trailer)
+                ),
+                score: 40,
+            });
+        }
+        findings.extend(idat_findings(png, &chunks));
+
+        Analysis {
+            chunks,
+            findings,
+            pixels: None,
+        }
+    }
+
+    /// Adds a finding for every problem that lenient parsing worked around. A
+    /// chunk edited after encoding keeps its old CRC, and a chunk with an invalid
+    /// type is skipped by readers, so either can carry data.
+    pub fn with_issues(mut self, issues: &[ParseIssue]) -> Self {
+        for issue in issues {
+            let (chunk_type, message, score) = match &issue.kind {
+                IssueKind::BadCrc { stored, computed } => (
+                    None,
+                    format!(
+                        "CRC mismatch (stored {:08x}, computed {:08x}), data may have been edited",
+                        stored, computed
+                    ),
+                    20,
+                ),
+                IssueKind::Truncated {
+                    declared,
+                    available,
+                    ..
+                } => (
+                    None,
+                    format!(
+                        "truncated chunk (declares {} data bytes, {} available)",
+                        declared, available
+                    ),
+                    10,
+                ),
+                IssueKind::InvalidType(bytes) => (
+                    Some(String::from_utf8_lossy(bytes).into_owned()),
+                    "chunk with an invalid type, skipped by readers".to_string(),
+                    30,
+                ),
+            };
+            self.findings.push(Finding {
+                offset: Some(issue.offset),
+                chunk_type,
+                message,
+                score,
+            });
+        }
+        self
+    }
+
+    /// Adds LSB steganalysis of the pixel data and the findings it leads to.
+    /// Images it does not support, such as indexed, 16-bit or interlaced ones,
+    /// keep the chunk analysis and record why the pixels were skipped.
+    pub fn with_pixels(mut self, png: &Png) -> Self {
+        let pixels = match PixelAnalysis::for_png(png) {
+            Ok(pixels) => pixels,
+            Err(e) => {
+                self.pixels = Some(Err(e));
+                return self;
+            }
+        };
+        let mut flag = |message: String, score: u32| {
+            self.findings.push(Finding {
+                offset: None,
+                chunk_type: None,
+                message,
+                score,
+            })
+        };
+
+        // The chi-square attack also fires on images whose histogram is smooth
+        // at the level of pairs of values, so it only counts fully when RS
+        // analysis agrees.
+        let estimate = pixels.estimated_fraction();
+        let confirmed = estimate.is_none_or(|f| f >= MIN_PAYLOAD_FRACTION);
+        let detected: Vec<String> = pixels
+            .channels
+            .iter()
+            .filter(|c| c.sequential_fraction >= MIN_PAYLOAD_FRACTION)
+            .map(|c| format!("{} ({:.0}%)", c.channel, c.sequential_fraction * 100.0))
+            .collect();
+        if !detected.is_empty() {
+            flag(
+                format!(
+                    "chi-square attack detects LSB embedding from the start of {}{}",
+                    detected.join(", "),
+                    if confirmed {
+                        ""
+                    } else {
+                        ", not confirmed by RS analysis"
+                    }
+                ),
+                if confirmed { 30 } else { 10 },
+            );
+        }
+        if let Some(fraction) = estimate.filter(|&f| f >= MIN_PAYLOAD_FRACTION) {
+            flag(
+                format!(
+                    "RS analysis estimates that {:.0}% of LSBs carry data (about {} bytes)",
+                    fraction * 100.0,
+                    pixels.estimated_bytes()
+                ),
+                if fraction >= 0.15 { 40 } else { 20 },
+            );
+        }
+
+        self.pixels = Some(Ok(pixels));
+        self
+    }
+
+    /// Sum of the finding scores, capped at 100.
+    pub fn score(&self) -> u32 {
+        self.findings.iter().map(|f| f.score).sum::<u32>().min(100)
+    }
+
+    pub fn verdict(&self) -> &'static str {
+        match self.score() {
+            0 => "clean",
+            1..=29 => "low",
+            30..=59 => "medium",
+            _ => "high",
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        let chunks: Vec<Value> = self
+            .chunks
+            .iter()
+            .map(|c| {
+                json!({
+                    "type": c.chunk_type,
+                    "offset": c.offset,
+                    "length": c.length,
+                    "entropy": c.entropy,
+                })
+            })
+            .collect();
+        let findings: Vec<Value> = self
+            .findings
+            .iter()
+            .map(|f| {
+                json!({
+                    "offset": f.offset,
+                    "type": f.chunk_type,
+                    "message": f.message,
+                    "score": f.score,
+                })
+            })
+            .collect();
+        json!({
+            "chunks": chunks,
+            "findings": findings,
+            "pixels": match &self.pixels {
+                Some(Ok(pixels)) => pixels.to_value(),
+                Some(Err(e)) => json!({ "error": e }),
+                None => Value::Null,
+            },
+            "score": self.score(),
+            "verdict": self.verdict(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+}
+
+fn check_chunk(chunk: &Chunk, stats: &ChunkStats, flag: &mut impl FnMut(String, u32)) {
+    let chunk_type = chunk.chunk_type();
+    let bytes = chunk_type.bytes();
+
+    if !chunk_type.is_public() && !KNOWN_PRIVATE.contains(&&bytes) {
+        flag("unknown private chunk".to_string(), 30);
+    } else if chunk_type.is_public() && !KNOWN_PUBLIC.contains(&&bytes) {
+        flag(
+            "public chunk type not defined by the specification".to_string(),
+            20,
+        );
+    }
+
+    let mut compressed = COMPRESSED.contains(&&bytes);
+    if TextKind::from_chunk_type(chunk_type).is_some() {
+        if let Ok(text) = TextChunk::try_from(chunk) {
+            compressed |= text.compressed;
+            let is_profile =
+                text.keyword == "XML:com.adobe.xmp" || text.keyword.starts_with("Raw profile type");
+            if text.text.len() > MAX_TEXT_LENGTH && !is_profile {
+                flag(
+                    format!(
+                        "{:?} holds {} bytes of text, more than {}",
+                        text.keyword,
+                        text.text.len(),
+                        MAX_TEXT_LENGTH
+                    ),
+                    15,
+                );
+            }
+        }
+    }
+
+    if !chunk_type.is_critical()
+        && !compressed
+        && stats.length >= MIN_ENTROPY_LENGTH
+        && stats.entropy > HIGH_ENTROPY
+    {
+        flag(
+            format!(
+                "high entropy payload ({:.2} bits per byte), likely compressed or encrypted",
+                stats.entropy
+            ),
+            25,
+        );
+    }
+}
+
+/// Compares the IDAT layout with what common encoders write: consecutive
+/// chunks of one size, except for a shorter last one, holding a single zlib
+/// stream with nothing after it.
+fn idat_findings(png: &Png, chunks: &[ChunkStats]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let positions: Vec<usize> = chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| c.chunk_type == "IDAT")
+        .map(|(i, _)| i)
+        .collect();
+    let (Some(&first), Some(&last)) = (positions.first(), positions.last()) else {
+        return findings;
+    };
+    let mut flag = |index: usize, message: String, score: u32| {
+        findings.push(Finding {
+            offset: Some(chunks[index].offset),
+            chunk_type: Some("IDAT".to_string()),
+            message,
+            score,
+        })
+    };
+
+    if last - first + 1 != positions.len() {
+        flag(
+            first,
+            "IDAT chunks are interleaved with other chunks".to_string(),
+            30,
+        );
+    }
+
+    let lengths: Vec<usize> = positions.iter().map(|&i| chunks[i].length).collect();
+    if let Some((&tail, body)) = lengths.split_last() {
+        let regular =
+            body.iter().all(|&l| l == lengths[0]) && (body.is_empty() || tail <= lengths[0]);
+        if !regular || lengths.contains(&0) {
+            flag(
+                first,
+                format!("irregular IDAT sizes: {}", describe_lengths(&lengths)),
+                15,
+            );
+        }
+    }
+
+    let data = png.image_data();
+    let mut decoder = ZlibDecoder::new(data.as_slice());
+    match io::copy(&mut decoder, &mut io::sink()) {
+        Ok(_) if (decoder.total_in() as usize) < data.len() => flag(
+            last,
+            format!(
+                "{} bytes after the end of the compressed image data",
+                data.len() - decoder.total_in() as usize
+            ),
+            35,
+        ),
+        Ok(_) => {}
+        Err(e) => flag(first, format!("image data does not decompress: {}", e), 10),
+    }
+    findings
+}
+
+fn describe_lengths(lengths: &[usize]) -> String {
+    const SHOWN: usize = 8;
+    let mut text: Vec<String> = lengths.iter().take(SHOWN).map(|l| l.to_string()).collect();
+    if lengths.len() > SHOWN {
+        text.push(format!("... ({} chunks)", lengths.len()));
+    }
+    text.join(", ")
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<6} {:>10} {:>10} {:>8}",
+            "Chunk", "Offset", "Length", "Entropy"
+        )?;
+        for c in &self.chunks {
+            writeln!(
+                f,
+                "{:<6} {:>10} {:>10} {:>8.2}",
+                c.chunk_type, c.offset, c.length, c.entropy
+            )?;
+        }
+        match &self.pixels {
+            Some(Ok(pixels)) => {
+                writeln!(f)?;
+                write!(f, "{}", pixels)?;
+            }
+            Some(Err(e)) => {
+                writeln!(f)?;
+                writeln!(f, "Pixel analysis: not supported ({})", e)?;
+            }
+            None => {}
+        }
+        writeln!(f)?;
+        if self.findings.is_empty() {
+            writeln!(f, "No findings")?;
+        } else {
+            writeln!(f, "Findings:")?;
+        }
+        for finding in &self.findings {
+            write!(f, "  [+{}] ", finding.score)?;
+            match (finding.offset, &finding.chunk_type) {
+                (Some(offset), Some(chunk_type)) => write!(f, "{} {}: ", offset, chunk_type)?,
+                (Some(offset), None) => write!(f, "{}: ", offset)?,
+                (None, Some(chunk_type)) => write!(f, "{}: ", chunk_type)?,
+                (None, None) => {}
+            }
+            writeln!(f, "{}", finding.message)?;
+        }
+        writeln!(
+            f,
+            "Suspicion score: {}/100 ({})",
+            self.score(),
+            self.verdict()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn clean_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("IDAT", &zlib(&[0, 0])),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    fn pseudo_random(length: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_u32;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_entropy() {
+        assert_eq!(entropy(&[]), 0.0);
+        assert_eq!(entropy(&[7; 100]), 0.0);
+        assert!((entropy(&[0, 1, 2, 3]) - 2.0).abs() < 1e-9);
+        let all_bytes: Vec<u8> = (0..=255).collect();
+        assert!((entropy(&all_bytes) - 8.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_clean_png_has_no_findings() {
+        let analysis = Analysis::for_png(&clean_png());
+        assert!(analysis.findings.is_empty());
+        assert_eq!(analysis.score(), 0);
+        assert_eq!(analysis.verdict(), "clean");
+        assert_eq!(analysis.chunks[1].offset, 33);
+    }
+
+    #[test]
+    fn test_suspicious_png() {
+        let mut png = clean_png();
+        png.append_chunk(chunk("ruSt", &pseudo_random(1024)));
+        png.append_chunk(
+            TextChunk::new(TextKind::Text, "Comment", &"a".repeat(5000))
+                .to_chunk()
+                .unwrap(),
+        );
+        let bytes = [png.as_bytes(), b"extra".to_vec()].concat();
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+
+        let analysis = Analysis::for_png(&png);
+        let messages: Vec<&str> = analysis
+            .findings
+            .iter()
+            .map(|f| f.message.as_str())
+            .collect();
+        assert!(messages.contains(&"unknown private chunk"));
+        assert!(messages.iter().any(|m| m.starts_with("high entropy")));
+        assert!(messages.iter().any(|m| m.contains("5000 bytes of text")));
+        assert!(messages.iter().any(|m| m.contains("after IEND")));
+        assert_eq!(analysis.score(), 100);
+        assert!(analysis.to_json().contains("\"verdict\":\"high\""));
+    }
+
+    #[test]
+    fn test_parse_issues() {
+        let mut bytes = clean_png().as_bytes();
+        bytes[29] ^= 1;
+        let (png, issues) = crate::repair::parse_lenient(&bytes).unwrap();
+
+        let analysis = Analysis::for_png(&png).with_issues(&issues);
+        assert_eq!(analysis.findings.len(), 1);
+        assert_eq!(analysis.findings[0].offset, Some(8));
+        assert!(analysis.findings[0].message.starts_with("CRC mismatch"));
+        assert_eq!(analysis.score(), 20);
+
+        let json: Value = serde_json::from_str(&analysis.to_json()).unwrap();
+        assert_eq!(json["findings"][0]["score"], 20);
+        assert_eq!(json["findings"][0]["type"], Value::Null);
+    }
+
+    #[test]
+    fn test_with_pixels() {
+        let analysis = Analysis::for_png(&clean_png()).with_pixels(&clean_png());
+        let pixels = analysis.pixels.as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(pixels.channels.len(), 1);
+        assert_eq!(pixels.estimated_fraction(), None);
+        assert!(analysis.findings.is_empty());
+        assert!(analysis.to_json().contains("\"pixels\":{\"channels\""));
+
+        let indexed = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+            chunk("PLTE", &[0, 0, 0]),
+            chunk("IDAT", &zlib(&[0, 0])),
+            chunk("IEND", &[]),
+        ]);
+        let analysis = Analysis::for_png(&indexed).with_pixels(&indexed);
+        assert!(matches!(analysis.pixels, Some(Err(_))));
+        assert_eq!(analysis.chunks.len(), 4);
+        assert!(analysis
+            .to_string()
+            .contains("Pixel analysis: not supported"));
+    }
+
+    #[test]
+    fn test_idat_patterns() {
+        let stream = [zlib(&[0; 4000]), b"hidden".to_vec()].concat();
+        let mut chunks = vec![chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0])];
+        chunks.push(chunk("IDAT", &stream[..4]));
+        chunks.push(chunk("tEXt", b"a\0b"));
+        chunks.push(chunk("IDAT", &stream[4..]));
+        chunks.push(chunk("IEND", &[]));
+
+        let analysis = Analysis::for_png(&Png::from_chunks(chunks));
+        let messages: Vec<&str> = analysis
+            .findings
+            .iter()
+            .map(|f| f.message.as_str())
+            .collect();
+        assert!(messages.contains(&"IDAT chunks are interleaved with other chunks"));
+        assert!(messages
+            .iter()
+            .any(|m| m.starts_with("irregular IDAT sizes")));
+        assert!(messages.contains(&"6 bytes after the end of the compressed image data"));
+    }
+}
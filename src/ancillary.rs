@@ -0,0 +1,566 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ihdr::{ColorType, Ihdr};
+use crate::palette::Palette;
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Only the aspect ratio is known.
+    Unknown,
+    Meter,
+}
+
+/// pHYs: intended pixel size or aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalDimensions {
+    pub pixels_per_unit_x: u32,
+    pub pixels_per_unit_y: u32,
+    pub unit: Unit,
+}
+
+impl PhysicalDimensions {
+    pub fn from_dpi(dpi: f64) -> Self {
+        let pixels_per_meter = (dpi / METERS_PER_INCH).round() as u32;
+        PhysicalDimensions {
+            pixels_per_unit_x: pixels_per_meter,
+            pixels_per_unit_y: pixels_per_meter,
+            unit: Unit::Meter,
+        }
+    }
+
+    /// Horizontal and vertical resolution in dots per inch, if the unit is known.
+    pub fn dpi(&self) -> Option<(f64, f64)> {
+        match self.unit {
+            Unit::Meter => Some((
+                self.pixels_per_unit_x as f64 * METERS_PER_INCH,
+                self.pixels_per_unit_y as f64 * METERS_PER_INCH,
+            )),
+            Unit::Unknown => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.pixels_per_unit_x.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.pixels_per_unit_y.to_be_bytes());
+        bytes.push(match self.unit {
+            Unit::Unknown => 0,
+            Unit::Meter => 1,
+        });
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for PhysicalDimensions {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 9 {
+            return Err(format!("pHYs data must be 9 bytes, got {}", value.len()));
+        }
+        let unit = match value[8] {
+            0 => Unit::Unknown,
+            1 => Unit::Meter,
+            other => return Err(format!("Invalid pHYs unit: {}", other)),
+        };
+        Ok(PhysicalDimensions {
+            pixels_per_unit_x: u32::from_be_bytes([value[0], value[1], value[2], value[3]]),
+            pixels_per_unit_y: u32::from_be_bytes([value[4], value[5], value[6], value[7]]),
+            unit,
+        })
+    }
+}
+
+impl fmt::Display for PhysicalDimensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dpi() {
+            Some((x, _)) if self.pixels_per_unit_x == self.pixels_per_unit_y => {
+                write!(f, "{:.0} dpi", x)
+            }
+            Some((x, y)) => write!(f, "{:.0}x{:.0} dpi", x, y),
+            None => write!(
+                f,
+                "aspect ratio {}:{}",
+                self.pixels_per_unit_x, self.pixels_per_unit_y
+            ),
+        }
+    }
+}
+
+/// tIME: time of the last image modification, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Time {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    pub const LENGTH: usize = 7;
+
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Time::from_unix(seconds)
+    }
+
+    pub fn from_unix(seconds: u64) -> Self {
+        let (days, rest) = (seconds / 86400, seconds % 86400);
+        // Converts days since 1970-01-01 to a civil date (Howard Hinnant's algorithm).
+        let z = days as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + (month <= 2) as i64;
+
+        Time {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (rest / 3600) as u8,
+            minute: (rest / 60 % 60) as u8,
+            second: (rest % 60) as u8,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let leap = self.year.is_multiple_of(4)
+            && (!self.year.is_multiple_of(100) || self.year.is_multiple_of(400));
+        let days_in_month = match self.month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        let valid = (1..=12).contains(&self.month)
+            && (1..=days_in_month).contains(&self.day)
+            && self.hour <= 23
+            && self.minute <= 59
+            // 60 allows for leap seconds.
+            && self.second <= 60;
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid tIME value: {}", self))
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.year.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Time {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let &[y1, y2, month, day, hour, minute, second] = value else {
+            return Err(format!(
+                "tIME data must be {} bytes, got {}",
+                Time::LENGTH,
+                value.len()
+            ));
+        };
+        let time = Time {
+            year: u16::from_be_bytes([y1, y2]),
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        time.validate()?;
+        Ok(time)
+    }
+}
+
+impl FromStr for Time {
+    type Err = String;
+
+    /// Parses `now` or `YYYY-MM-DDTHH:MM:SS` (a space may replace the `T`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "now" {
+            return Ok(Time::now());
+        }
+        let invalid = || format!("Invalid time '{}', expected YYYY-MM-DDTHH:MM:SS", s);
+        let (date, time) = s.split_once(['T', ' ']).ok_or_else(invalid)?;
+        let date: Vec<&str> = date.split('-').collect();
+        let time: Vec<&str> = time.trim_end_matches('Z').split(':').collect();
+        let (&[year, month, day], &[hour, minute, second]) = (&date[..], &time[..]) else {
+            return Err(invalid());
+        };
+        let number = |n: &str| n.parse::<u8>().map_err(|_| invalid());
+        let time = Time {
+            year: year.parse().map_err(|_| invalid())?,
+            month: number(month)?,
+            day: number(day)?,
+            hour: number(hour)?,
+            minute: number(minute)?,
+            second: number(second)?,
+        };
+        time.validate()?;
+        Ok(time)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+/// bKGD: the preferred background color, in the format of the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Background {
+    Indexed(u8),
+    Grayscale(u16),
+    Rgb(u16, u16, u16),
+}
+
+impl Background {
+    pub fn parse(data: &[u8], ihdr: &Ihdr, palette: Option<&Palette>) -> Result<Self, String> {
+        let sample = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+        let expected = match ihdr.color_type {
+            ColorType::Indexed => 1,
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb | ColorType::Rgba => 6,
+        };
+        if data.len() != expected {
+            return Err(format!(
+                "bKGD data must be {} bytes for {} images, got {}",
+                expected,
+                ihdr.color_type,
+                data.len()
+            ));
+        }
+        match ihdr.color_type {
+            ColorType::Indexed => {
+                let index = data[0];
+                let entries = palette.map_or(0, |p| p.len());
+                if index as usize >= entries {
+                    return Err(format!(
+                        "bKGD palette index {} is out of range for {} entries",
+                        index, entries
+                    ));
+                }
+                Ok(Background::Indexed(index))
+            }
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                Ok(Background::Grayscale(sample(0)))
+            }
+            ColorType::Rgb | ColorType::Rgba => {
+                Ok(Background::Rgb(sample(0), sample(2), sample(4)))
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match *self {
+            Background::Indexed(index) => vec![index],
+            Background::Grayscale(gray) => gray.to_be_bytes().to_vec(),
+            Background::Rgb(r, g, b) => [r, g, b].iter().flat_map(|s| s.to_be_bytes()).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Background {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Background::Indexed(index) => write!(f, "palette entry {}", index),
+            Background::Grayscale(gray) => write!(f, "gray {}", gray),
+            Background::Rgb(r, g, b) => write!(f, "rgb({}, {}, {})", r, g, b),
+        }
+    }
+}
+
+/// sBIT: the number of significant bits in each channel of the original image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignificantBits(pub Vec<u8>);
+
+impl SignificantBits {
+    pub fn parse(data: &[u8], ihdr: &Ihdr) -> Result<Self, String> {
+        let (channels, max) = match ihdr.color_type {
+            // Palette entries are always 8 bits per channel.
+            ColorType::Indexed => (3, 8),
+            color_type => (color_type.channels(), ihdr.bit_depth),
+        };
+        if data.len() != channels as usize {
+            return Err(format!(
+                "sBIT data must be {} bytes for {} images, got {}",
+                channels,
+                ihdr.color_type,
+                data.len()
+            ));
+        }
+        if let Some(bits) = data.iter().find(|&&bits| bits == 0 || bits > max) {
+            return Err(format!("sBIT value {} is out of range 1 to {}", bits, max));
+        }
+        Ok(SignificantBits(data.to_vec()))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+impl fmt::Display for SignificantBits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits: Vec<String> = self.0.iter().map(u8::to_string).collect();
+        write!(f, "{} significant bits", bits.join("/"))
+    }
+}
+
+/// hIST: approximate usage frequency of each palette entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram(pub Vec<u16>);
+
+impl Histogram {
+    pub fn parse(data: &[u8], palette: &Palette) -> Result<Self, String> {
+        if data.len() != palette.len() * 2 {
+            return Err(format!(
+                "hIST must have one entry per palette entry ({}), got {} bytes",
+                palette.len(),
+                data.len()
+            ));
+        }
+        Ok(Histogram(
+            data.chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect(),
+        ))
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.0.iter().flat_map(|f| f.to_be_bytes()).collect()
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let used = self.0.iter().filter(|&&f| f > 0).count();
+        write!(f, "{} of {} palette entries used", used, self.0.len())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuggestedEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+/// sPLT: a named palette suggested for viewers that cannot show all colors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestedPalette {
+    pub name: String,
+    /// Sample depth of the entries, 8 or 16.
+    pub depth: u8,
+    pub entries: Vec<SuggestedEntry>,
+}
+
+impl SuggestedPalette {
+    pub fn as_bytes(&self) -> Result<Vec<u8>, String> {
+        if self.name.is_empty() || self.name.len() > 79 {
+            return Err("sPLT name must be 1 to 79 bytes long".to_string());
+        }
+        let mut bytes: Vec<u8> = self
+            .name
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| format!("Character {:?} is not Latin-1", c)))
+            .collect::<Result<_, _>>()?;
+        bytes.extend_from_slice(&[0, self.depth]);
+        for entry in &self.entries {
+            let samples = [entry.red, entry.green, entry.blue, entry.alpha];
+            match self.depth {
+                8 => bytes.extend(samples.iter().map(|&s| s as u8)),
+                16 => bytes.extend(samples.iter().flat_map(|s| s.to_be_bytes())),
+                depth => return Err(format!("Invalid sPLT sample depth: {}", depth)),
+            }
+            bytes.extend_from_slice(&entry.frequency.to_be_bytes());
+        }
+        Ok(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for SuggestedPalette {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let separator = value
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| "sPLT chunk is missing a null separator".to_string())?;
+        let name = value[..separator].iter().map(|&b| b as char).collect();
+        let depth = *value
+            .get(separator + 1)
+            .ok_or_else(|| "sPLT chunk is missing its sample depth".to_string())?;
+        let entry_size = match depth {
+            8 => 6,
+            16 => 10,
+            _ => return Err(format!("Invalid sPLT sample depth: {}", depth)),
+        };
+        let data = &value[separator + 2..];
+        if !data.len().is_multiple_of(entry_size) {
+            return Err(format!(
+                "sPLT entries must be {} bytes each, got {} bytes",
+                entry_size,
+                data.len()
+            ));
+        }
+
+        let entries = data
+            .chunks_exact(entry_size)
+            .map(|e| {
+                let sample = |i: usize| match depth {
+                    8 => e[i] as u16,
+                    _ => u16::from_be_bytes([e[2 * i], e[2 * i + 1]]),
+                };
+                let f = entry_size - 2;
+                SuggestedEntry {
+                    red: sample(0),
+                    green: sample(1),
+                    blue: sample(2),
+                    alpha: sample(3),
+                    frequency: u16::from_be_bytes([e[f], e[f + 1]]),
+                }
+            })
+            .collect();
+        Ok(SuggestedPalette {
+            name,
+            depth,
+            entries,
+        })
+    }
+}
+
+impl fmt::Display for SuggestedPalette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' ({} entries, {}-bit)",
+            self.name,
+            self.entries.len(),
+            self.depth
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr(color_type: ColorType, bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width: 1,
+            height: 1,
+            bit_depth,
+            color_type,
+            interlace: false,
+        }
+    }
+
+    #[test]
+    fn test_physical_dimensions_dpi() {
+        let phys = PhysicalDimensions::from_dpi(300.0);
+        assert_eq!(phys.pixels_per_unit_x, 11811);
+        assert_eq!(phys.to_string(), "300 dpi");
+
+        let parsed = PhysicalDimensions::try_from(phys.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed, phys);
+
+        let aspect = PhysicalDimensions {
+            pixels_per_unit_x: 2,
+            pixels_per_unit_y: 1,
+            unit: Unit::Unknown,
+        };
+        assert_eq!(aspect.dpi(), None);
+        assert!(PhysicalDimensions::try_from(&[0; 8][..]).is_err());
+    }
+
+    #[test]
+    fn test_time() {
+        let time = Time::from_unix(1_700_000_000);
+        assert_eq!(time.to_string(), "2023-11-14T22:13:20Z");
+        assert_eq!(Time::try_from(time.as_bytes().as_slice()).unwrap(), time);
+        assert_eq!(Time::from_str("2023-11-14 22:13:20").unwrap(), time);
+        assert_eq!(
+            Time::from_unix(951_782_400).to_string(),
+            "2000-02-29T00:00:00Z"
+        );
+
+        assert!(Time::from_str("2023-13-01T00:00:00").is_err());
+        assert!(Time::from_str("2023-02-29T00:00:00").is_err());
+        assert!(Time::from_str("yesterday").is_err());
+        assert!(Time::try_from(&[7, 231, 1, 32, 0, 0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn test_background() {
+        let rgb = ihdr(ColorType::Rgb, 8);
+        let bkgd = Background::parse(&[0, 1, 0, 2, 0, 3], &rgb, None).unwrap();
+        assert_eq!(bkgd, Background::Rgb(1, 2, 3));
+        assert_eq!(bkgd.as_bytes(), vec![0, 1, 0, 2, 0, 3]);
+
+        let indexed = ihdr(ColorType::Indexed, 8);
+        let palette = Palette::new(vec![[0, 0, 0]; 2]).unwrap();
+        assert!(Background::parse(&[1], &indexed, Some(&palette)).is_ok());
+        assert!(Background::parse(&[2], &indexed, Some(&palette)).is_err());
+    }
+
+    #[test]
+    fn test_significant_bits() {
+        let rgba = ihdr(ColorType::Rgba, 8);
+        assert!(SignificantBits::parse(&[5, 6, 5, 8], &rgba).is_ok());
+        assert!(SignificantBits::parse(&[5, 6, 5], &rgba).is_err());
+        assert!(SignificantBits::parse(&[5, 6, 5, 9], &rgba).is_err());
+    }
+
+    #[test]
+    fn test_histogram() {
+        let palette = Palette::new(vec![[0, 0, 0]; 3]).unwrap();
+        let hist = Histogram::parse(&[0, 5, 0, 0, 1, 0], &palette).unwrap();
+        assert_eq!(hist, Histogram(vec![5, 0, 256]));
+        assert_eq!(hist.to_string(), "2 of 3 palette entries used");
+        assert!(Histogram::parse(&[0, 5], &palette).is_err());
+    }
+
+    #[test]
+    fn test_suggested_palette_round_trip() {
+        for depth in [8, 16] {
+            let splt = SuggestedPalette {
+                name: "web".to_string(),
+                depth,
+                entries: vec![SuggestedEntry {
+                    red: 255,
+                    green: 0,
+                    blue: 128,
+                    alpha: 255,
+                    frequency: 42,
+                }],
+            };
+            let bytes = splt.as_bytes().unwrap();
+            assert_eq!(SuggestedPalette::try_from(bytes.as_slice()).unwrap(), splt);
+        }
+        assert!(SuggestedPalette::try_from(&b"web\0\x08\x01"[..]).is_err());
+    }
+}
@@ -0,0 +1,497 @@
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+/// acTL: number of frames and how many times to play the animation (0 = forever).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationControl {
+    pub num_frames: u32,
+    pub num_plays: u32,
+}
+
+impl AnimationControl {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.num_frames.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.num_plays.to_be_bytes());
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for AnimationControl {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 8 {
+            return Err(format!("acTL data must be 8 bytes, got {}", value.len()));
+        }
+        let num_frames = read_u32(value, 0);
+        if num_frames == 0 {
+            return Err("acTL must declare at least one frame".to_string());
+        }
+        Ok(AnimationControl {
+            num_frames,
+            num_plays: read_u32(value, 4),
+        })
+    }
+}
+
+impl fmt::Display for AnimationControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} frames, ", self.num_frames)?;
+        match self.num_plays {
+            0 => write!(f, "loops forever"),
+            n => write!(f, "plays {} times", n),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposeOp {
+    None,
+    Background,
+    Previous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendOp {
+    Source,
+    Over,
+}
+
+/// fcTL: geometry and timing of one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub sequence_number: u32,
+    pub width: u32,
+    pub height: u32,
+    pub x_offset: u32,
+    pub y_offset: u32,
+    pub delay_num: u16,
+    pub delay_den: u16,
+    pub dispose_op: DisposeOp,
+    pub blend_op: BlendOp,
+}
+
+impl FrameControl {
+    pub const LENGTH: usize = 26;
+
+    /// Frame delay in seconds; a denominator of 0 means 1/100 s per the APNG specification.
+    pub fn delay_seconds(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(FrameControl::LENGTH);
+        for value in [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.delay_num.to_be_bytes());
+        bytes.extend_from_slice(&self.delay_den.to_be_bytes());
+        bytes.push(match self.dispose_op {
+            DisposeOp::None => 0,
+            DisposeOp::Background => 1,
+            DisposeOp::Previous => 2,
+        });
+        bytes.push(match self.blend_op {
+            BlendOp::Source => 0,
+            BlendOp::Over => 1,
+        });
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for FrameControl {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != FrameControl::LENGTH {
+            return Err(format!(
+                "fcTL data must be {} bytes, got {}",
+                FrameControl::LENGTH,
+                value.len()
+            ));
+        }
+        let dispose_op = match value[24] {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            other => return Err(format!("Invalid fcTL dispose_op: {}", other)),
+        };
+        let blend_op = match value[25] {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            other => return Err(format!("Invalid fcTL blend_op: {}", other)),
+        };
+        let width = read_u32(value, 4);
+        let height = read_u32(value, 8);
+        if width == 0 || height == 0 {
+            return Err("fcTL width and height must be non-zero".to_string());
+        }
+        Ok(FrameControl {
+            sequence_number: read_u32(value, 0),
+            width,
+            height,
+            x_offset: read_u32(value, 12),
+            y_offset: read_u32(value, 16),
+            delay_num: u16::from_be_bytes([value[20], value[21]]),
+            delay_den: u16::from_be_bytes([value[22], value[23]]),
+            dispose_op,
+            blend_op,
+        })
+    }
+}
+
+impl fmt::Display for FrameControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} at ({}, {}), {:.3}s, dispose {:?}, blend {:?}",
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+            self.delay_seconds(),
+            self.dispose_op,
+            self.blend_op
+        )
+    }
+}
+
+/// fdAT: a sequence number followed by compressed frame data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameData<'a> {
+    pub sequence_number: u32,
+    pub data: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for FrameData<'a> {
+    type Error = String;
+
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        if value.len() < 4 {
+            return Err("fdAT data is too short for a sequence number".to_string());
+        }
+        Ok(FrameData {
+            sequence_number: read_u32(value, 0),
+            data: &value[4..],
+        })
+    }
+}
+
+/// One animation frame: its fcTL plus the image data that belongs to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub control: FrameControl,
+    /// Compressed data slices, from IDAT for the default image or from fdAT otherwise.
+    pub data: Vec<&'a [u8]>,
+    /// Whether this frame is the default image stored in IDAT.
+    pub is_default_image: bool,
+}
+
+/// Iterator over the frames of an APNG, created by [`Png::frames`].
+pub struct Frames<'a> {
+    chunks: &'a [Chunk],
+    position: usize,
+}
+
+impl<'a> Frames<'a> {
+    pub fn new(png: &'a Png) -> Self {
+        Frames {
+            chunks: png.chunks(),
+            position: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Result<Frame<'a>, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.chunks[self.position..]
+            .iter()
+            .position(|c| c.chunk_type().to_string() == "fcTL")?
+            + self.position;
+        self.position = start + 1;
+
+        let control = match FrameControl::try_from(self.chunks[start].data()) {
+            Ok(control) => control,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let mut data = Vec::new();
+        let mut is_default_image = false;
+        while let Some(chunk) = self.chunks.get(self.position) {
+            match chunk.chunk_type().to_string().as_str() {
+                "IDAT" => {
+                    is_default_image = true;
+                    data.push(chunk.data());
+                }
+                "fdAT" => match FrameData::try_from(chunk.data()) {
+                    Ok(frame_data) => data.push(frame_data.data),
+                    Err(e) => return Some(Err(e)),
+                },
+                "fcTL" | "IEND" => break,
+                _ => {}
+            }
+            self.position += 1;
+        }
+
+        Some(Ok(Frame {
+            control,
+            data,
+            is_default_image,
+        }))
+    }
+}
+
+/// Checks that fcTL and fdAT sequence numbers run 0, 1, 2, ... in file order and
+/// that acTL matches the number of frames. Returns a description of every problem.
+pub fn sequence_problems(png: &Png) -> Vec<String> {
+    let mut problems = Vec::new();
+    // u64 so that a sequence number of u32::MAX does not overflow when
+    // computing the next expected one.
+    let mut expected = 0u64;
+    let mut frames = 0u32;
+
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if chunk_type != "fcTL" && chunk_type != "fdAT" {
+            continue;
+        }
+        if chunk_type == "fcTL" {
+            frames += 1;
+        }
+        if chunk.data().len() < 4 {
+            problems.push(format!("{} #{} has no sequence number", chunk_type, index));
+            continue;
+        }
+        let sequence_number = u64::from(read_u32(chunk.data(), 0));
+        if sequence_number > expected {
+            problems.push(format!(
+                "{} #{} skips to sequence number {} (expected {})",
+                chunk_type, index, sequence_number, expected
+            ));
+        } else if sequence_number + 1 == expected {
+            problems.push(format!(
+                "{} #{} repeats sequence number {}",
+                chunk_type, index, sequence_number
+            ));
+        } else if sequence_number < expected {
+            problems.push(format!(
+                "{} #{} goes back to sequence number {} (expected {})",
+                chunk_type, index, sequence_number, expected
+            ));
+        }
+        expected = sequence_number + 1;
+    }
+
+    match png.animation_control() {
+        Ok(Some(actl)) if actl.num_frames != frames => problems.push(format!(
+            "acTL declares {} frames but the file has {} fcTL chunks",
+            actl.num_frames, frames
+        )),
+        Ok(None) if frames > 0 => problems.push("fcTL chunks without an acTL chunk".to_string()),
+        Err(e) => problems.push(e),
+        _ => {}
+    }
+
+    problems
+}
+
+/// Rewrites fcTL and fdAT sequence numbers to run 0, 1, 2, ... in file order and sets the
+/// acTL frame count to the number of fcTL chunks. Returns whether any chunk changed.
+pub fn renumber(chunks: &mut [Chunk]) -> bool {
+    let mut changed = false;
+    let mut next = 0u32;
+    let mut frames = 0u32;
+
+    for chunk in chunks.iter_mut() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if (chunk_type != "fcTL" && chunk_type != "fdAT") || chunk.data().len() < 4 {
+            continue;
+        }
+        if chunk_type == "fcTL" {
+            frames += 1;
+        }
+        if read_u32(chunk.data(), 0) != next {
+            let mut data = chunk.data().to_vec();
+            data[..4].copy_from_slice(&next.to_be_bytes());
+            *chunk = Chunk::new(chunk.chunk_type().clone(), data);
+            changed = true;
+        }
+        next += 1;
+    }
+
+    if let Some(actl) = chunks
+        .iter_mut()
+        .find(|c| c.chunk_type().to_string() == "acTL")
+    {
+        if let Ok(mut control) = AnimationControl::try_from(actl.data()) {
+            if frames > 0 && control.num_frames != frames {
+                control.num_frames = frames;
+                *actl = Chunk::new(actl.chunk_type().clone(), control.as_bytes());
+                changed = true;
+            }
+        }
+    }
+
+    changed
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn fctl(sequence_number: u32) -> Chunk {
+        let control = FrameControl {
+            sequence_number,
+            width: 4,
+            height: 2,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Over,
+        };
+        chunk("fcTL", control.as_bytes())
+    }
+
+    fn fdat(sequence_number: u32, data: &[u8]) -> Chunk {
+        let mut bytes = sequence_number.to_be_bytes().to_vec();
+        bytes.extend_from_slice(data);
+        chunk("fdAT", bytes)
+    }
+
+    fn testing_apng(sequence: [u32; 4]) -> Png {
+        let actl = AnimationControl {
+            num_frames: 2,
+            num_plays: 0,
+        };
+        Png::from_chunks(vec![
+            chunk("IHDR", vec![0; 13]),
+            chunk("acTL", actl.as_bytes()),
+            fctl(sequence[0]),
+            chunk("IDAT", b"default".to_vec()),
+            fctl(sequence[1]),
+            fdat(sequence[2], b"second-a"),
+            fdat(sequence[3], b"second-b"),
+            chunk("IEND", vec![]),
+        ])
+    }
+
+    #[test]
+    fn test_frame_control_round_trip() {
+        let bytes = fctl(7).data().to_vec();
+        let control = FrameControl::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(control.sequence_number, 7);
+        assert_eq!(control.delay_seconds(), 0.1);
+        assert_eq!(control.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_invalid_dispose_op() {
+        let mut bytes = fctl(0).data().to_vec();
+        bytes[24] = 3;
+        assert!(FrameControl::try_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let png = testing_apng([0, 1, 2, 3]);
+        let frames: Vec<Frame> = png.frames().collect::<Result<_, _>>().unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].is_default_image);
+        assert_eq!(frames[0].data, vec![b"default".as_slice()]);
+        assert!(!frames[1].is_default_image);
+        assert_eq!(
+            frames[1].data,
+            vec![b"second-a".as_slice(), b"second-b".as_slice()]
+        );
+    }
+
+    #[test]
+    fn test_renumber() {
+        let mut png = testing_apng([0, 5, 5, 9]);
+        assert!(renumber(&mut png.chunks));
+        assert!(sequence_problems(&png).is_empty());
+        assert!(!renumber(&mut png.chunks));
+    }
+
+    #[test]
+    fn test_remove_frame_keeps_sequence_valid() {
+        let mut png = testing_apng([0, 1, 2, 3]);
+        png.remove_first_chunk("fdAT").unwrap();
+        assert!(sequence_problems(&png).is_empty());
+
+        png.remove_first_chunk("fcTL").unwrap();
+        assert!(sequence_problems(&png).is_empty());
+        assert_eq!(png.animation_control().unwrap().unwrap().num_frames, 1);
+    }
+
+    #[test]
+    fn test_valid_sequence() {
+        let png = testing_apng([0, 1, 2, 3]);
+        assert!(sequence_problems(&png).is_empty());
+    }
+
+    #[test]
+    fn test_sequence_gaps_and_duplicates() {
+        let png = testing_apng([0, 2, 2, 3]);
+        let problems = sequence_problems(&png);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("skips"));
+        assert!(problems[1].contains("repeats"));
+    }
+
+    #[test]
+    fn test_sequence_number_at_u32_max() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", vec![0; 13]),
+            chunk(
+                "acTL",
+                AnimationControl {
+                    num_frames: 1,
+                    num_plays: 0,
+                }
+                .as_bytes(),
+            ),
+            fctl(u32::MAX),
+            chunk("IDAT", b"default".to_vec()),
+            fdat(u32::MAX, b"again"),
+            chunk("IEND", vec![]),
+        ]);
+        let problems = sequence_problems(&png);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("skips"));
+        assert!(problems[1].contains("repeats"));
+    }
+}
@@ -1,6 +1,6 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
 
 #[derive(Debug, Clone)]
 pub enum Input {
@@ -16,12 +16,69 @@ impl Input {
             Input::FilePath(PathBuf::from(input))
         }
     }
+
+    /// The local file path; `None` for URLs.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Input::FilePath(path) => Some(path),
+            Input::Url(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Method {
+    /// Store the message in its own chunk
+    Chunk,
+    /// Store the message in the order of PLTE entries of an indexed image
+    Palette,
+    /// Store the message in the bytes following the IEND chunk
+    Trailer,
+}
+
+impl From<Method> for crate::embedder::Method {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Chunk => crate::embedder::Method::Chunk,
+            Method::Palette => crate::embedder::Method::Palette,
+            Method::Trailer => crate::embedder::Method::Trailer,
+        }
+    }
+}
+
+// Options for commands that can process many files at once. A doc comment here
+// would replace the about text of every command that flattens it.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BatchArgs {
+    /// Include PNG files in subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+    /// Number of files to process in parallel; defaults to the number of CPUs
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
 }
 
 #[derive(Parser)]
 pub struct Args {
     #[command(subcommand)]
     pub command: Commands,
+    /// Record the current time in the tIME chunk of every PNG file written
+    #[arg(long, global = true)]
+    pub touch: bool,
+    /// Allow replacing an existing output file that is not the input. This is
+    /// not `--force`, which already allows critical chunk changes on encode and remove
+    #[arg(long, global = true)]
+    pub overwrite: bool,
+    /// Keep a copy of every file that is replaced, named with this suffix
+    #[arg(
+        long,
+        global = true,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak"
+    )]
+    pub backup: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -29,29 +86,280 @@ pub enum Commands {
     Encode {
         #[arg(short, long)]
         url: bool,
-        input: String,
-        chunk_type: String,
-        message: String,
+        /// PNG files, directories or glob patterns, followed by the chunk type
+        /// (chunk method only) and the message
+        #[arg(required_unless_present = "manifest")]
+        input: Vec<String>,
         #[arg(short, long)]
         output_file: Option<PathBuf>,
         #[arg(short, long)]
         password: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = Method::Chunk)]
+        method: Method,
+        /// Allow critical chunk types and duplicates of chunks that must be unique
+        #[arg(long)]
+        force: bool,
+        /// CSV or JSON lines file with one file to encode per row; a chunk type
+        /// given before it applies to rows without their own
+        #[arg(long, conflicts_with_all = ["url", "output_file", "password"])]
+        manifest: Option<PathBuf>,
+        /// Where to write the result manifest instead of standard output
+        #[arg(long, requires = "manifest")]
+        results: Option<PathBuf>,
+        #[command(flatten)]
+        batch: BatchArgs,
     },
     Decode {
         #[arg(short, long)]
         url: bool,
-        input: String,
-        chunk_type: String,
+        /// PNG files, directories or glob patterns, followed by the chunk type
+        /// (chunk method only)
+        #[arg(required = true)]
+        input: Vec<String>,
         #[arg(short, long)]
         password: Option<String>,
+        #[arg(short, long, value_enum, default_value_t = Method::Chunk)]
+        method: Method,
+        /// Keep going past damaged chunks instead of rejecting the file
+        #[arg(long)]
+        lenient: bool,
+        #[command(flatten)]
+        batch: BatchArgs,
     },
     Remove {
-        input: PathBuf,
+        /// PNG files, directories or glob patterns
+        #[arg(required = true)]
+        input: Vec<String>,
         chunk_type: String,
+        /// Allow removing critical chunks such as IHDR, IDAT or IEND
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        batch: BatchArgs,
     },
     Print {
+        #[arg(short, long)]
+        url: bool,
+        /// PNG files, directories or glob patterns
+        #[arg(required = true)]
+        input: Vec<String>,
+        /// Keep going past damaged chunks instead of rejecting the file
+        #[arg(long)]
+        lenient: bool,
+        /// Print every chunk and its decoded value as JSON
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    Strip {
+        /// PNG files, directories or glob patterns
+        #[arg(required = true)]
+        input: Vec<String>,
+        /// Ancillary chunk types to keep, e.g. sRGB,gAMA,iCCP
+        #[arg(short, long, value_delimiter = ',')]
+        keep: Vec<String>,
+        /// Keep the data after IEND
+        #[arg(long)]
+        keep_trailer: bool,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+        /// Report what would be removed without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    Transplant {
+        source: PathBuf,
+        target: PathBuf,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+        /// Also copy unsafe-to-copy chunks even when the images differ
+        #[arg(long)]
+        include_unsafe: bool,
+    },
+    Diff {
+        left: PathBuf,
+        right: PathBuf,
+    },
+    /// Read and edit the EXIF data stored in the eXIf chunk
+    Exif {
+        #[command(subcommand)]
+        action: ExifAction,
+    },
+    /// Read and edit the XMP packet stored in the iTXt chunk
+    Xmp {
+        #[command(subcommand)]
+        action: XmpAction,
+    },
+    /// Extract or embed the ICC color profile stored in the iCCP chunk
+    Icc {
+        #[command(subcommand)]
+        action: IccAction,
+    },
+    /// Change the resolution or the last modification time
+    #[command(group(ArgGroup::new("values").required(true).multiple(true)))]
+    Set {
+        input: PathBuf,
+        /// Resolution in dots per inch, stored in pHYs
+        #[arg(long, group = "values")]
+        dpi: Option<f64>,
+        /// `now` or YYYY-MM-DDTHH:MM:SS in UTC, stored in tIME
+        #[arg(long, group = "values")]
+        time: Option<String>,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    Validate {
+        #[arg(short, long)]
+        url: bool,
+        /// PNG files, directories or glob patterns
+        #[arg(required = true)]
+        input: Vec<String>,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    Repair {
+        input: PathBuf,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    Capacity {
         #[arg(short, long)]
         url: bool,
         input: String,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Look for signs of hidden data and rate how suspicious the file is
+    Analyze {
+        #[arg(short, long)]
+        url: bool,
+        /// PNG files, directories or glob patterns
+        #[arg(required = true)]
+        input: Vec<String>,
+        #[arg(long)]
+        json: bool,
+        /// Also run chi-square and RS analysis on the pixel LSBs
+        #[arg(long)]
+        pixels: bool,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+    /// List the chunks that match a type, property or content pattern
+    Find {
+        /// PNG files, directories or glob patterns
+        #[arg(required = true)]
+        input: Vec<String>,
+        /// Chunk types to match, with ? for one letter and * for any, e.g. ru*,tEXt
+        #[arg(short = 't', long = "type", value_delimiter = ',')]
+        types: Vec<String>,
+        /// Regular expression to search in chunk data and decoded text
+        #[arg(short = 'e', long)]
+        text: Option<String>,
+        /// Only critical chunks
+        #[arg(long, conflicts_with = "ancillary")]
+        critical: bool,
+        /// Only ancillary chunks
+        #[arg(long)]
+        ancillary: bool,
+        /// Only public chunks
+        #[arg(long, conflicts_with = "private")]
+        public: bool,
+        /// Only private chunks
+        #[arg(long)]
+        private: bool,
+        /// Only safe-to-copy chunks
+        #[arg(long, conflicts_with = "unsafe_to_copy")]
+        safe_to_copy: bool,
+        /// Only unsafe-to-copy chunks
+        #[arg(long)]
+        unsafe_to_copy: bool,
+        #[command(flatten)]
+        batch: BatchArgs,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExifAction {
+    /// List all tags, or print the value of one tag
+    Get {
+        #[arg(short, long)]
+        url: bool,
+        input: String,
+        /// Tag as `[ifd:]tag`, e.g. Make, gps:GPSLatitude or exif:0x9003
+        tag: Option<String>,
+    },
+    /// Set a tag, creating the eXIf chunk if needed
+    Set {
+        input: PathBuf,
+        tag: String,
+        value: String,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    /// Remove tags, or whole IFDs such as `gps`
+    Remove {
+        input: PathBuf,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum XmpAction {
+    /// Print the XMP packet, or write it to a file
+    Extract {
+        #[arg(short, long)]
+        url: bool,
+        input: String,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    /// Replace the XMP packet with the contents of a file
+    Replace {
+        input: PathBuf,
+        packet: PathBuf,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+    /// Print the value of a property such as dc:rights
+    Get {
+        #[arg(short, long)]
+        url: bool,
+        input: String,
+        property: String,
+    },
+    /// Set a property such as dc:creator or dc:rights, creating the packet if needed
+    Set {
+        input: PathBuf,
+        property: String,
+        value: String,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum IccAction {
+    /// Write the decompressed ICC profile to a file
+    Extract {
+        #[arg(short, long)]
+        url: bool,
+        input: String,
+        output: PathBuf,
+    },
+    /// Embed an ICC profile file, replacing any sRGB chunk
+    Embed {
+        input: PathBuf,
+        profile: PathBuf,
+        /// Profile name stored in the chunk; defaults to the profile file name
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(short, long)]
+        output_file: Option<PathBuf>,
     },
 }
@@ -0,0 +1,86 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replaces the contents of `path` so that it holds either the old or the new
+/// contents even if the process dies or the disk fills up halfway.
+///
+/// The data goes to a temporary file in the same directory, is flushed to disk,
+/// and is then renamed over `path`. An existing file keeps its permissions.
+pub fn write(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temp = temp_path(path)?;
+    let result = write_synced(&temp, path, contents).and_then(|_| fs::rename(&temp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+        return result;
+    }
+
+    // Persist the rename itself. Not every platform can open a directory, so
+    // failures here are ignored: the file contents are already safe.
+    if let Ok(dir) = File::open(directory(path)) {
+        let _ = dir.sync_all();
+    }
+    Ok(())
+}
+
+fn write_synced(temp: &Path, target: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(temp)?;
+    file.write_all(contents)?;
+    if let Ok(metadata) = fs::metadata(target) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    file.sync_all()
+}
+
+fn directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn temp_path(path: &Path) -> io::Result<PathBuf> {
+    let name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?} is not a file path", path),
+        )
+    })?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", std::process::id()));
+    Ok(directory(path).join(temp_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_replaces_contents() {
+        let dir = std::env::temp_dir().join(format!("pngme-atomic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("image.png");
+
+        write(&path, b"first").unwrap();
+        write(&path, b"second").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+
+        let leftovers: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(leftovers, vec!["image.png"]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_failure_leaves_no_temp_file() {
+        let path = std::env::temp_dir()
+            .join(format!("pngme-missing-{}", std::process::id()))
+            .join("image.png");
+        assert!(write(&path, b"data").is_err());
+        assert!(!temp_path(&path).unwrap().exists());
+    }
+}
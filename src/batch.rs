@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Whether `inputs` names more than a single file, so results need per-file reporting.
+pub fn is_batch(inputs: &[String]) -> bool {
+    match inputs {
+        [input] => is_pattern(input) || Path::new(input).is_dir(),
+        _ => true,
+    }
+}
+
+/// Whether `input` is a glob pattern. A file that exists is taken literally,
+/// even if its name contains pattern characters such as `[`.
+fn is_pattern(input: &str) -> bool {
+    input.contains(['*', '?', '[']) && !Path::new(input).exists()
+}
+
+/// Expands file paths, directories and glob patterns into a list of files.
+///
+/// Directories contribute the `.png` files they contain, including those in
+/// subdirectories when `recursive` is set. Plain paths are kept as given even
+/// if they do not exist, so the caller reports them as failures. Each file is
+/// listed once, in the order it was first found.
+pub fn expand(inputs: &[String], recursive: bool) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for input in inputs {
+        if is_pattern(input) {
+            let entries =
+                glob::glob(input).map_err(|e| format!("Invalid pattern '{}': {}", input, e))?;
+            let mut matched = Vec::new();
+            for entry in entries {
+                let path = entry.map_err(|e| e.to_string())?;
+                if path.is_file() {
+                    matched.push(path);
+                }
+            }
+            if matched.is_empty() {
+                return Err(format!("Pattern '{}' matched no files", input));
+            }
+            files.extend(matched);
+        } else if Path::new(input).is_dir() {
+            collect_pngs(Path::new(input), recursive, &mut files)?;
+        } else {
+            files.push(PathBuf::from(input));
+        }
+    }
+
+    let mut seen = HashSet::new();
+    files.retain(|path| seen.insert(path.clone()));
+    Ok(files)
+}
+
+fn collect_pngs(dir: &Path, recursive: bool, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect())
+        .map_err(|e| format!("Failed to read directory {:?}: {}", dir, e))?;
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            if recursive {
+                collect_pngs(&path, recursive, files)?;
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Runs `task` for every item, such as a file path, on up to `jobs` worker threads.
+///
+/// Each task writes its output to its own buffer, and `report` is called on the
+/// calling thread with the item, that output and the task's result as soon as
+/// the task finishes, so output of different items is never interleaved.
+pub fn run<I, T, F, R>(items: &[I], jobs: usize, task: F, mut report: R)
+where
+    I: Sync,
+    T: Send,
+    F: Fn(&I, &mut Vec<u8>) -> T + Sync,
+    R: FnMut(&I, Vec<u8>, T),
+{
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            let sender = sender.clone();
+            let (next, task) = (&next, &task);
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let mut output = Vec::new();
+                let result = task(item, &mut output);
+                if sender.send((index, output, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (index, output, result) in receiver {
+            report(&items[index], output, result);
+        }
+    });
+}
+
+/// Number of worker threads to use when none is requested.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_tree() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("pngme-batch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub")).unwrap();
+        for name in ["b.png", "a.PNG", "notes.txt", "sub/c.png"] {
+            fs::write(root.join(name), b"").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_expand() {
+        let root = temp_tree();
+        let dir = root.to_string_lossy().to_string();
+
+        let files = expand(std::slice::from_ref(&dir), false).unwrap();
+        assert_eq!(files, vec![root.join("a.PNG"), root.join("b.png")]);
+
+        let files = expand(&[dir.clone(), format!("{}/b.png", dir)], true).unwrap();
+        assert_eq!(
+            files,
+            vec![
+                root.join("a.PNG"),
+                root.join("b.png"),
+                root.join("sub/c.png")
+            ]
+        );
+
+        let files = expand(&[format!("{}/*/*.png", dir)], false).unwrap();
+        assert_eq!(files, vec![root.join("sub/c.png")]);
+        assert!(expand(&[format!("{}/*.gif", dir)], false).is_err());
+
+        assert!(is_batch(std::slice::from_ref(&dir)));
+        assert!(!is_batch(&[format!("{}/b.png", dir)]));
+        assert!(is_batch(&["x.png".to_string(), "y.png".to_string()]));
+
+        let literal = root.join("img[1].png");
+        fs::write(&literal, b"").unwrap();
+        let name = literal.to_string_lossy().to_string();
+        assert_eq!(
+            expand(std::slice::from_ref(&name), false).unwrap(),
+            vec![literal]
+        );
+        assert!(!is_batch(&[name]));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_run_reports_every_path() {
+        let paths: Vec<PathBuf> = (0..20).map(|i| PathBuf::from(format!("{}", i))).collect();
+        let mut reported = Vec::new();
+        run(
+            &paths,
+            4,
+            |path, output| {
+                write!(output, "{}", path.display()).unwrap();
+                path.to_str().unwrap().parse::<u32>().unwrap()
+            },
+            |path, output, value| {
+                assert_eq!(output, path.to_str().unwrap().as_bytes());
+                reported.push(value);
+            },
+        );
+        reported.sort();
+        assert_eq!(reported, (0..20).collect::<Vec<_>>());
+    }
+}
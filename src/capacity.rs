@@ -0,0 +1,231 @@
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::crypto::EncryptedData;
+use crate::ihdr::ColorType;
+use crate::palette_stego;
+use crate::png::Png;
+
+/// Largest data length a single chunk may declare, per the PNG specification.
+pub const MAX_CHUNK_LENGTH: u64 = (1 << 31) - 1;
+
+/// Largest payload a trailer record can hold, as its length prefix is a u32.
+pub const MAX_TRAILER_LENGTH: u64 = u32::MAX as u64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Capacity {
+    Bytes(u64),
+    Unlimited,
+    Unavailable(String),
+}
+
+impl Capacity {
+    fn encrypted(&self) -> Capacity {
+        match self {
+            Capacity::Bytes(bytes) => {
+                Capacity::Bytes(EncryptedData::max_plaintext_len(*bytes as usize) as u64)
+            }
+            other => other.clone(),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Capacity::Bytes(bytes) => json!(bytes),
+            Capacity::Unlimited => json!("unlimited"),
+            Capacity::Unavailable(_) => Value::Null,
+        }
+    }
+}
+
+impl fmt::Display for Capacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Capacity::Bytes(bytes) => write!(f, "{} bytes", bytes),
+            Capacity::Unlimited => write!(f, "unlimited"),
+            Capacity::Unavailable(reason) => write!(f, "n/a ({})", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MethodCapacity {
+    pub method: String,
+    pub plain: Capacity,
+    pub encrypted: Capacity,
+}
+
+/// Maximum payload size per embedding method for one image, before and after encryption.
+///
+/// Capacities are net of each method's envelope: the palette method spends one
+/// byte of the ordering on the payload length, and a trailer record's u32 length
+/// prefix caps its payload. Split chunks have no limit, as a payload can be
+/// spread over any number of chunks. LSB capacities count every sample of
+/// every pixel at 1 to 4 bits each, where the bit depth allows. Encrypted capacities also subtract the salt, nonce and
+/// tag and the base64 expansion of the encrypted form. Payloads are stored
+/// uncompressed, so there is no compression overhead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapacityReport {
+    pub methods: Vec<MethodCapacity>,
+}
+
+impl CapacityReport {
+    pub fn for_png(png: &Png) -> Result<Self, String> {
+        let ihdr = png.ihdr()?;
+        let mut methods = Vec::new();
+        let mut push = |method: &str, plain: Capacity| {
+            methods.push(MethodCapacity {
+                method: method.to_string(),
+                encrypted: plain.encrypted(),
+                plain,
+            });
+        };
+
+        push("chunk", Capacity::Bytes(MAX_CHUNK_LENGTH));
+        push("split-chunks", Capacity::Unlimited);
+
+        let samples = ihdr.width as u64 * ihdr.height as u64 * ihdr.color_type.channels() as u64;
+        for bits in 1..=4u64 {
+            let capacity = if ihdr.color_type == ColorType::Indexed {
+                Capacity::Unavailable("indexed image".to_string())
+            } else if bits > ihdr.bit_depth as u64 {
+                Capacity::Unavailable(format!("bit depth is {}", ihdr.bit_depth))
+            } else {
+                Capacity::Bytes(samples * bits / 8)
+            };
+            push(&format!("lsb{}", bits), capacity);
+        }
+
+        let palette = match png.palette()? {
+            Some(palette) if ihdr.color_type == ColorType::Indexed => {
+                Capacity::Bytes(palette_stego::capacity(palette.len()) as u64)
+            }
+            _ => Capacity::Unavailable("not an indexed image".to_string()),
+        };
+        push("palette", palette);
+        push("trailer", Capacity::Bytes(MAX_TRAILER_LENGTH));
+
+        Ok(CapacityReport { methods })
+    }
+
+    pub fn to_value(&self) -> Value {
+        let methods: Vec<Value> = self
+            .methods
+            .iter()
+            .map(|m| {
+                json!({
+                    "method": m.method,
+                    "plain": m.plain.to_value(),
+                    "encrypted": m.encrypted.to_value(),
+                })
+            })
+            .collect();
+        json!({ "methods": methods })
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+}
+
+impl fmt::Display for CapacityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<14} {:<28} Encrypted", "Method", "Plain")?;
+        for m in &self.methods {
+            writeln!(
+                f,
+                "{:<14} {:<28} {}",
+                m.method,
+                m.plain.to_string(),
+                m.encrypted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::ihdr::Ihdr;
+    use std::str::FromStr;
+
+    fn png_with_header(color_type: ColorType, bit_depth: u8) -> Png {
+        let ihdr = Ihdr {
+            width: 10,
+            height: 10,
+            bit_depth,
+            color_type,
+            interlace: false,
+        };
+        let mut chunks = vec![Chunk::new(
+            ChunkType::from_str("IHDR").unwrap(),
+            ihdr.as_bytes(),
+        )];
+        if color_type == ColorType::Indexed {
+            let palette = (0..=255).flat_map(|i| [i, i, i]).collect();
+            chunks.push(Chunk::new(ChunkType::from_str("PLTE").unwrap(), palette));
+        }
+        Png::from_chunks(chunks)
+    }
+
+    fn capacity_of<'a>(report: &'a CapacityReport, method: &str) -> &'a MethodCapacity {
+        report.methods.iter().find(|m| m.method == method).unwrap()
+    }
+
+    #[test]
+    fn test_rgba_capacity() {
+        let report = CapacityReport::for_png(&png_with_header(ColorType::Rgba, 8)).unwrap();
+        assert_eq!(
+            capacity_of(&report, "chunk").plain,
+            Capacity::Bytes(MAX_CHUNK_LENGTH)
+        );
+        assert_eq!(
+            capacity_of(&report, "trailer").plain,
+            Capacity::Bytes(u32::MAX as u64)
+        );
+        assert!(matches!(
+            capacity_of(&report, "palette").plain,
+            Capacity::Unavailable(_)
+        ));
+        assert_eq!(
+            capacity_of(&report, "split-chunks").plain,
+            Capacity::Unlimited
+        );
+        assert_eq!(capacity_of(&report, "lsb1").plain, Capacity::Bytes(50));
+        assert_eq!(capacity_of(&report, "lsb4").plain, Capacity::Bytes(200));
+        assert_eq!(capacity_of(&report, "lsb1").encrypted, Capacity::Bytes(0));
+        assert_eq!(capacity_of(&report, "lsb4").encrypted, Capacity::Bytes(90));
+    }
+
+    #[test]
+    fn test_indexed_capacity() {
+        let report = CapacityReport::for_png(&png_with_header(ColorType::Indexed, 8)).unwrap();
+        let palette = capacity_of(&report, "palette");
+        assert_eq!(palette.plain, Capacity::Bytes(209));
+        // 209 bytes of base64 hold 156 raw bytes, 60 of them salt, nonce and tag.
+        assert_eq!(palette.encrypted, Capacity::Bytes(96));
+        assert_eq!(EncryptedData::encoded_len(96), 208);
+        assert!(matches!(
+            capacity_of(&report, "lsb1").plain,
+            Capacity::Unavailable(_)
+        ));
+    }
+
+    #[test]
+    fn test_json_output() {
+        let report = CapacityReport::for_png(&png_with_header(ColorType::Grayscale, 2)).unwrap();
+        let json: Value = serde_json::from_str(&report.to_json()).unwrap();
+        let methods = json["methods"].as_array().unwrap();
+        let entry = |method: &str| methods.iter().find(|m| m["method"] == method).unwrap();
+        assert_eq!(entry("lsb2")["plain"], 25);
+        assert_eq!(entry("lsb2")["encrypted"], 0);
+        assert!(entry("lsb3")["plain"].is_null());
+        assert_eq!(entry("split-chunks")["plain"], "unlimited");
+        assert!(entry("palette")["encrypted"].is_null());
+        assert_eq!(entry("trailer")["plain"], 4294967295u64);
+    }
+}
@@ -6,6 +6,16 @@ pub struct ChunkType {
 }
 
 impl ChunkType {
+    /// Starts building a private, ancillary, unsafe-to-copy type from four letters.
+    pub fn private_ancillary(letters: &str) -> ChunkTypeBuilder {
+        ChunkTypeBuilder {
+            letters: letters.to_string(),
+            critical: false,
+            public: false,
+            safe_to_copy: false,
+        }
+    }
+
     pub fn bytes(&self) -> [u8; 4] {
         self.bytes
     }
@@ -29,6 +39,73 @@ impl ChunkType {
     pub fn is_valid(&self) -> bool {
         self.bytes.iter().all(|b| b.is_ascii_alphabetic()) && self.is_reserved_bit_valid()
     }
+
+    /// Whether the PNG specification allows at most one chunk of this type per file.
+    /// Types the specification does not define are never considered unique.
+    pub fn is_unique(&self) -> bool {
+        const UNIQUE: [&[u8; 4]; 18] = [
+            b"IHDR", b"PLTE", b"IEND", b"cHRM", b"cICP", b"gAMA", b"iCCP", b"mDCV", b"cLLI",
+            b"sBIT", b"sRGB", b"bKGD", b"hIST", b"tRNS", b"eXIf", b"pHYs", b"tIME", b"acTL",
+        ];
+        UNIQUE.contains(&&self.bytes)
+    }
+}
+
+/// Builds a chunk type from its property flags instead of exact letter case.
+///
+/// Case is applied to the given letters, so `private_ancillary("rust")` and
+/// `private_ancillary("RUST")` give the same type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkTypeBuilder {
+    letters: String,
+    critical: bool,
+    public: bool,
+    safe_to_copy: bool,
+}
+
+impl ChunkTypeBuilder {
+    pub fn critical(mut self, critical: bool) -> Self {
+        self.critical = critical;
+        self
+    }
+
+    pub fn public(mut self, public: bool) -> Self {
+        self.public = public;
+        self
+    }
+
+    pub fn safe_to_copy(mut self, safe_to_copy: bool) -> Self {
+        self.safe_to_copy = safe_to_copy;
+        self
+    }
+
+    pub fn build(&self) -> Result<ChunkType, String> {
+        let mut bytes: [u8; 4] = self
+            .letters
+            .as_bytes()
+            .try_into()
+            .map_err(|_| "Chunk type must be exactly 4 letters".to_string())?;
+        if !bytes.iter().all(|b| b.is_ascii_alphabetic()) {
+            return Err(format!(
+                "Chunk type '{}' must contain only letters",
+                self.letters
+            ));
+        }
+        // Lowercase sets bit 5, which marks ancillary, private and safe-to-copy.
+        // The reserved third letter is always uppercase.
+        let set_case = |b: u8, lower: bool| {
+            if lower {
+                b.to_ascii_lowercase()
+            } else {
+                b.to_ascii_uppercase()
+            }
+        };
+        bytes[0] = set_case(bytes[0], !self.critical);
+        bytes[1] = set_case(bytes[1], !self.public);
+        bytes[2] = set_case(bytes[2], false);
+        bytes[3] = set_case(bytes[3], self.safe_to_copy);
+        Ok(ChunkType { bytes })
+    }
 }
 
 impl TryFrom<[u8; 4]> for ChunkType {
@@ -145,6 +222,35 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    pub fn test_chunk_type_is_unique() {
+        assert!(ChunkType::from_str("IHDR").unwrap().is_unique());
+        assert!(ChunkType::from_str("gAMA").unwrap().is_unique());
+        assert!(!ChunkType::from_str("IDAT").unwrap().is_unique());
+        assert!(!ChunkType::from_str("tEXt").unwrap().is_unique());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_unique());
+    }
+
+    #[test]
+    pub fn test_chunk_type_builder() {
+        let chunk = ChunkType::private_ancillary("rust")
+            .safe_to_copy(true)
+            .build()
+            .unwrap();
+        assert_eq!(&chunk.to_string(), "ruSt");
+        assert!(chunk.is_valid());
+
+        let chunk = ChunkType::private_ancillary("rust")
+            .critical(true)
+            .public(true)
+            .build()
+            .unwrap();
+        assert_eq!(&chunk.to_string(), "RUST");
+
+        assert!(ChunkType::private_ancillary("ru5t").build().is_err());
+        assert!(ChunkType::private_ancillary("rusty").build().is_err());
+    }
+
     #[test]
     pub fn test_chunk_type_string() {
         let chunk = ChunkType::from_str("RuSt").unwrap();
@@ -158,4 +264,4 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
-}
\ No newline at end of file
+}
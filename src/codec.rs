@@ -0,0 +1,641 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use serde_json::{json, Value};
+
+use crate::ancillary::{
+    Background, Histogram, PhysicalDimensions, SignificantBits, SuggestedPalette, Time,
+};
+use crate::apng::{AnimationControl, FrameControl};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::color::{Chromaticities, CodePoints, Gamma, IccProfile, RenderingIntent};
+use crate::exif::Exif;
+use crate::palette::{Palette, Transparency};
+use crate::png::Png;
+use crate::text::{TextChunk, TextKind};
+
+/// Converts the data of one chunk type to and from a typed value.
+///
+/// Register an implementation with a [`CodecRegistry`] to have `print`, JSON
+/// output and validation understand a custom chunk type.
+pub trait ChunkCodec: Send + Sync {
+    type Value;
+
+    /// The chunk type this codec handles.
+    fn chunk_type(&self) -> ChunkType;
+
+    /// Human-readable label, e.g. "Gamma".
+    fn name(&self) -> &str;
+
+    fn decode(&self, data: &[u8]) -> Result<Self::Value, String>;
+
+    /// Decodes the data of a chunk of `png`, for chunk types whose layout
+    /// depends on other chunks such as IHDR. Defaults to `decode`.
+    fn decode_in(&self, data: &[u8], _png: &Png) -> Result<Self::Value, String> {
+        self.decode(data)
+    }
+
+    fn encode(&self, value: &Self::Value) -> Result<Vec<u8>, String>;
+
+    fn display(&self, value: &Self::Value) -> String;
+
+    /// JSON representation of a value; a JSON string of `display` by default.
+    fn to_json(&self, value: &Self::Value) -> Value {
+        Value::String(self.display(value))
+    }
+
+    /// Problems with a value that decoded successfully.
+    fn validate(&self, _value: &Self::Value) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn to_chunk(&self, value: &Self::Value) -> Result<Chunk, String> {
+        Ok(Chunk::new(self.chunk_type(), self.encode(value)?))
+    }
+}
+
+/// Object-safe view of a codec, used to store codecs with different value types.
+/// `png` is the file the chunk belongs to, when there is one.
+trait DynCodec: Send + Sync {
+    fn name(&self) -> &str;
+    fn display(&self, data: &[u8], png: Option<&Png>) -> Result<String, String>;
+    fn to_json(&self, data: &[u8], png: Option<&Png>) -> Result<Value, String>;
+    fn validate(&self, data: &[u8], png: Option<&Png>) -> Vec<String>;
+}
+
+impl<C: ChunkCodec> DynCodec for C {
+    fn name(&self) -> &str {
+        ChunkCodec::name(self)
+    }
+
+    fn display(&self, data: &[u8], png: Option<&Png>) -> Result<String, String> {
+        decode(self, data, png).map(|value| ChunkCodec::display(self, &value))
+    }
+
+    fn to_json(&self, data: &[u8], png: Option<&Png>) -> Result<Value, String> {
+        decode(self, data, png).map(|value| ChunkCodec::to_json(self, &value))
+    }
+
+    fn validate(&self, data: &[u8], png: Option<&Png>) -> Vec<String> {
+        match decode(self, data, png) {
+            Ok(value) => ChunkCodec::validate(self, &value),
+            Err(e) => vec![e],
+        }
+    }
+}
+
+fn decode<C: ChunkCodec>(codec: &C, data: &[u8], png: Option<&Png>) -> Result<C::Value, String> {
+    match png {
+        Some(png) => codec.decode_in(data, png),
+        None => codec.decode(data),
+    }
+}
+
+/// Codec for a type that already parses itself from chunk data and implements `Display`.
+pub struct StandardCodec<T> {
+    chunk_type: &'static str,
+    name: &'static str,
+    encode: fn(&T) -> Result<Vec<u8>, String>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> StandardCodec<T> {
+    pub fn new(
+        chunk_type: &'static str,
+        name: &'static str,
+        encode: fn(&T) -> Result<Vec<u8>, String>,
+    ) -> Self {
+        StandardCodec {
+            chunk_type,
+            name,
+            encode,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T> ChunkCodec for StandardCodec<T>
+where
+    T: for<'a> TryFrom<&'a [u8], Error = String> + fmt::Display,
+{
+    type Value = T;
+
+    fn chunk_type(&self) -> ChunkType {
+        ChunkType::from_str(self.chunk_type).expect("standard chunk types are valid")
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, String> {
+        T::try_from(data)
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, String> {
+        (self.encode)(value)
+    }
+
+    fn display(&self, value: &T) -> String {
+        value.to_string()
+    }
+}
+
+/// Codec for tEXt, zTXt and iTXt chunks.
+pub struct TextCodec(pub TextKind);
+
+impl ChunkCodec for TextCodec {
+    type Value = TextChunk;
+
+    fn chunk_type(&self) -> ChunkType {
+        ChunkType::from_str(self.0.chunk_type()).expect("text chunk types are valid")
+    }
+
+    fn name(&self) -> &str {
+        "Text"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<TextChunk, String> {
+        TextChunk::try_from(&Chunk::new(self.chunk_type(), data.to_vec()))
+    }
+
+    fn encode(&self, value: &TextChunk) -> Result<Vec<u8>, String> {
+        value.to_chunk().map(|chunk| chunk.data().to_vec())
+    }
+
+    fn display(&self, value: &TextChunk) -> String {
+        format!("{} = {}", value.keyword, value.text)
+    }
+
+    fn to_json(&self, value: &TextChunk) -> Value {
+        json!({ "keyword": value.keyword, "text": value.text })
+    }
+}
+
+/// Codec for eXIf chunks.
+pub struct ExifCodec;
+
+impl ChunkCodec for ExifCodec {
+    type Value = Exif;
+
+    fn chunk_type(&self) -> ChunkType {
+        ChunkType::from_str("eXIf").expect("eXIf is a valid chunk type")
+    }
+
+    fn name(&self) -> &str {
+        "EXIF"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Exif, String> {
+        Exif::try_from(data)
+    }
+
+    fn encode(&self, value: &Exif) -> Result<Vec<u8>, String> {
+        Ok(value.as_bytes())
+    }
+
+    fn display(&self, value: &Exif) -> String {
+        // The Display heading names EXIF again, which the label already does.
+        let listing = value.to_string();
+        let entries = listing.lines().skip(1).collect::<Vec<_>>();
+        let mut text = value.byte_order.to_string();
+        for entry in entries {
+            text.push('\n');
+            text.push_str(entry);
+        }
+        text
+    }
+}
+
+/// Codec for PLTE chunks; JSON lists the entries as `#rrggbb` strings.
+pub struct PaletteCodec;
+
+impl ChunkCodec for PaletteCodec {
+    type Value = Palette;
+
+    fn chunk_type(&self) -> ChunkType {
+        ChunkType::from_str("PLTE").expect("PLTE is a valid chunk type")
+    }
+
+    fn name(&self) -> &str {
+        "Palette"
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Palette, String> {
+        Palette::try_from(data)
+    }
+
+    fn decode_in(&self, data: &[u8], png: &Png) -> Result<Palette, String> {
+        let palette = Palette::try_from(data)?;
+        palette.validate(&png.ihdr()?)?;
+        Ok(palette)
+    }
+
+    fn encode(&self, value: &Palette) -> Result<Vec<u8>, String> {
+        Ok(value.as_bytes())
+    }
+
+    fn display(&self, value: &Palette) -> String {
+        // The Display heading names the palette again, which the label already does.
+        let listing = value.to_string();
+        let mut text = format!("{} entries", value.len());
+        for entry in listing.lines().skip(1) {
+            text.push('\n');
+            text.push_str(entry);
+        }
+        text
+    }
+
+    fn to_json(&self, value: &Palette) -> Value {
+        let entries: Vec<String> = value
+            .entries()
+            .iter()
+            .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+            .collect();
+        json!(entries)
+    }
+}
+
+/// Codec for a chunk whose layout depends on IHDR or PLTE, such as bKGD. Its
+/// data can only be decoded as part of a file.
+pub struct ImageCodec<T> {
+    chunk_type: &'static str,
+    name: &'static str,
+    parse: fn(&[u8], &Png) -> Result<T, String>,
+    encode: fn(&T) -> Vec<u8>,
+}
+
+impl<T> ImageCodec<T> {
+    pub fn new(
+        chunk_type: &'static str,
+        name: &'static str,
+        parse: fn(&[u8], &Png) -> Result<T, String>,
+        encode: fn(&T) -> Vec<u8>,
+    ) -> Self {
+        ImageCodec {
+            chunk_type,
+            name,
+            parse,
+            encode,
+        }
+    }
+}
+
+impl<T: fmt::Display> ChunkCodec for ImageCodec<T> {
+    type Value = T;
+
+    fn chunk_type(&self) -> ChunkType {
+        ChunkType::from_str(self.chunk_type).expect("standard chunk types are valid")
+    }
+
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn decode(&self, _data: &[u8]) -> Result<T, String> {
+        Err(format!(
+            "{} can only be decoded together with the IHDR chunk",
+            self.chunk_type
+        ))
+    }
+
+    fn decode_in(&self, data: &[u8], png: &Png) -> Result<T, String> {
+        (self.parse)(data, png)
+    }
+
+    fn encode(&self, value: &T) -> Result<Vec<u8>, String> {
+        Ok((self.encode)(value))
+    }
+
+    fn display(&self, value: &T) -> String {
+        value.to_string()
+    }
+}
+
+/// Codecs keyed by chunk type. `print`, JSON output and validation consult the
+/// registry for every chunk, so registering a codec is all a new type needs.
+#[derive(Default)]
+pub struct CodecRegistry {
+    codecs: HashMap<[u8; 4], Box<dyn DynCodec>>,
+}
+
+impl CodecRegistry {
+    /// An empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with codecs for PLTE, the standard ancillary chunks and the
+    /// APNG control chunks.
+    pub fn standard() -> Self {
+        let mut registry = Self::new();
+        registry.register(PaletteCodec);
+        registry.register(ImageCodec::new(
+            "tRNS",
+            "Transparency",
+            |data, png| Transparency::parse(data, &png.ihdr()?, png.palette()?.as_ref()),
+            Transparency::as_bytes,
+        ));
+        registry.register(ImageCodec::new(
+            "bKGD",
+            "Background",
+            |data, png| Background::parse(data, &png.ihdr()?, png.palette()?.as_ref()),
+            Background::as_bytes,
+        ));
+        registry.register(ImageCodec::new(
+            "sBIT",
+            "Precision",
+            |data, png| SignificantBits::parse(data, &png.ihdr()?),
+            SignificantBits::as_bytes,
+        ));
+        registry.register(ImageCodec::new(
+            "hIST",
+            "Histogram",
+            |data, png| {
+                let palette = png
+                    .palette()?
+                    .ok_or_else(|| "hIST chunk without a PLTE chunk".to_string())?;
+                Histogram::parse(data, &palette)
+            },
+            Histogram::as_bytes,
+        ));
+        registry.register(StandardCodec::new("gAMA", "Gamma", |v: &Gamma| {
+            Ok(v.as_bytes())
+        }));
+        registry.register(StandardCodec::new(
+            "cHRM",
+            "Chromaticities",
+            |v: &Chromaticities| Ok(v.as_bytes()),
+        ));
+        registry.register(StandardCodec::new(
+            "sRGB",
+            "sRGB rendering intent",
+            |v: &RenderingIntent| Ok(v.as_bytes()),
+        ));
+        registry.register(StandardCodec::new(
+            "iCCP",
+            "ICC profile",
+            IccProfile::as_bytes,
+        ));
+        registry.register(StandardCodec::new("cICP", "cICP", |v: &CodePoints| {
+            Ok(v.as_bytes())
+        }));
+        registry.register(StandardCodec::new(
+            "pHYs",
+            "Resolution",
+            |v: &PhysicalDimensions| Ok(v.as_bytes()),
+        ));
+        registry.register(StandardCodec::new("tIME", "Last modified", |v: &Time| {
+            Ok(v.as_bytes())
+        }));
+        registry.register(StandardCodec::new(
+            "sPLT",
+            "Suggested palette",
+            SuggestedPalette::as_bytes,
+        ));
+        registry.register(ExifCodec);
+        registry.register(StandardCodec::new(
+            "acTL",
+            "Animation",
+            |v: &AnimationControl| Ok(v.as_bytes()),
+        ));
+        registry.register(StandardCodec::new("fcTL", "Frame", |v: &FrameControl| {
+            Ok(v.as_bytes())
+        }));
+        for kind in [
+            TextKind::Text,
+            TextKind::Compressed,
+            TextKind::International,
+        ] {
+            registry.register(TextCodec(kind));
+        }
+        registry
+    }
+
+    /// Adds a codec, replacing any codec already registered for its chunk type.
+    pub fn register<C: ChunkCodec + 'static>(&mut self, codec: C) {
+        self.codecs
+            .insert(codec.chunk_type().bytes(), Box::new(codec));
+    }
+
+    pub fn contains(&self, chunk_type: &ChunkType) -> bool {
+        self.codecs.contains_key(&chunk_type.bytes())
+    }
+
+    pub fn name(&self, chunk_type: &ChunkType) -> Option<&str> {
+        self.codec(chunk_type).map(|codec| codec.name())
+    }
+
+    /// Decodes and formats a chunk; `None` when no codec handles its type.
+    pub fn display(&self, chunk: &Chunk) -> Option<Result<String, String>> {
+        self.codec(chunk.chunk_type())
+            .map(|codec| codec.display(chunk.data(), None))
+    }
+
+    /// Like [`display`](Self::display), for a chunk of `png`, so that chunks
+    /// whose layout depends on IHDR or PLTE can be decoded too.
+    pub fn display_in(&self, chunk: &Chunk, png: &Png) -> Option<Result<String, String>> {
+        self.codec(chunk.chunk_type())
+            .map(|codec| codec.display(chunk.data(), Some(png)))
+    }
+
+    /// Decodes a chunk into JSON; `None` when no codec handles its type.
+    pub fn to_json(&self, chunk: &Chunk) -> Option<Result<Value, String>> {
+        self.codec(chunk.chunk_type())
+            .map(|codec| codec.to_json(chunk.data(), None))
+    }
+
+    /// Like [`to_json`](Self::to_json), for a chunk of `png`.
+    pub fn to_json_in(&self, chunk: &Chunk, png: &Png) -> Option<Result<Value, String>> {
+        self.codec(chunk.chunk_type())
+            .map(|codec| codec.to_json(chunk.data(), Some(png)))
+    }
+
+    /// Problems with a chunk's data, including failure to decode it.
+    pub fn validate(&self, chunk: &Chunk) -> Vec<String> {
+        self.codec(chunk.chunk_type())
+            .map(|codec| codec.validate(chunk.data(), None))
+            .unwrap_or_default()
+    }
+
+    /// Like [`validate`](Self::validate), for a chunk of `png`.
+    pub fn validate_in(&self, chunk: &Chunk, png: &Png) -> Vec<String> {
+        self.codec(chunk.chunk_type())
+            .map(|codec| codec.validate(chunk.data(), Some(png)))
+            .unwrap_or_default()
+    }
+
+    /// Describes every chunk of `png` as a JSON document, with decoded values
+    /// for the chunk types this registry knows.
+    pub fn png_to_value(&self, png: &Png) -> Value {
+        let chunks: Vec<Value> = png
+            .chunks()
+            .iter()
+            .map(|chunk| {
+                let mut entry = json!({
+                    "type": chunk.chunk_type().to_string(),
+                    "length": chunk.length(),
+                    "crc": chunk.crc(),
+                    "value": null,
+                });
+                match self.to_json_in(chunk, png) {
+                    Some(Ok(value)) => entry["value"] = value,
+                    Some(Err(e)) => entry["error"] = json!(e),
+                    None => {}
+                }
+                entry
+            })
+            .collect();
+        json!({ "chunks": chunks, "trailer": png.trailer().len() })
+    }
+
+    pub fn png_to_json(&self, png: &Png) -> String {
+        self.png_to_value(png).to_string()
+    }
+
+    fn codec(&self, chunk_type: &ChunkType) -> Option<&dyn DynCodec> {
+        self.codecs.get(&chunk_type.bytes()).map(|codec| &**codec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A private chunk holding a version byte and a big-endian u16 counter.
+    struct CounterCodec;
+
+    impl ChunkCodec for CounterCodec {
+        type Value = (u8, u16);
+
+        fn chunk_type(&self) -> ChunkType {
+            ChunkType::from_str("ruSt").unwrap()
+        }
+
+        fn name(&self) -> &str {
+            "Counter"
+        }
+
+        fn decode(&self, data: &[u8]) -> Result<(u8, u16), String> {
+            match data {
+                &[version, hi, lo] => Ok((version, u16::from_be_bytes([hi, lo]))),
+                _ => Err(format!("ruSt data must be 3 bytes, got {}", data.len())),
+            }
+        }
+
+        fn encode(&self, value: &(u8, u16)) -> Result<Vec<u8>, String> {
+            let [hi, lo] = value.1.to_be_bytes();
+            Ok(vec![value.0, hi, lo])
+        }
+
+        fn display(&self, value: &(u8, u16)) -> String {
+            format!("v{} count {}", value.0, value.1)
+        }
+
+        fn to_json(&self, value: &(u8, u16)) -> Value {
+            json!({ "version": value.0, "count": value.1 })
+        }
+
+        fn validate(&self, value: &(u8, u16)) -> Vec<String> {
+            match value.0 {
+                1 => Vec::new(),
+                version => vec![format!("Unsupported ruSt version {}", version)],
+            }
+        }
+    }
+
+    #[test]
+    fn test_custom_codec() {
+        let mut registry = CodecRegistry::standard();
+        let chunk = CounterCodec.to_chunk(&(1, 258)).unwrap();
+        assert_eq!(chunk.data(), &[1, 1, 2]);
+        assert!(registry.display(&chunk).is_none());
+
+        registry.register(CounterCodec);
+        assert_eq!(registry.name(chunk.chunk_type()), Some("Counter"));
+        assert_eq!(registry.display(&chunk).unwrap().unwrap(), "v1 count 258");
+        assert_eq!(
+            registry.to_json(&chunk).unwrap().unwrap(),
+            json!({ "version": 1, "count": 258 })
+        );
+        assert!(registry.validate(&chunk).is_empty());
+
+        let bad = Chunk::new(chunk.chunk_type().clone(), vec![2, 0, 0]);
+        assert_eq!(registry.validate(&bad), vec!["Unsupported ruSt version 2"]);
+        let short = Chunk::new(chunk.chunk_type().clone(), vec![1]);
+        assert!(registry.display(&short).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_standard_codecs() {
+        let registry = CodecRegistry::standard();
+        let gamma = StandardCodec::new("gAMA", "Gamma", |v: &Gamma| Ok(v.as_bytes()))
+            .to_chunk(&Gamma::SRGB)
+            .unwrap();
+        assert_eq!(registry.display(&gamma).unwrap().unwrap(), "0.45455");
+        assert_eq!(registry.to_json(&gamma).unwrap().unwrap(), json!("0.45455"));
+
+        let text = TextChunk::new(TextKind::Text, "Title", "Hi \"there\"")
+            .to_chunk()
+            .unwrap();
+        assert_eq!(
+            registry.to_json(&text).unwrap().unwrap().to_string(),
+            "{\"keyword\":\"Title\",\"text\":\"Hi \\\"there\\\"\"}"
+        );
+
+        let zero = Chunk::new(ChunkType::from_str("gAMA").unwrap(), vec![0; 4]);
+        assert_eq!(registry.validate(&zero), vec!["gAMA must not be zero"]);
+    }
+
+    #[test]
+    fn test_image_codecs() {
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+        };
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+            chunk("PLTE", &[255, 0, 0, 0, 0, 255]),
+            chunk("tRNS", &[0]),
+            chunk("bKGD", &[1]),
+            chunk("hIST", &[0, 3, 0, 0]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        let registry = CodecRegistry::standard();
+        let bkgd = &png.chunks()[3];
+        assert!(registry.display(bkgd).unwrap().is_err());
+        assert_eq!(
+            registry.display_in(bkgd, &png).unwrap().unwrap(),
+            "palette entry 1"
+        );
+        assert_eq!(
+            registry
+                .to_json_in(&png.chunks()[1], &png)
+                .unwrap()
+                .unwrap(),
+            json!(["#ff0000", "#0000ff"])
+        );
+
+        let json = registry.png_to_value(&png);
+        for chunk in &json["chunks"].as_array().unwrap()[1..5] {
+            assert!(!chunk["value"].is_null(), "{}", chunk);
+        }
+
+        let background = ImageCodec::new(
+            "bKGD",
+            "Background",
+            |data, png| Background::parse(data, &png.ihdr()?, png.palette()?.as_ref()),
+            Background::as_bytes,
+        );
+        assert_eq!(
+            png.decode(&background).unwrap(),
+            Some(Background::Indexed(1))
+        );
+
+        let out_of_range = chunk("bKGD", &[2]);
+        assert_eq!(registry.validate_in(&out_of_range, &png).len(), 1);
+    }
+}
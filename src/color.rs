@@ -0,0 +1,350 @@
+use std::fmt;
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::zlib;
+
+/// Largest decompressed ICC profile accepted from an iCCP chunk.
+pub const MAX_PROFILE_LENGTH: usize = 16 << 20;
+
+/// gAMA: image gamma times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma(pub u32);
+
+impl Gamma {
+    /// The gamma implied by sRGB, 1/2.2.
+    pub const SRGB: Gamma = Gamma(45455);
+
+    pub fn value(&self) -> f64 {
+        self.0 as f64 / 100000.0
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.0.to_be_bytes().to_vec()
+    }
+}
+
+impl TryFrom<&[u8]> for Gamma {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; 4] = value
+            .try_into()
+            .map_err(|_| format!("gAMA data must be 4 bytes, got {}", value.len()))?;
+        match u32::from_be_bytes(bytes) {
+            0 => Err("gAMA must not be zero".to_string()),
+            gamma => Ok(Gamma(gamma)),
+        }
+    }
+}
+
+impl fmt::Display for Gamma {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.5}", self.value())
+    }
+}
+
+/// cHRM: CIE 1931 x,y chromaticities times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticities {
+    pub white: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+impl Chromaticities {
+    /// The primaries and white point of sRGB (ITU-R BT.709, D65).
+    pub const SRGB: Chromaticities = Chromaticities {
+        white: (31270, 32900),
+        red: (64000, 33000),
+        green: (30000, 60000),
+        blue: (15000, 6000),
+    };
+
+    fn points(&self) -> [(u32, u32); 4] {
+        [self.white, self.red, self.green, self.blue]
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.points()
+            .iter()
+            .flat_map(|&(x, y)| [x.to_be_bytes(), y.to_be_bytes()])
+            .flatten()
+            .collect()
+    }
+
+    /// Whether every coordinate is within `tolerance` (in units of 1/100000) of `other`.
+    pub fn approx_eq(&self, other: &Chromaticities, tolerance: u32) -> bool {
+        self.points()
+            .iter()
+            .zip(other.points())
+            .all(|(a, b)| a.0.abs_diff(b.0) <= tolerance && a.1.abs_diff(b.1) <= tolerance)
+    }
+}
+
+impl TryFrom<&[u8]> for Chromaticities {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != 32 {
+            return Err(format!("cHRM data must be 32 bytes, got {}", value.len()));
+        }
+        let read = |i: usize| {
+            let at =
+                |j: usize| u32::from_be_bytes([value[j], value[j + 1], value[j + 2], value[j + 3]]);
+            (at(i * 8), at(i * 8 + 4))
+        };
+        Ok(Chromaticities {
+            white: read(0),
+            red: read(1),
+            green: read(2),
+            blue: read(3),
+        })
+    }
+}
+
+impl fmt::Display for Chromaticities {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = ["white", "red", "green", "blue"];
+        let points: Vec<String> = names
+            .iter()
+            .zip(self.points())
+            .map(|(name, (x, y))| {
+                format!(
+                    "{} ({:.4}, {:.4})",
+                    name,
+                    x as f64 / 100000.0,
+                    y as f64 / 100000.0
+                )
+            })
+            .collect();
+        write!(f, "{}", points.join(", "))
+    }
+}
+
+/// sRGB: the rendering intent of an image in the sRGB color space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let value = match self {
+            RenderingIntent::Perceptual => 0,
+            RenderingIntent::RelativeColorimetric => 1,
+            RenderingIntent::Saturation => 2,
+            RenderingIntent::AbsoluteColorimetric => 3,
+        };
+        vec![value]
+    }
+}
+
+impl TryFrom<&[u8]> for RenderingIntent {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match value {
+            [0] => Ok(RenderingIntent::Perceptual),
+            [1] => Ok(RenderingIntent::RelativeColorimetric),
+            [2] => Ok(RenderingIntent::Saturation),
+            [3] => Ok(RenderingIntent::AbsoluteColorimetric),
+            [other] => Err(format!("Invalid sRGB rendering intent: {}", other)),
+            _ => Err(format!("sRGB data must be 1 byte, got {}", value.len())),
+        }
+    }
+}
+
+impl fmt::Display for RenderingIntent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// iCCP: a named ICC profile. `profile` holds the uncompressed profile bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+impl IccProfile {
+    pub fn new(name: &str, profile: Vec<u8>) -> Self {
+        IccProfile {
+            name: name.to_string(),
+            profile,
+        }
+    }
+
+    /// Serializes the profile name and the zlib-compressed profile.
+    pub fn as_bytes(&self) -> Result<Vec<u8>, String> {
+        if self.name.is_empty() || self.name.len() > 79 {
+            return Err("ICC profile name must be 1 to 79 bytes long".to_string());
+        }
+        let mut data: Vec<u8> = self
+            .name
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| format!("Character {:?} is not Latin-1", c)))
+            .collect::<Result<_, _>>()?;
+        data.extend_from_slice(&[0, 0]);
+
+        let mut encoder = ZlibEncoder::new(data, Compression::default());
+        encoder
+            .write_all(&self.profile)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| format!("Failed to compress ICC profile: {}", e))
+    }
+}
+
+impl TryFrom<&[u8]> for IccProfile {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let separator = value
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| "iCCP chunk is missing a null separator".to_string())?;
+        let name = value[..separator].iter().map(|&b| b as char).collect();
+        match value.get(separator + 1) {
+            Some(0) => {}
+            Some(method) => return Err(format!("Unknown iCCP compression method: {}", method)),
+            None => return Err("iCCP chunk is missing its compression method".to_string()),
+        }
+
+        let profile = zlib::inflate(&value[separator + 2..], MAX_PROFILE_LENGTH)
+            .map_err(|e| format!("Failed to decompress ICC profile: {}", e))?;
+        Ok(IccProfile { name, profile })
+    }
+}
+
+impl fmt::Display for IccProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' ({} bytes)", self.name, self.profile.len())
+    }
+}
+
+/// cICP: coding-independent code points as defined in ITU-T H.273.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CodePoints {
+    pub colour_primaries: u8,
+    pub transfer_function: u8,
+    /// Always 0 in PNG, since images are stored as RGB.
+    pub matrix_coefficients: u8,
+    pub full_range: bool,
+}
+
+impl CodePoints {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        vec![
+            self.colour_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            self.full_range as u8,
+        ]
+    }
+}
+
+impl TryFrom<&[u8]> for CodePoints {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        let &[colour_primaries, transfer_function, matrix_coefficients, full_range] = value else {
+            return Err(format!("cICP data must be 4 bytes, got {}", value.len()));
+        };
+        if matrix_coefficients != 0 {
+            return Err(format!(
+                "cICP matrix coefficients must be 0, got {}",
+                matrix_coefficients
+            ));
+        }
+        if full_range > 1 {
+            return Err(format!("Invalid cICP full range flag: {}", full_range));
+        }
+        Ok(CodePoints {
+            colour_primaries,
+            transfer_function,
+            matrix_coefficients,
+            full_range: full_range == 1,
+        })
+    }
+}
+
+impl fmt::Display for CodePoints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "primaries {}, transfer {}, matrix {}, {} range",
+            self.colour_primaries,
+            self.transfer_function,
+            self.matrix_coefficients,
+            if self.full_range { "full" } else { "narrow" }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gamma() {
+        let gamma = Gamma::try_from(&[0, 0, 177, 143][..]).unwrap();
+        assert_eq!(gamma, Gamma::SRGB);
+        assert_eq!(gamma.to_string(), "0.45455");
+        assert_eq!(gamma.as_bytes(), vec![0, 0, 177, 143]);
+        assert!(Gamma::try_from(&[0, 0, 0, 0][..]).is_err());
+    }
+
+    #[test]
+    fn test_chromaticities_round_trip() {
+        let bytes = Chromaticities::SRGB.as_bytes();
+        let chrm = Chromaticities::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(chrm, Chromaticities::SRGB);
+        assert!(chrm.approx_eq(
+            &Chromaticities {
+                white: (31271, 32900),
+                ..chrm
+            },
+            1
+        ));
+        assert!(Chromaticities::try_from(&bytes[1..]).is_err());
+    }
+
+    #[test]
+    fn test_rendering_intent() {
+        let intent = RenderingIntent::try_from(&[2][..]).unwrap();
+        assert_eq!(intent, RenderingIntent::Saturation);
+        assert_eq!(intent.as_bytes(), vec![2]);
+        assert!(RenderingIntent::try_from(&[4][..]).is_err());
+    }
+
+    #[test]
+    fn test_icc_profile_round_trip() {
+        let icc = IccProfile::new("Display P3", vec![7; 500]);
+        let bytes = icc.as_bytes().unwrap();
+        assert!(bytes.starts_with(b"Display P3\0\0"));
+        assert!(bytes.len() < 500);
+        assert_eq!(IccProfile::try_from(bytes.as_slice()).unwrap(), icc);
+        assert!(IccProfile::new("", vec![]).as_bytes().is_err());
+    }
+
+    #[test]
+    fn test_code_points() {
+        let cicp = CodePoints::try_from(&[9, 16, 0, 1][..]).unwrap();
+        assert_eq!(cicp.transfer_function, 16);
+        assert!(cicp.full_range);
+        assert_eq!(cicp.as_bytes(), vec![9, 16, 0, 1]);
+        assert!(CodePoints::try_from(&[9, 16, 1, 1][..]).is_err());
+    }
+}
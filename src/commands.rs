@@ -1,14 +1,193 @@
 use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use clap::ValueEnum;
+use serde_json::json;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use url::Url;
 
-use crate::args::Input;
-use crate::chunk::Chunk;
+use crate::analyze::Analysis;
+use crate::ancillary::{PhysicalDimensions, Time};
+use crate::args::{BatchArgs, Input, Method};
+use crate::atomic;
+use crate::batch;
+use crate::capacity::CapacityReport;
 use crate::chunk_type::ChunkType;
-use crate::crypto::{Crypto, EncryptedData};
+use crate::codec::CodecRegistry;
+use crate::color::IccProfile;
+use crate::diff::PngDiff;
+use crate::embedder::Embedder;
+use crate::exif::{self, ByteOrder, Exif, IfdKind};
+use crate::find::{self, Query};
+use crate::listing::Listing;
+use crate::manifest;
 use crate::png::Png;
+use crate::repair;
+use crate::validate;
+use crate::xmp::Xmp;
+
+/// Options shared by every command that writes a file.
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Record the current time in tIME of every PNG written.
+    pub touch: bool,
+    /// Allow replacing an existing file that is not the input.
+    pub overwrite: bool,
+    /// Copy an existing file to its name plus this suffix before replacing it.
+    pub backup: Option<String>,
+}
+
+/// Picks the output path, defaulting to the input, and makes sure writing it
+/// will not clobber an unrelated file.
+fn resolve_output(
+    input: &Path,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<PathBuf> {
+    let output = output_file.unwrap_or_else(|| input.to_path_buf());
+    check_overwrite(Some(input), &output, options)?;
+    Ok(output)
+}
+
+/// Refuses to replace an existing file other than `input` unless --overwrite was given.
+fn check_overwrite(input: Option<&Path>, output: &Path, options: &WriteOptions) -> Result<()> {
+    if !output.exists() || options.overwrite {
+        return Ok(());
+    }
+    let same_file =
+        input.is_some_and(
+            |input| match (fs::canonicalize(input), fs::canonicalize(output)) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => input == output,
+            },
+        );
+    if !same_file {
+        anyhow::bail!(
+            "{:?} already exists; pass --overwrite to replace it, with --backup to keep a copy",
+            output
+        );
+    }
+    Ok(())
+}
+
+/// Writes `contents` atomically, first copying any existing file if --backup was given.
+fn write_file(path: &Path, contents: &[u8], options: &WriteOptions) -> Result<()> {
+    if let Some(suffix) = &options.backup {
+        if path.exists() {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(suffix);
+            fs::copy(path, &backup)
+                .with_context(|| format!("Failed to back up {:?} to {:?}", path, backup))?;
+        }
+    }
+    atomic::write(path, contents).with_context(|| format!("Failed to write file: {:?}", path))
+}
+
+/// Writes `png` and returns the bytes written.
+fn save_png(png: &mut Png, path: &Path, options: &WriteOptions) -> Result<Vec<u8>> {
+    if options.touch {
+        png.touch();
+    }
+    let bytes = png.as_bytes();
+    write_file(path, &bytes, options)?;
+    Ok(bytes)
+}
+
+/// Runs `task` for every file named by `inputs`, which may include directories
+/// and glob patterns. A single file is processed directly; otherwise each file's
+/// output is printed after a header, followed by a summary. Headers, errors and
+/// the summary go to standard error, so standard output only holds what `task`
+/// writes, such as one JSON object per file. Returns whether every file succeeded.
+pub fn for_each_input<F>(
+    inputs: Vec<String>,
+    url: bool,
+    batch: &BatchArgs,
+    output_file: Option<&Path>,
+    task: F,
+) -> Result<bool>
+where
+    F: Fn(Input, &mut dyn Write) -> Result<bool> + Sync,
+{
+    if !batch::is_batch(&inputs) {
+        let input = inputs.into_iter().next().context("No input given")?;
+        return task(Input::from_args(input, url), &mut io::stdout().lock());
+    }
+    if url {
+        anyhow::bail!("Only one URL can be processed at a time");
+    }
+    if output_file.is_some() {
+        anyhow::bail!("--output-file cannot be used with several input files");
+    }
+
+    let paths = batch::expand(&inputs, batch.recursive).map_err(anyhow::Error::msg)?;
+    let jobs = batch.jobs.unwrap_or_else(batch::default_jobs);
+    let (mut succeeded, mut failed) = (0, 0);
+    batch::run(
+        &paths,
+        jobs,
+        |path, output| task(Input::FilePath(path.to_path_buf()), output),
+        |path, output, result| {
+            eprintln!("==> {} <==", path.display());
+            let mut stdout = io::stdout().lock();
+            let _ = stdout.write_all(&output);
+            let _ = stdout.flush();
+            match result {
+                Ok(true) => succeeded += 1,
+                Ok(false) => failed += 1,
+                Err(e) => {
+                    failed += 1;
+                    eprintln!("Error: {:#}", e);
+                }
+            }
+        },
+    );
+    eprintln!(
+        "{} files: {} succeeded, {} failed",
+        paths.len(),
+        succeeded,
+        failed
+    );
+    Ok(failed == 0)
+}
+
+/// Prints every chunk that matches `query` as `file:offset: type`, followed by
+/// the text keyword and the matched content. Files are read leniently, so damaged
+/// chunks are reported as warnings and the rest of the file is still searched.
+/// Files that cannot be read are reported and skipped. Returns whether anything
+/// matched.
+pub fn find(inputs: Vec<String>, query: &Query, batch_args: &BatchArgs) -> Result<bool> {
+    let paths = batch::expand(&inputs, batch_args.recursive).map_err(anyhow::Error::msg)?;
+    let jobs = batch_args.jobs.unwrap_or_else(batch::default_jobs);
+    let mut found = false;
+    batch::run(
+        &paths,
+        jobs,
+        |path, _| -> Result<(Vec<repair::ParseIssue>, Vec<find::Match>)> {
+            let file_bytes =
+                fs::read(path).with_context(|| format!("Failed to read file: {:?}", path))?;
+            let (png, issues) = repair::parse_lenient(&file_bytes)
+                .map_err(anyhow::Error::msg)
+                .context("Failed to parse PNG data")?;
+            Ok((issues, query.search(&png)))
+        },
+        |path, _, result| match result {
+            Ok((issues, matches)) => {
+                for issue in issues {
+                    eprintln!("Warning: {}: {}", path.display(), issue);
+                }
+                let mut stdout = io::stdout().lock();
+                for m in matches {
+                    found = true;
+                    let _ = writeln!(stdout, "{}:{}", path.display(), m);
+                }
+            }
+            Err(e) => eprintln!("Error: {}: {:#}", path.display(), e),
+        },
+    );
+    Ok(found)
+}
 
 fn get_png_bytes(input: &Input) -> Result<Vec<u8>> {
     match input {
@@ -24,118 +203,807 @@ fn get_png_bytes(input: &Input) -> Result<Vec<u8>> {
     }
 }
 
+/// Parses a file, leniently if asked. Returns the problems lenient parsing
+/// stepped over, so callers can report them with the rest of the file's output.
+fn parse_png(file_bytes: &[u8], lenient: bool) -> Result<(Png, Vec<repair::ParseIssue>)> {
+    if !lenient {
+        let png = Png::try_from(file_bytes)
+            .map_err(anyhow::Error::msg)
+            .context("Failed to parse PNG data")?;
+        return Ok((png, Vec::new()));
+    }
+
+    repair::parse_lenient(file_bytes)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse PNG data")
+}
+
+/// Embeds `message` and writes the result. Returns the contents of the written file.
 pub fn encode(
     input: Input,
-    chunk_type: String,
-    message: String,
+    embedder: &Embedder,
+    message: &[u8],
     output_file: Option<PathBuf>,
-    password: Option<String>,
-) -> Result<()> {
+    options: &WriteOptions,
+    out: &mut dyn Write,
+) -> Result<Vec<u8>> {
     let file_bytes = get_png_bytes(&input)?;
 
     let mut png = Png::try_from(file_bytes.as_slice())
         .map_err(anyhow::Error::msg)
         .context("Failed to parse PNG data")?;
 
-    let chunk_type = ChunkType::from_str(&chunk_type)
+    embedder
+        .embed_payload(&mut png, message)
+        .map_err(anyhow::Error::msg)?;
+    for warning in embedder.warnings() {
+        writeln!(out, "Warning: {}", warning)?;
+    }
+
+    let output_path = output_file.unwrap_or_else(|| match &input {
+        Input::FilePath(path) => path.with_extension("png"),
+        Input::Url(_) => PathBuf::from("output.png"),
+    });
+    check_overwrite(input.path(), &output_path, options)?;
+
+    let written = save_png(&mut png, &output_path, options)?;
+
+    writeln!(out, "Message encoded successfully to {:?}", output_path)?;
+    Ok(written)
+}
+
+/// Encodes every row of a CSV or JSON lines plan and writes a result manifest
+/// with the status and SHA-256 of each output file. `method` and `chunk_type`
+/// apply to rows that do not set their own. Returns whether every row succeeded.
+pub fn encode_manifest(
+    manifest_path: &Path,
+    results_path: Option<&Path>,
+    method: Method,
+    chunk_type: Option<String>,
+    batch_args: &BatchArgs,
+    force: bool,
+    options: &WriteOptions,
+) -> Result<bool> {
+    let format = manifest::Format::from_path(manifest_path);
+    let text = fs::read_to_string(manifest_path)
+        .with_context(|| format!("Failed to read manifest {:?}", manifest_path))?;
+    let rows = manifest::parse(&text, format)
         .map_err(anyhow::Error::msg)
-        .context("Invalid chunk type")?;
+        .context("Invalid manifest")?;
+    if let Some(results_path) = results_path {
+        check_overwrite(None, results_path, options)?;
+    }
 
-    let final_message = if let Some(password) = password {
-        let encrypted =
-            Crypto::encrypt(&message, &password).context("Failed to encrypt message")?;
-        encrypted.to_base64()
-    } else {
-        message
+    let jobs = batch_args.jobs.unwrap_or_else(batch::default_jobs);
+    let mut results = Vec::with_capacity(rows.len());
+    batch::run(
+        &rows,
+        jobs,
+        |row, output| encode_row(row, method, chunk_type.as_deref(), force, options, output),
+        |row, _, outcome| {
+            results.push(manifest::RowResult {
+                row: row.row,
+                input: row.input.clone(),
+                output: row.output_path(),
+                recipient: row.recipient.clone(),
+                outcome: outcome.map_err(|e| format!("{:#}", e)),
+            })
+        },
+    );
+    results.sort_by_key(|result| result.row);
+
+    let report = manifest::format_results(&results, format);
+    match results_path {
+        Some(path) => write_file(path, report.as_bytes(), options)?,
+        None => print!("{}", report),
+    }
+    let failed = results.iter().filter(|r| r.outcome.is_err()).count();
+    eprintln!(
+        "{} rows: {} succeeded, {} failed",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+    Ok(failed == 0)
+}
+
+fn encode_row(
+    row: &manifest::ManifestRow,
+    method: Method,
+    chunk_type: Option<&str>,
+    force: bool,
+    options: &WriteOptions,
+    output: &mut Vec<u8>,
+) -> Result<String> {
+    let method = match &row.method {
+        Some(name) => Method::from_str(name, true)
+            .map_err(|_| anyhow::anyhow!("Unknown method '{}'", name))?,
+        None => method,
+    };
+    let message = row.message.load().map_err(anyhow::Error::msg)?;
+    let password = row.resolve_password().map_err(anyhow::Error::msg)?;
+    let embedder = embedder(
+        method,
+        row.chunk_type.clone().or(chunk_type.map(String::from)),
+        password,
+    )?
+    .force(force);
+    let written = encode(
+        Input::FilePath(row.input.clone()),
+        &embedder,
+        &message,
+        Some(row.output_path()),
+        options,
+        output,
+    )?;
+    Ok(manifest::sha256_hex(&written))
+}
+
+/// Builds the embedder for the method, chunk type and password given on the command line.
+pub fn embedder(
+    method: Method,
+    chunk_type: Option<String>,
+    password: Option<String>,
+) -> Result<Embedder> {
+    let mut embedder = Embedder::new(method.into());
+    if let Some(chunk_type) = chunk_type {
+        let chunk_type = ChunkType::from_str(&chunk_type)
+            .map_err(anyhow::Error::msg)
+            .context("Invalid chunk type")?;
+        embedder = embedder.chunk_type(chunk_type);
+    }
+    if let Some(password) = password {
+        embedder = embedder.password(password);
+    }
+    Ok(embedder)
+}
+
+/// Prints the hidden message. Returns false when the file holds none.
+pub fn decode(
+    input: Input,
+    method: Method,
+    chunk_type: Option<String>,
+    password: Option<String>,
+    lenient: bool,
+    out: &mut dyn Write,
+) -> Result<bool> {
+    let file_bytes = get_png_bytes(&input)?;
+
+    let (png, issues) = parse_png(&file_bytes, lenient)?;
+    for issue in &issues {
+        writeln!(out, "Warning: {}", issue)?;
+    }
+
+    let source = match (method, &chunk_type) {
+        (Method::Chunk, Some(chunk_type)) => format!("chunk '{}'", chunk_type),
+        (Method::Chunk, None) => String::new(),
+        (Method::Palette, _) => "palette order".to_string(),
+        (Method::Trailer, _) => "trailing data".to_string(),
+    };
+    let embedder = embedder(method, chunk_type, password)?;
+    let Some(payload) = embedder.extract_payload(&png).map_err(anyhow::Error::msg)? else {
+        writeln!(out, "No {} found in the PNG file.", source)?;
+        return Ok(false);
     };
 
-    let chunk = Chunk::new(chunk_type, final_message.into_bytes());
+    match String::from_utf8(payload) {
+        Ok(message) => writeln!(out, "Decoded message from {}: {}", source, message)?,
+        Err(e) => writeln!(
+            out,
+            "Decoded {}-byte binary payload from {} (base64): {}",
+            e.as_bytes().len(),
+            source,
+            STANDARD.encode(e.as_bytes())
+        )?,
+    }
+
+    Ok(true)
+}
+
+pub fn remove(
+    input: PathBuf,
+    chunk_type: String,
+    force: bool,
+    options: &WriteOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let file_bytes =
+        fs::read(&input).with_context(|| format!("Failed to read file: {:?}", input))?;
+
+    let mut png = Png::try_from(file_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", input))?;
+
+    let parsed = ChunkType::from_str(&chunk_type)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to remove chunk of type: {}", chunk_type))?;
+    Embedder::chunk(parsed)
+        .force(force)
+        .remove(&mut png)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to remove chunk of type: {}", chunk_type))?;
+
+    save_png(&mut png, &input, options)?;
+
+    writeln!(
+        out,
+        "Chunk '{}' removed successfully from {:?}",
+        chunk_type, input
+    )?;
+    Ok(())
+}
+
+pub fn strip(
+    input: PathBuf,
+    keep: Vec<String>,
+    keep_trailer: bool,
+    output_file: Option<PathBuf>,
+    dry_run: bool,
+    options: &WriteOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let file_bytes =
+        fs::read(&input).with_context(|| format!("Failed to read file: {:?}", input))?;
+
+    let mut png = Png::try_from(file_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", input))?;
+
+    let keep = keep
+        .iter()
+        .map(|chunk_type| {
+            ChunkType::from_str(chunk_type)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid chunk type to keep: {}", chunk_type))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let trailer_length = png.trailer().len();
+    let removed = png.strip_ancillary(&keep, keep_trailer);
+    let trailer_removed = png.trailer().len() < trailer_length;
+    if removed.is_empty() && !trailer_removed {
+        writeln!(out, "No ancillary chunks to remove from {:?}", input)?;
+        return Ok(());
+    }
+
+    for chunk in &removed {
+        writeln!(
+            out,
+            "Removed '{}' ({} bytes)",
+            chunk.chunk_type(),
+            chunk.length()
+        )?;
+    }
+    if trailer_removed {
+        writeln!(out, "Removed {} bytes after IEND", trailer_length)?;
+    }
+    let saved = file_bytes.len() - png.as_bytes().len();
+
+    if dry_run {
+        writeln!(out, "Dry run: would save {} bytes", saved)?;
+        return Ok(());
+    }
+
+    let output_path = resolve_output(&input, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    writeln!(out, "Saved {} bytes, written to {:?}", saved, output_path)?;
+    Ok(())
+}
+
+pub fn print(
+    input: Input,
+    registry: &CodecRegistry,
+    lenient: bool,
+    json: bool,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let file_bytes = get_png_bytes(&input)?;
 
-    png.append_chunk(chunk);
+    let (png, issues) = parse_png(&file_bytes, lenient)?;
 
-    let output_path = output_file.unwrap_or_else(|| {
-        match &input {
-            Input::FilePath(path) => path.with_extension("png"),
-            Input::Url(_) => PathBuf::from("output.png"),
+    if json {
+        // Warnings go inside the document so that the output stays valid JSON.
+        let mut document = registry.png_to_value(&png);
+        if !issues.is_empty() {
+            let warnings: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+            document["warnings"] = json!(warnings);
         }
-    });
+        writeln!(out, "{}", document)?;
+        return Ok(());
+    }
+
+    for issue in &issues {
+        writeln!(out, "Warning: {}", issue)?;
+    }
+
+    let listing = Listing::for_png(&png, registry);
 
-    fs::write(&output_path, png.as_bytes())
-        .with_context(|| format!("Failed to write output file: {:?}", output_path))?;
+    if listing.chunks.is_empty() {
+        writeln!(out, "No chunks found in the PNG file.")?;
+    } else {
+        writeln!(out, "Chunks in the PNG file:")?;
+        for chunk in &listing.chunks {
+            match &chunk.text {
+                Some(text) => writeln!(out, "{}: {}", chunk.chunk_type, text)?,
+                None => writeln!(out)?,
+            }
+        }
+    }
+
+    if listing.trailer_length > 0 {
+        writeln!(out, "{} bytes of data after IEND", listing.trailer_length)?;
+    }
+
+    for (name, value) in &listing.properties {
+        match value {
+            Ok(value) => writeln!(out, "{}: {}", name, value)?,
+            Err(e) => writeln!(out, "{}: invalid ({})", name, e)?,
+        }
+    }
+
+    // acTL and fcTL are listed above; this adds where each frame's data is.
+    if let Some(Ok(animation)) = &listing.animation {
+        match &animation.frames {
+            Ok(frames) => {
+                for (i, frame) in frames.iter().enumerate() {
+                    let source = if frame.is_default_image {
+                        "IDAT"
+                    } else {
+                        "fdAT"
+                    };
+                    writeln!(out, "Frame {} data: {} {} chunks", i, frame.chunks, source)?;
+                }
+            }
+            Err(e) => writeln!(out, "Frames: invalid ({})", e)?,
+        }
+        for problem in &animation.problems {
+            writeln!(out, "Animation warning: {}", problem)?;
+        }
+    }
+
+    match &listing.xmp {
+        Some(Ok(xmp)) => {
+            writeln!(out, "XMP ({} bytes):", xmp.length)?;
+            for (property, value) in &xmp.properties {
+                match value {
+                    Ok(value) => writeln!(out, "  {} = {}", property, value)?,
+                    Err(e) => writeln!(out, "  {} = invalid ({})", property, e)?,
+                }
+            }
+        }
+        Some(Err(e)) => writeln!(out, "XMP: invalid ({})", e)?,
+        None => {}
+    }
 
-    println!("Message encoded successfully to {:?}", output_path);
     Ok(())
 }
 
-pub fn decode(input: Input, chunk_type: String, password: Option<String>) -> Result<()> {
+fn read_png_file(input: &PathBuf) -> Result<Png> {
+    let file_bytes =
+        fs::read(input).with_context(|| format!("Failed to read file: {:?}", input))?;
+
+    Png::try_from(file_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", input))
+}
+
+fn read_exif(png: &Png) -> Result<Option<Exif>> {
+    png.exif()
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse eXIf chunk")
+}
+
+pub fn exif_get(input: Input, tag: Option<String>) -> Result<()> {
     let file_bytes = get_png_bytes(&input)?;
 
     let png = Png::try_from(file_bytes.as_slice())
         .map_err(anyhow::Error::msg)
         .context("Failed to parse PNG data")?;
 
-    if let Some(chunk) = png.chunk_by_type(&chunk_type) {
-        let raw_message = chunk
-            .data_as_string()
-            .with_context(|| format!("Failed to decode chunk data as string: {}", chunk_type))?;
+    let Some(exif) = read_exif(&png)? else {
+        println!("No EXIF data found");
+        return Ok(());
+    };
 
-        let final_message = if let Some(password) = password {
-            let encrypted_data = EncryptedData::from_base64(&raw_message)
-                .context("Failed to parse encrypted data")?;
-            Crypto::decrypt(&encrypted_data, &password)
-                .context("Failed to decrypt message - wrong password or corrupted data")?
-        } else {
-            raw_message
+    match tag {
+        None => print!("{}", exif),
+        Some(tag) => {
+            let (kind, number) = exif::resolve_tag(&tag).map_err(anyhow::Error::msg)?;
+            let entry = exif
+                .get(kind, number)
+                .with_context(|| format!("EXIF tag not found: {}", tag))?;
+            println!("{}", entry.display_value(exif.byte_order));
+        }
+    }
+    Ok(())
+}
+
+pub fn exif_set(
+    input: PathBuf,
+    tag: String,
+    value: String,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let mut png = read_png_file(&input)?;
+
+    let mut exif = read_exif(&png)?.unwrap_or_else(|| Exif::new(ByteOrder::Big));
+    exif.set_tag(&tag, &value)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to set EXIF tag: {}", tag))?;
+    png.set_exif(&exif);
+
+    let output_path = resolve_output(&input, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    println!("EXIF tag '{}' set, written to {:?}", tag, output_path);
+    Ok(())
+}
+
+pub fn exif_remove(
+    input: PathBuf,
+    tags: Vec<String>,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let mut png = read_png_file(&input)?;
+
+    let mut exif = read_exif(&png)?.with_context(|| format!("No EXIF data in {:?}", input))?;
+    for tag in &tags {
+        let removed = match IfdKind::from_str(tag) {
+            Ok(kind) => exif.remove_ifd(kind),
+            Err(_) => {
+                let (kind, number) = exif::resolve_tag(tag).map_err(anyhow::Error::msg)?;
+                exif.remove(kind, number).is_some()
+            }
         };
+        if removed {
+            println!("Removed EXIF '{}'", tag);
+        } else {
+            println!("EXIF '{}' not present", tag);
+        }
+    }
+    png.set_exif(&exif);
 
-        println!(
-            "Decoded message from chunk '{}': {}",
-            chunk_type, final_message
-        );
-    } else {
-        println!("No chunk of type '{}' found in the PNG file.", chunk_type);
+    let output_path = resolve_output(&input, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    println!("Written to {:?}", output_path);
+    Ok(())
+}
+
+fn read_xmp(png: &Png) -> Result<Option<Xmp>> {
+    png.xmp()
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse XMP packet")
+}
+
+fn write_xmp(mut png: Png, xmp: &Xmp, output_path: &Path, options: &WriteOptions) -> Result<()> {
+    png.set_xmp(xmp)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to build XMP chunk")?;
+    save_png(&mut png, output_path, options)?;
+    Ok(())
+}
+
+pub fn xmp_extract(
+    input: Input,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let file_bytes = get_png_bytes(&input)?;
+
+    let png = Png::try_from(file_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse PNG data")?;
+
+    let xmp = read_xmp(&png)?.context("No XMP packet found")?;
+    match output_file {
+        Some(path) => {
+            check_overwrite(None, &path, options)?;
+            write_file(&path, xmp.packet().as_bytes(), options)?;
+            println!("XMP packet written to {:?}", path);
+        }
+        None => println!("{}", xmp.packet()),
     }
+    Ok(())
+}
 
+pub fn xmp_replace(
+    input: PathBuf,
+    packet: PathBuf,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let png = read_png_file(&input)?;
+
+    let text = fs::read_to_string(&packet)
+        .with_context(|| format!("Failed to read XMP packet: {:?}", packet))?;
+    let xmp = Xmp::from_str(&text)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Invalid XMP packet in {:?}", packet))?;
+
+    let output_path = resolve_output(&input, output_file, options)?;
+    write_xmp(png, &xmp, &output_path, options)?;
+
+    println!("XMP packet replaced, written to {:?}", output_path);
     Ok(())
 }
 
-pub fn remove(input: PathBuf, chunk_type: String) -> Result<()> {
-    let file_bytes =
-        fs::read(&input).with_context(|| format!("Failed to read file: {:?}", input))?;
+pub fn xmp_get(input: Input, property: String) -> Result<()> {
+    let file_bytes = get_png_bytes(&input)?;
 
-    let mut png = Png::try_from(file_bytes.as_slice())
+    let png = Png::try_from(file_bytes.as_slice())
         .map_err(anyhow::Error::msg)
-        .with_context(|| format!("Failed to parse PNG file: {:?}", input))?;
+        .context("Failed to parse PNG data")?;
 
-    png.remove_first_chunk(&chunk_type)
+    let xmp = read_xmp(&png)?.context("No XMP packet found")?;
+    let value = xmp
+        .get(&property)
+        .map_err(anyhow::Error::msg)?
+        .with_context(|| format!("XMP property not found: {}", property))?;
+    println!("{}", value);
+    Ok(())
+}
+
+pub fn xmp_set(
+    input: PathBuf,
+    property: String,
+    value: String,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let png = read_png_file(&input)?;
+
+    let mut xmp = read_xmp(&png)?.unwrap_or_default();
+    xmp.set(&property, &value)
         .map_err(anyhow::Error::msg)
-        .with_context(|| format!("Failed to remove chunk of type: {}", chunk_type))?;
+        .with_context(|| format!("Failed to set XMP property: {}", property))?;
 
-    fs::write(&input, png.as_bytes())
-        .with_context(|| format!("Failed to write updated PNG file: {:?}", input))?;
+    let output_path = resolve_output(&input, output_file, options)?;
+    write_xmp(png, &xmp, &output_path, options)?;
 
     println!(
-        "Chunk '{}' removed successfully from {:?}",
-        chunk_type, input
+        "XMP property '{}' set, written to {:?}",
+        property, output_path
     );
     Ok(())
 }
 
-pub fn print(input: Input) -> Result<()> {
+pub fn icc_extract(input: Input, output: PathBuf, options: &WriteOptions) -> Result<()> {
     let file_bytes = get_png_bytes(&input)?;
 
     let png = Png::try_from(file_bytes.as_slice())
         .map_err(anyhow::Error::msg)
         .context("Failed to parse PNG data")?;
 
-    if png.chunks.is_empty() {
-        println!("No chunks found in the PNG file.");
+    let icc = png
+        .icc_profile()
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse iCCP chunk")?
+        .context("No ICC profile found")?;
+
+    check_overwrite(None, &output, options)?;
+    write_file(&output, &icc.profile, options)?;
+
+    println!("ICC profile {} written to {:?}", icc, output);
+    Ok(())
+}
+
+pub fn icc_embed(
+    input: PathBuf,
+    profile: PathBuf,
+    name: Option<String>,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let mut png = read_png_file(&input)?;
+
+    let profile_bytes =
+        fs::read(&profile).with_context(|| format!("Failed to read file: {:?}", profile))?;
+    let name = name
+        .or_else(|| {
+            profile
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "ICC profile".to_string());
+    let icc = IccProfile::new(&name, profile_bytes);
+
+    if png.chunk_by_type("sRGB").is_some() {
+        println!("Removing sRGB chunk, which must not be combined with iCCP");
+    }
+    png.set_icc_profile(&icc)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to embed ICC profile")?;
+
+    let output_path = resolve_output(&input, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    println!("ICC profile {} embedded, written to {:?}", icc, output_path);
+    Ok(())
+}
+
+pub fn set(
+    input: PathBuf,
+    dpi: Option<f64>,
+    time: Option<String>,
+    output_file: Option<PathBuf>,
+    options: &WriteOptions,
+) -> Result<()> {
+    let mut png = read_png_file(&input)?;
+    // An explicit --time must not be replaced with the current time by --touch.
+    let options = &WriteOptions {
+        touch: options.touch && time.is_none(),
+        ..options.clone()
+    };
+
+    if let Some(dpi) = dpi {
+        if !(dpi.is_finite() && dpi > 0.0) {
+            anyhow::bail!("DPI must be a positive number, got {}", dpi);
+        }
+        let phys = PhysicalDimensions::from_dpi(dpi);
+        png.set_physical_dimensions(&phys);
+        println!("Resolution set to {}", phys);
+    }
+    if let Some(time) = time {
+        let time = Time::from_str(&time).map_err(anyhow::Error::msg)?;
+        png.set_time(&time);
+        println!("Modification time set to {}", time);
+    }
+
+    let output_path = resolve_output(&input, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    println!("Written to {:?}", output_path);
+    Ok(())
+}
+
+pub fn transplant(
+    source: PathBuf,
+    target: PathBuf,
+    output_file: Option<PathBuf>,
+    include_unsafe: bool,
+    options: &WriteOptions,
+) -> Result<()> {
+    let source_bytes =
+        fs::read(&source).with_context(|| format!("Failed to read file: {:?}", source))?;
+    let source_png = Png::try_from(source_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", source))?;
+
+    let target_bytes =
+        fs::read(&target).with_context(|| format!("Failed to read file: {:?}", target))?;
+    let mut png = Png::try_from(target_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", target))?;
+
+    let copied = if include_unsafe {
+        png.copy_all_ancillary_from(&source_png)
     } else {
-        println!("Chunks in the PNG file:");
-        for chunk in &png.chunks {
-            println!("{}", chunk);
+        let (copied, dropped) = png.copy_ancillary_from(&source_png);
+        for chunk_type in dropped {
+            println!(
+                "Skipped unsafe-to-copy chunk '{}' because the images differ",
+                chunk_type
+            );
         }
+        copied
+    };
+
+    let output_path = resolve_output(&target, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    println!(
+        "Copied {} chunks from {:?} to {:?}",
+        copied, source, output_path
+    );
+    Ok(())
+}
+
+/// Prints the chunk-level differences and returns whether the files are structurally equal.
+pub fn diff(left: PathBuf, right: PathBuf) -> Result<bool> {
+    let left_bytes = fs::read(&left).with_context(|| format!("Failed to read file: {:?}", left))?;
+    let left_png = Png::try_from(left_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", left))?;
+
+    let right_bytes =
+        fs::read(&right).with_context(|| format!("Failed to read file: {:?}", right))?;
+    let right_png = Png::try_from(right_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to parse PNG file: {:?}", right))?;
+
+    let diff = PngDiff::new(&left_png, &right_png);
+    print!("{}", diff);
+
+    Ok(diff.is_equal())
+}
+
+/// Prints every structural problem and warning, and returns whether the file is valid.
+/// Warnings alone do not make a file invalid.
+pub fn validate(input: Input, out: &mut dyn Write) -> Result<bool> {
+    let file_bytes = get_png_bytes(&input)?;
+
+    let png = Png::try_from(file_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse PNG data")?;
+
+    let problems = validate::validate(&png);
+    let warnings = validate::warnings(&png);
+    if problems.is_empty() && warnings.is_empty() {
+        writeln!(out, "No problems found")?;
+    }
+    for problem in &problems {
+        writeln!(out, "{}", problem)?;
     }
+    for warning in &warnings {
+        writeln!(out, "warning: {}", warning)?;
+    }
+
+    Ok(problems.is_empty())
+}
+
+pub fn repair(input: PathBuf, output_file: Option<PathBuf>, options: &WriteOptions) -> Result<()> {
+    let file_bytes =
+        fs::read(&input).with_context(|| format!("Failed to read file: {:?}", input))?;
+
+    let (mut png, fixes) = repair::repair(&file_bytes)
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to repair PNG file: {:?}", input))?;
+
+    if fixes.is_empty() {
+        println!("No problems found in {:?}", input);
+        return Ok(());
+    }
+
+    for fix in &fixes {
+        println!("{}", fix);
+    }
+
+    let output_path = resolve_output(&input, output_file, options)?;
+    save_png(&mut png, &output_path, options)?;
+
+    println!("Repaired PNG written to {:?}", output_path);
+    Ok(())
+}
+
+pub fn capacity(input: Input, json: bool) -> Result<()> {
+    let file_bytes = get_png_bytes(&input)?;
+
+    let png = Png::try_from(file_bytes.as_slice())
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse PNG data")?;
+
+    let report = CapacityReport::for_png(&png)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to compute capacity")?;
 
+    if json {
+        println!("{}", report.to_json());
+    } else {
+        print!("{}", report);
+    }
+
+    Ok(())
+}
+
+pub fn analyze(input: Input, json: bool, pixels: bool, out: &mut dyn Write) -> Result<()> {
+    let file_bytes = get_png_bytes(&input)?;
+
+    let (png, issues) = repair::parse_lenient(&file_bytes)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to parse PNG data")?;
+
+    let mut analysis = Analysis::for_png(&png).with_issues(&issues);
+    if pixels {
+        analysis = analysis.with_pixels(&png);
+    }
+    if json {
+        writeln!(out, "{}", analysis.to_json())?;
+    } else {
+        write!(out, "{}", analysis)?;
+    }
     Ok(())
 }
@@ -8,11 +8,12 @@ use base64::{engine::general_purpose::STANDARD, Engine as _};
 
 const SALT_LENGTH: usize = 32;
 const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
 
 pub struct Crypto;
 
 impl Crypto {
-    pub fn encrypt(plaintext: &str, password: &str) -> Result<EncryptedData> {
+    pub fn encrypt(plaintext: impl AsRef<[u8]>, password: &str) -> Result<EncryptedData> {
         let mut rng = OsRng;
         let key = Aes256Gcm::generate_key(&mut rng);
         let nonce = Aes256Gcm::generate_nonce(&mut rng);
@@ -26,24 +27,27 @@ impl Crypto {
         let cipher = Aes256Gcm::new(password_key);
 
         let ciphertext = cipher
-            .encrypt(&nonce, plaintext.as_bytes())
+            .encrypt(&nonce, plaintext.as_ref())
             .map_err(|e| anyhow::anyhow!("Encryption failed: {}", e))?;
 
         Ok(EncryptedData::new(salt, nonce_bytes, ciphertext))
     }
 
     pub fn decrypt(encrypted_data: &EncryptedData, password: &str) -> Result<String> {
+        let plaintext = Self::decrypt_bytes(encrypted_data, password)?;
+        String::from_utf8(plaintext).context("Invalid UTF-8 in decrypted data")
+    }
+
+    pub fn decrypt_bytes(encrypted_data: &EncryptedData, password: &str) -> Result<Vec<u8>> {
         let key_bytes = Self::derive_key_from_password(password, encrypted_data.salt());
         let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
 
         let cipher = Aes256Gcm::new(key);
         let nonce = Nonce::from_slice(encrypted_data.nonce());
 
-        let plaintext = cipher
+        cipher
             .decrypt(nonce, encrypted_data.ciphertext())
-            .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))?;
-
-        String::from_utf8(plaintext).context("Invalid UTF-8 in decrypted data")
+            .map_err(|e| anyhow::anyhow!("Decryption failed: {}", e))
     }
 
     fn derive_key_from_password(password: &str, salt: &[u8]) -> [u8; 32] {
@@ -92,6 +96,17 @@ impl EncryptedData {
         STANDARD.encode(combined)
     }
 
+    /// Length of the base64 string produced for a plaintext of `plaintext_len` bytes.
+    pub fn encoded_len(plaintext_len: usize) -> usize {
+        let raw = SALT_LENGTH + NONCE_LENGTH + plaintext_len + TAG_LENGTH;
+        raw.div_ceil(3) * 4
+    }
+
+    /// Largest plaintext whose encrypted base64 form fits in `encoded_len` bytes.
+    pub fn max_plaintext_len(encoded_len: usize) -> usize {
+        (encoded_len / 4 * 3).saturating_sub(SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH)
+    }
+
     pub fn from_base64(encoded: &str) -> Result<Self> {
         let decoded = STANDARD
             .decode(encoded)
@@ -128,6 +143,18 @@ mod tests {
         assert_eq!(message, decrypted);
     }
 
+    #[test]
+    fn encrypt_decrypt_bytes() {
+        let message = [0u8, 0xff, 0xfe, 0x80];
+        let encrypted = Crypto::encrypt(message, "password").unwrap();
+
+        assert_eq!(
+            Crypto::decrypt_bytes(&encrypted, "password").unwrap(),
+            message
+        );
+        assert!(Crypto::decrypt(&encrypted, "password").is_err());
+    }
+
     #[test]
     fn wrong_password() {
         let message = "Secret Message!";
@@ -152,6 +179,16 @@ mod tests {
         assert!(!encrypted.ciphertext().is_empty());
     }
 
+    #[test]
+    fn encoded_length() {
+        let message = "Secret Message!";
+        let encrypted = Crypto::encrypt(message, "password").unwrap();
+
+        let encoded_len = EncryptedData::encoded_len(message.len());
+        assert_eq!(encrypted.to_base64().len(), encoded_len);
+        assert_eq!(EncryptedData::max_plaintext_len(encoded_len), message.len());
+    }
+
     #[test]
     fn base64_encoding() {
         let message = "Secret Message!";
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::chunk::Chunk;
+use crate::png::Png;
+use crate::text::TextChunk;
+
+/// Position, size and CRC of one chunk in one of the compared files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInfo {
+    pub chunk_type: String,
+    pub index: usize,
+    pub length: u32,
+    pub crc: u32,
+}
+
+impl ChunkInfo {
+    fn new(index: usize, chunk: &Chunk) -> Self {
+        ChunkInfo {
+            chunk_type: chunk.chunk_type().to_string(),
+            index,
+            length: chunk.length(),
+            crc: chunk.crc(),
+        }
+    }
+}
+
+impl fmt::Display for ChunkInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' #{} ({} bytes, crc {:08x})",
+            self.chunk_type, self.index, self.length, self.crc
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChunkChange {
+    Added(ChunkInfo),
+    Removed(ChunkInfo),
+    Reordered {
+        left: ChunkInfo,
+        right: ChunkInfo,
+    },
+    Modified {
+        left: ChunkInfo,
+        right: ChunkInfo,
+        /// Line diff of the text, for tEXt, zTXt and iTXt chunks.
+        text_diff: Option<Vec<String>>,
+    },
+}
+
+impl fmt::Display for ChunkChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkChange::Added(info) => write!(f, "+ added {}", info),
+            ChunkChange::Removed(info) => write!(f, "- removed {}", info),
+            ChunkChange::Reordered { left, right } => write!(
+                f,
+                "~ moved '{}' from #{} to #{}",
+                left.chunk_type, left.index, right.index
+            ),
+            ChunkChange::Modified {
+                left,
+                right,
+                text_diff,
+            } => {
+                write!(f, "* modified {} -> {}", left, right)?;
+                for line in text_diff.iter().flatten() {
+                    write!(f, "\n    {}", line)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Chunk-level differences between two PNG files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PngDiff {
+    pub changes: Vec<ChunkChange>,
+    pub trailer_changed: bool,
+}
+
+impl PngDiff {
+    /// Chunks are matched by type and occurrence, so the second tEXt chunk on the left is
+    /// compared with the second tEXt chunk on the right.
+    pub fn new(left: &Png, right: &Png) -> Self {
+        let left_keys = occurrence_keys(left);
+        let right_keys = occurrence_keys(right);
+        let right_lookup: HashMap<&(String, usize), usize> = right_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key, i))
+            .collect();
+        let left_lookup: HashMap<&(String, usize), usize> = left_keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| (key, i))
+            .collect();
+
+        let mut changes = Vec::new();
+        let mut matched = Vec::new();
+
+        for (i, key) in left_keys.iter().enumerate() {
+            let left_chunk = &left.chunks()[i];
+            match right_lookup.get(key) {
+                None => changes.push(ChunkChange::Removed(ChunkInfo::new(i, left_chunk))),
+                Some(&j) => {
+                    matched.push((i, j));
+                    let right_chunk = &right.chunks()[j];
+                    if left_chunk.as_bytes() != right_chunk.as_bytes() {
+                        changes.push(ChunkChange::Modified {
+                            left: ChunkInfo::new(i, left_chunk),
+                            right: ChunkInfo::new(j, right_chunk),
+                            text_diff: text_diff(left_chunk, right_chunk),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (j, key) in right_keys.iter().enumerate() {
+            if !left_lookup.contains_key(key) {
+                changes.push(ChunkChange::Added(ChunkInfo::new(j, &right.chunks()[j])));
+            }
+        }
+
+        // The matched chunks that keep their relative order form the longest increasing
+        // run of right positions; only the chunks outside it count as moved.
+        let right_positions: Vec<usize> = matched.iter().map(|&(_, j)| j).collect();
+        let in_order = longest_increasing(&right_positions);
+        for (&(i, j), &in_order) in matched.iter().zip(&in_order) {
+            if !in_order {
+                changes.push(ChunkChange::Reordered {
+                    left: ChunkInfo::new(i, &left.chunks()[i]),
+                    right: ChunkInfo::new(j, &right.chunks()[j]),
+                });
+            }
+        }
+
+        PngDiff {
+            changes,
+            trailer_changed: left.trailer() != right.trailer(),
+        }
+    }
+
+    pub fn is_equal(&self) -> bool {
+        self.changes.is_empty() && !self.trailer_changed
+    }
+}
+
+impl fmt::Display for PngDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_equal() {
+            return writeln!(f, "Files are structurally equal");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        if self.trailer_changed {
+            writeln!(f, "* data after IEND differs")?;
+        }
+        Ok(())
+    }
+}
+
+fn occurrence_keys(png: &Png) -> Vec<(String, usize)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    png.chunks()
+        .iter()
+        .map(|chunk| {
+            let chunk_type = chunk.chunk_type().to_string();
+            let count = seen.entry(chunk_type.clone()).or_default();
+            *count += 1;
+            (chunk_type, *count)
+        })
+        .collect()
+}
+
+/// Marks the values that belong to one longest strictly increasing subsequence.
+fn longest_increasing(values: &[usize]) -> Vec<bool> {
+    // tails[k] is the index of the smallest value ending an increasing run of length k + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+    for (index, &value) in values.iter().enumerate() {
+        let length = tails.partition_point(|&t| values[t] < value);
+        if length > 0 {
+            previous[index] = Some(tails[length - 1]);
+        }
+        if length == tails.len() {
+            tails.push(index);
+        } else {
+            tails[length] = index;
+        }
+    }
+
+    let mut members = vec![false; values.len()];
+    let mut next = tails.last().copied();
+    while let Some(index) = next {
+        members[index] = true;
+        next = previous[index];
+    }
+    members
+}
+
+fn text_diff(left: &Chunk, right: &Chunk) -> Option<Vec<String>> {
+    let left = TextChunk::try_from(left).ok()?;
+    let right = TextChunk::try_from(right).ok()?;
+
+    let mut lines = Vec::new();
+    if left.keyword != right.keyword {
+        lines.push(format!("- keyword: {}", left.keyword));
+        lines.push(format!("+ keyword: {}", right.keyword));
+    }
+    lines.extend(line_diff(&left.text, &right.text));
+    Some(lines)
+}
+
+/// Minimal line diff based on the longest common subsequence.
+fn line_diff(left: &str, right: &str) -> Vec<String> {
+    let a: Vec<&str> = left.lines().collect();
+    let b: Vec<&str> = right.lines().collect();
+
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::text::TextKind;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &str) -> Chunk {
+        Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            data.as_bytes().to_vec(),
+        )
+    }
+
+    fn text(keyword: &str, text: &str) -> Chunk {
+        TextChunk::new(TextKind::Text, keyword, text)
+            .to_chunk()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_equal_files() {
+        let png = Png::from_chunks(vec![chunk("IHDR", "a"), chunk("IEND", "")]);
+        let other = Png::from_chunks(vec![chunk("IHDR", "a"), chunk("IEND", "")]);
+        let diff = PngDiff::new(&png, &other);
+        assert!(diff.is_equal());
+    }
+
+    #[test]
+    fn test_added_removed_modified() {
+        let left = Png::from_chunks(vec![
+            chunk("IHDR", "a"),
+            chunk("ruSt", "old"),
+            chunk("IEND", ""),
+        ]);
+        let right = Png::from_chunks(vec![
+            chunk("IHDR", "b"),
+            chunk("teSt", "new"),
+            chunk("IEND", ""),
+        ]);
+
+        let diff = PngDiff::new(&left, &right);
+
+        assert!(!diff.is_equal());
+        assert!(
+            matches!(&diff.changes[0], ChunkChange::Modified { left, .. } if left.chunk_type == "IHDR")
+        );
+        assert!(
+            matches!(&diff.changes[1], ChunkChange::Removed(info) if info.chunk_type == "ruSt")
+        );
+        assert!(matches!(&diff.changes[2], ChunkChange::Added(info) if info.chunk_type == "teSt"));
+        assert_eq!(diff.changes.len(), 3);
+    }
+
+    #[test]
+    fn test_reordered() {
+        let left = Png::from_chunks(vec![chunk("ruSt", "1"), chunk("teSt", "2")]);
+        let right = Png::from_chunks(vec![chunk("teSt", "2"), chunk("ruSt", "1")]);
+
+        let diff = PngDiff::new(&left, &right);
+
+        assert!(!diff.is_equal());
+        assert!(diff
+            .changes
+            .iter()
+            .all(|c| matches!(c, ChunkChange::Reordered { .. })));
+    }
+
+    #[test]
+    fn test_one_chunk_moved() {
+        let types = ["IHDR", "gAMA", "pHYs", "tEXt", "IDAT", "IEND"];
+        let left = Png::from_chunks(types.iter().map(|t| chunk(t, "")).collect());
+        let right = Png::from_chunks(
+            ["IHDR", "pHYs", "tEXt", "IDAT", "gAMA", "IEND"]
+                .iter()
+                .map(|t| chunk(t, ""))
+                .collect(),
+        );
+
+        let diff = PngDiff::new(&left, &right);
+
+        assert_eq!(diff.changes.len(), 1);
+        assert!(matches!(
+            &diff.changes[0],
+            ChunkChange::Reordered { left, right } if left.index == 1 && right.index == 4
+        ));
+    }
+
+    #[test]
+    fn test_longest_increasing() {
+        assert_eq!(
+            longest_increasing(&[3, 0, 1, 4, 2]),
+            [false, true, true, false, true]
+        );
+        assert!(longest_increasing(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_text_diff() {
+        let left = Png::from_chunks(vec![text("Comment", "one\ntwo\nthree")]);
+        let right = Png::from_chunks(vec![text("Comment", "one\n2\nthree")]);
+
+        let diff = PngDiff::new(&left, &right);
+
+        let ChunkChange::Modified { text_diff, .. } = &diff.changes[0] else {
+            panic!("expected a modified chunk");
+        };
+        assert_eq!(text_diff.as_ref().unwrap(), &["- two", "+ 2"]);
+    }
+
+    #[test]
+    fn test_trailer_changed() {
+        let left = Png::from_chunks(vec![chunk("IEND", "")]);
+        let mut right = Png::from_chunks(vec![chunk("IEND", "")]);
+        right.trailer = b"extra".to_vec();
+        assert!(PngDiff::new(&left, &right).trailer_changed);
+    }
+}
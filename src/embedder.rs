@@ -0,0 +1,339 @@
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::crypto::{Crypto, EncryptedData};
+use crate::palette_stego;
+use crate::png::Png;
+use crate::trailer;
+
+/// Where an [`Embedder`] hides its message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// In a chunk of its own.
+    Chunk,
+    /// In the order of the PLTE entries of an indexed image.
+    Palette,
+    /// In the bytes following the IEND chunk.
+    Trailer,
+}
+
+/// Hides messages in PNG images, reads them back and removes them.
+///
+/// Works on [`Png`] values or on file contents, never touches the file system
+/// and never prints. Problems are returned as errors, and advice that does not
+/// stop embedding is available from [`Embedder::warnings`].
+///
+/// ```
+/// # use std::str::FromStr;
+/// # use pngme::{chunk_type::ChunkType, Embedder};
+/// # fn hide(image: &[u8]) -> Result<(), String> {
+/// let embedder = Embedder::chunk(ChunkType::from_str("ruSt")?).password("hunter2");
+/// let stego = embedder.embed_bytes(image, "Meet at noon")?;
+/// assert_eq!(embedder.extract_bytes(&stego)?.as_deref(), Some("Meet at noon"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Embedder {
+    method: Method,
+    chunk_type: Option<ChunkType>,
+    password: Option<String>,
+    force: bool,
+}
+
+impl Embedder {
+    /// An embedder for `method`. The chunk method also needs [`Embedder::chunk_type`].
+    pub fn new(method: Method) -> Self {
+        Embedder {
+            method,
+            chunk_type: None,
+            password: None,
+            force: false,
+        }
+    }
+
+    /// An embedder that stores messages in chunks of `chunk_type`.
+    pub fn chunk(chunk_type: ChunkType) -> Self {
+        Embedder::new(Method::Chunk).chunk_type(chunk_type)
+    }
+
+    pub fn chunk_type(mut self, chunk_type: ChunkType) -> Self {
+        self.chunk_type = Some(chunk_type);
+        self
+    }
+
+    /// Encrypts messages with `password` before embedding them, and decrypts
+    /// them when extracting.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Allows adding a second chunk of a type that must be unique, adding a
+    /// critical chunk and removing a critical chunk.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    fn required_chunk_type(&self) -> Result<&ChunkType, String> {
+        self.chunk_type
+            .as_ref()
+            .ok_or_else(|| "A chunk type is required for the chunk method".to_string())
+    }
+
+    /// Why embedding in `png` is refused unless [`Embedder::force`] is set, if it is.
+    pub fn refusal(&self, png: &Png) -> Option<String> {
+        let chunk_type = match self.method {
+            Method::Chunk => self.chunk_type.as_ref()?,
+            Method::Palette | Method::Trailer => return None,
+        };
+        if chunk_type.is_unique() && png.chunk_by_type(&chunk_type.to_string()).is_some() {
+            return Some(format!(
+                "The file already has a '{}' chunk and the PNG specification allows only one; force to add another anyway",
+                chunk_type
+            ));
+        }
+        if chunk_type.is_critical() {
+            return Some(format!(
+                "'{}' is a critical chunk type; adding one can make decoders refuse or misread the image. Use an ancillary type such as '{}', or force to add it anyway",
+                chunk_type,
+                suggestion(chunk_type)
+            ));
+        }
+        None
+    }
+
+    /// Advice about the chunk type that does not prevent embedding.
+    pub fn warnings(&self) -> Vec<String> {
+        match &self.chunk_type {
+            Some(chunk_type) if self.method == Method::Chunk && chunk_type.is_public() => {
+                vec![format!(
+                    "'{}' is a public chunk type reserved for the PNG specification; consider a private type such as '{}'",
+                    chunk_type,
+                    suggestion(chunk_type)
+                )]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Adds `message` to `png`.
+    pub fn embed(&self, png: &mut Png, message: &str) -> Result<(), String> {
+        self.embed_payload(png, message.as_bytes())
+    }
+
+    /// Adds arbitrary bytes to `png`, such as the contents of a file.
+    pub fn embed_payload(&self, png: &mut Png, message: &[u8]) -> Result<(), String> {
+        if let Some(reason) = self.refusal(png).filter(|_| !self.force) {
+            return Err(reason);
+        }
+        let payload = match &self.password {
+            Some(password) => Crypto::encrypt(message, password)
+                .map_err(|e| format!("Failed to encrypt message: {:#}", e))?
+                .to_base64()
+                .into_bytes(),
+            None => message.to_vec(),
+        };
+
+        match self.method {
+            Method::Chunk => {
+                let chunk_type = self.required_chunk_type()?;
+                if !chunk_type.is_reserved_bit_valid() {
+                    return Err(format!(
+                        "'{}' has the reserved bit set (third letter lowercase); use '{}' instead",
+                        chunk_type,
+                        suggestion(chunk_type)
+                    ));
+                }
+                png.append_chunk(Chunk::new(chunk_type.clone(), payload));
+            }
+            Method::Palette => palette_stego::embed(png, &payload)
+                .map_err(|e| format!("Failed to encode message in palette order: {}", e))?,
+            Method::Trailer => trailer::embed(png, &payload)
+                .map_err(|e| format!("Failed to encode message after IEND: {}", e))?,
+        }
+        Ok(())
+    }
+
+    /// Like [`Embedder::embed`], for the contents of a PNG file.
+    pub fn embed_bytes(&self, bytes: &[u8], message: &str) -> Result<Vec<u8>, String> {
+        let mut png = Png::try_from(bytes)?;
+        self.embed(&mut png, message)?;
+        Ok(png.as_bytes())
+    }
+
+    /// Reads back a message. Returns `None` when the chunk method finds no
+    /// chunk of its type.
+    pub fn extract(&self, png: &Png) -> Result<Option<String>, String> {
+        match self.extract_payload(png)? {
+            Some(payload) => String::from_utf8(payload)
+                .map(Some)
+                .map_err(|_| "Failed to decode message as string".to_string()),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads back a message as bytes, such as one embedded with
+    /// [`Embedder::embed_payload`] from a binary file.
+    pub fn extract_payload(&self, png: &Png) -> Result<Option<Vec<u8>>, String> {
+        let payload = match self.method {
+            Method::Chunk => {
+                let chunk_type = self.required_chunk_type()?.to_string();
+                let Some(chunk) = png.chunk_by_type(&chunk_type) else {
+                    return Ok(None);
+                };
+                chunk.data().to_vec()
+            }
+            Method::Palette => palette_stego::extract(png)
+                .map_err(|e| format!("Failed to decode message from palette order: {}", e))?,
+            Method::Trailer => trailer::extract(png)
+                .map_err(|e| format!("Failed to decode message after IEND: {}", e))?,
+        };
+
+        let Some(password) = &self.password else {
+            return Ok(Some(payload));
+        };
+        let encoded = String::from_utf8(payload)
+            .map_err(|_| "Failed to parse encrypted data: not base64".to_string())?;
+        let encrypted = EncryptedData::from_base64(&encoded)
+            .map_err(|e| format!("Failed to parse encrypted data: {:#}", e))?;
+        Crypto::decrypt_bytes(&encrypted, password)
+            .map(Some)
+            .map_err(|_| "Failed to decrypt message - wrong password or corrupted data".to_string())
+    }
+
+    /// Like [`Embedder::extract`], for the contents of a PNG file.
+    pub fn extract_bytes(&self, bytes: &[u8]) -> Result<Option<String>, String> {
+        self.extract(&Png::try_from(bytes)?)
+    }
+
+    /// Removes the first chunk of the embedder's type and returns it.
+    pub fn remove(&self, png: &mut Png) -> Result<Chunk, String> {
+        let chunk_type = self.required_chunk_type()?;
+        if chunk_type.is_critical() && !self.force {
+            return Err(format!(
+                "'{}' is a critical chunk; the image cannot be decoded without it. Force to remove it anyway",
+                chunk_type
+            ));
+        }
+        png.remove_first_chunk(&chunk_type.to_string())
+    }
+
+    /// Like [`Embedder::remove`], for the contents of a PNG file. Returns the new
+    /// contents and the removed chunk.
+    pub fn remove_bytes(&self, bytes: &[u8]) -> Result<(Vec<u8>, Chunk), String> {
+        let mut png = Png::try_from(bytes)?;
+        let chunk = self.remove(&mut png)?;
+        Ok((png.as_bytes(), chunk))
+    }
+}
+
+/// A private, ancillary type with the same letters and safe-to-copy bit.
+fn suggestion(chunk_type: &ChunkType) -> ChunkType {
+    ChunkType::private_ancillary(&chunk_type.to_string())
+        .safe_to_copy(chunk_type.is_safe_to_copy())
+        .build()
+        .unwrap_or_else(|_| chunk_type.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    fn chunk_type(name: &str) -> ChunkType {
+        ChunkType::from_str(name).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = testing_png().as_bytes();
+        for embedder in [
+            Embedder::chunk(chunk_type("ruSt")),
+            Embedder::new(Method::Trailer),
+        ] {
+            assert_eq!(embedder.extract_bytes(&bytes).ok().flatten(), None);
+            let stego = embedder.embed_bytes(&bytes, "hello").unwrap();
+            assert_eq!(
+                embedder.extract_bytes(&stego).unwrap().as_deref(),
+                Some("hello")
+            );
+        }
+
+        let mut png = testing_png();
+        let embedder = Embedder::chunk(chunk_type("ruSt"));
+        embedder.embed(&mut png, "bye").unwrap();
+        let removed = embedder.remove(&mut png).unwrap();
+        assert_eq!(removed.data(), b"bye");
+        assert_eq!(png.as_bytes(), testing_png().as_bytes());
+    }
+
+    #[test]
+    fn test_binary_payload() {
+        let payload = [0u8, 0xff, 0x89, b'P', 0xc3, 0x28];
+        for embedder in [
+            Embedder::chunk(chunk_type("ruSt")),
+            Embedder::new(Method::Trailer),
+            Embedder::new(Method::Trailer).password("secret"),
+        ] {
+            let mut png = testing_png();
+            embedder.embed_payload(&mut png, &payload).unwrap();
+            assert_eq!(
+                embedder.extract_payload(&png).unwrap().as_deref(),
+                Some(&payload[..])
+            );
+            assert!(embedder.extract(&png).is_err());
+        }
+    }
+
+    #[test]
+    fn test_password() {
+        let mut png = testing_png();
+        let embedder = Embedder::chunk(chunk_type("ruSt")).password("secret");
+        embedder.embed(&mut png, "hidden").unwrap();
+        assert_ne!(png.chunk_by_type("ruSt").unwrap().data(), b"hidden");
+        assert_eq!(embedder.extract(&png).unwrap().as_deref(), Some("hidden"));
+        let wrong = Embedder::chunk(chunk_type("ruSt")).password("guess");
+        assert!(wrong.extract(&png).is_err());
+    }
+
+    #[test]
+    fn test_refusals_and_warnings() {
+        let mut png = testing_png();
+        let critical = Embedder::chunk(chunk_type("RUST"));
+        assert!(critical.refusal(&png).unwrap().contains("'ruST'"));
+        assert!(critical.embed(&mut png, "x").is_err());
+        assert!(critical.clone().force(true).embed(&mut png, "x").is_ok());
+        assert!(critical.remove(&mut png).is_err());
+        assert!(critical.force(true).remove(&mut png).is_ok());
+
+        let unique = Embedder::chunk(chunk_type("tIME"));
+        assert_eq!(unique.refusal(&png), None);
+        assert_eq!(unique.warnings().len(), 1);
+        unique.embed(&mut png, "x").unwrap();
+        assert!(unique.embed(&mut png, "y").is_err());
+
+        assert!(Embedder::chunk(chunk_type("ruse"))
+            .force(true)
+            .embed(&mut png, "x")
+            .is_err());
+        assert!(Embedder::new(Method::Chunk).embed(&mut png, "x").is_err());
+        assert!(Embedder::chunk(chunk_type("ruSt")).warnings().is_empty());
+    }
+}
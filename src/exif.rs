@@ -0,0 +1,935 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROP_POINTER: u16 = 0xA005;
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    /// "II", Intel byte order.
+    Little,
+    /// "MM", Motorola byte order.
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u64(self, bytes: &[u8]) -> u64 {
+        let (first, second) = (self.u32(&bytes[..4]) as u64, self.u32(&bytes[4..]) as u64);
+        match self {
+            ByteOrder::Little => second << 32 | first,
+            ByteOrder::Big => first << 32 | second,
+        }
+    }
+
+    fn u16_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u64_bytes(self, value: u64) -> [u8; 8] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteOrder::Little => write!(f, "little-endian"),
+            ByteOrder::Big => write!(f, "big-endian"),
+        }
+    }
+}
+
+/// The image file directories of an EXIF block, in the order they are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IfdKind {
+    /// IFD0, describing the main image.
+    Primary,
+    Exif,
+    Gps,
+    Interop,
+    /// IFD1, describing the embedded thumbnail.
+    Thumbnail,
+}
+
+impl IfdKind {
+    pub const ALL: [IfdKind; 5] = [
+        IfdKind::Primary,
+        IfdKind::Exif,
+        IfdKind::Gps,
+        IfdKind::Interop,
+        IfdKind::Thumbnail,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IfdKind::Primary => "ifd0",
+            IfdKind::Exif => "exif",
+            IfdKind::Gps => "gps",
+            IfdKind::Interop => "interop",
+            IfdKind::Thumbnail => "ifd1",
+        }
+    }
+
+    /// The IFD holding the pointer to this one, and the pointer's tag.
+    fn pointer(&self) -> Option<(IfdKind, u16)> {
+        match self {
+            IfdKind::Exif => Some((IfdKind::Primary, EXIF_POINTER)),
+            IfdKind::Gps => Some((IfdKind::Primary, GPS_POINTER)),
+            IfdKind::Interop => Some((IfdKind::Exif, INTEROP_POINTER)),
+            IfdKind::Primary | IfdKind::Thumbnail => None,
+        }
+    }
+}
+
+impl FromStr for IfdKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IfdKind::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("Unknown EXIF IFD: {}", s))
+    }
+}
+
+impl fmt::Display for IfdKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    Byte,
+    Ascii,
+    Short,
+    Long,
+    Rational,
+    SByte,
+    Undefined,
+    SShort,
+    SLong,
+    SRational,
+    Float,
+    Double,
+}
+
+impl FieldType {
+    /// Size in bytes of a single value of this type.
+    pub fn size(&self) -> usize {
+        match self {
+            FieldType::Byte | FieldType::Ascii | FieldType::SByte | FieldType::Undefined => 1,
+            FieldType::Short | FieldType::SShort => 2,
+            FieldType::Long | FieldType::SLong | FieldType::Float => 4,
+            FieldType::Rational | FieldType::SRational | FieldType::Double => 8,
+        }
+    }
+}
+
+impl TryFrom<u16> for FieldType {
+    type Error = String;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => FieldType::Byte,
+            2 => FieldType::Ascii,
+            3 => FieldType::Short,
+            4 => FieldType::Long,
+            5 => FieldType::Rational,
+            6 => FieldType::SByte,
+            7 => FieldType::Undefined,
+            8 => FieldType::SShort,
+            9 => FieldType::SLong,
+            10 => FieldType::SRational,
+            11 => FieldType::Float,
+            12 => FieldType::Double,
+            _ => return Err(format!("Unknown EXIF field type: {}", value)),
+        })
+    }
+}
+
+impl From<FieldType> for u16 {
+    fn from(value: FieldType) -> Self {
+        match value {
+            FieldType::Byte => 1,
+            FieldType::Ascii => 2,
+            FieldType::Short => 3,
+            FieldType::Long => 4,
+            FieldType::Rational => 5,
+            FieldType::SByte => 6,
+            FieldType::Undefined => 7,
+            FieldType::SShort => 8,
+            FieldType::SLong => 9,
+            FieldType::SRational => 10,
+            FieldType::Float => 11,
+            FieldType::Double => 12,
+        }
+    }
+}
+
+/// Well-known tags. Tags of IFD0 are also valid in IFD1.
+const TAGS: &[(IfdKind, u16, &str, FieldType)] = &[
+    (
+        IfdKind::Primary,
+        0x010E,
+        "ImageDescription",
+        FieldType::Ascii,
+    ),
+    (IfdKind::Primary, 0x010F, "Make", FieldType::Ascii),
+    (IfdKind::Primary, 0x0110, "Model", FieldType::Ascii),
+    (IfdKind::Primary, 0x0112, "Orientation", FieldType::Short),
+    (IfdKind::Primary, 0x011A, "XResolution", FieldType::Rational),
+    (IfdKind::Primary, 0x011B, "YResolution", FieldType::Rational),
+    (IfdKind::Primary, 0x0128, "ResolutionUnit", FieldType::Short),
+    (IfdKind::Primary, 0x0131, "Software", FieldType::Ascii),
+    (IfdKind::Primary, 0x0132, "DateTime", FieldType::Ascii),
+    (IfdKind::Primary, 0x013B, "Artist", FieldType::Ascii),
+    (IfdKind::Primary, 0x8298, "Copyright", FieldType::Ascii),
+    (IfdKind::Exif, 0x829A, "ExposureTime", FieldType::Rational),
+    (IfdKind::Exif, 0x829D, "FNumber", FieldType::Rational),
+    (IfdKind::Exif, 0x8827, "ISOSpeedRatings", FieldType::Short),
+    (IfdKind::Exif, 0x9000, "ExifVersion", FieldType::Undefined),
+    (IfdKind::Exif, 0x9003, "DateTimeOriginal", FieldType::Ascii),
+    (IfdKind::Exif, 0x9004, "DateTimeDigitized", FieldType::Ascii),
+    (IfdKind::Exif, 0x920A, "FocalLength", FieldType::Rational),
+    (IfdKind::Exif, 0x927C, "MakerNote", FieldType::Undefined),
+    (IfdKind::Exif, 0x9286, "UserComment", FieldType::Undefined),
+    (IfdKind::Exif, 0xA001, "ColorSpace", FieldType::Short),
+    (IfdKind::Exif, 0xA002, "PixelXDimension", FieldType::Long),
+    (IfdKind::Exif, 0xA003, "PixelYDimension", FieldType::Long),
+    (IfdKind::Exif, 0xA430, "CameraOwnerName", FieldType::Ascii),
+    (IfdKind::Exif, 0xA431, "BodySerialNumber", FieldType::Ascii),
+    (IfdKind::Exif, 0xA433, "LensMake", FieldType::Ascii),
+    (IfdKind::Exif, 0xA434, "LensModel", FieldType::Ascii),
+    (IfdKind::Exif, 0xA435, "LensSerialNumber", FieldType::Ascii),
+    (IfdKind::Gps, 0x0000, "GPSVersionID", FieldType::Byte),
+    (IfdKind::Gps, 0x0001, "GPSLatitudeRef", FieldType::Ascii),
+    (IfdKind::Gps, 0x0002, "GPSLatitude", FieldType::Rational),
+    (IfdKind::Gps, 0x0003, "GPSLongitudeRef", FieldType::Ascii),
+    (IfdKind::Gps, 0x0004, "GPSLongitude", FieldType::Rational),
+    (IfdKind::Gps, 0x0005, "GPSAltitudeRef", FieldType::Byte),
+    (IfdKind::Gps, 0x0006, "GPSAltitude", FieldType::Rational),
+    (IfdKind::Gps, 0x0007, "GPSTimeStamp", FieldType::Rational),
+    (IfdKind::Gps, 0x0011, "GPSImgDirection", FieldType::Rational),
+    (IfdKind::Gps, 0x001D, "GPSDateStamp", FieldType::Ascii),
+];
+
+fn table_kind(kind: IfdKind) -> IfdKind {
+    match kind {
+        IfdKind::Thumbnail => IfdKind::Primary,
+        kind => kind,
+    }
+}
+
+fn known_tag(kind: IfdKind, tag: u16) -> Option<(&'static str, FieldType)> {
+    TAGS.iter()
+        .find(|(k, t, _, _)| *k == table_kind(kind) && *t == tag)
+        .map(|&(_, _, name, field_type)| (name, field_type))
+}
+
+pub fn tag_name(kind: IfdKind, tag: u16) -> String {
+    known_tag(kind, tag)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("0x{:04x}", tag))
+}
+
+/// Resolves a tag written as `[ifd:]tag`, where `tag` is a known name such as
+/// `GPSLatitude` or a number such as `0x010f`. Numbers without an IFD prefix
+/// refer to IFD0.
+pub fn resolve_tag(spec: &str) -> Result<(IfdKind, u16), String> {
+    let (kind, tag) = match spec.split_once(':') {
+        Some((kind, tag)) => (Some(IfdKind::from_str(kind)?), tag),
+        None => (None, spec),
+    };
+
+    let number = match tag.strip_prefix("0x").or_else(|| tag.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => tag.parse::<u16>().ok(),
+    };
+    if let Some(number) = number {
+        return Ok((kind.unwrap_or(IfdKind::Primary), number));
+    }
+
+    let &(table, number, _, _) = TAGS
+        .iter()
+        .find(|(k, _, name, _)| {
+            name.eq_ignore_ascii_case(tag) && kind.is_none_or(|kind| table_kind(kind) == *k)
+        })
+        .ok_or_else(|| format!("Unknown EXIF tag: {}", spec))?;
+    Ok((kind.unwrap_or(table), number))
+}
+
+/// One IFD entry. `data` holds the values in the byte order of the EXIF block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub tag: u16,
+    pub field_type: FieldType,
+    pub count: u32,
+    pub data: Vec<u8>,
+}
+
+impl Entry {
+    /// Parses a comma-separated list of values (or a plain string for ASCII and
+    /// UNDEFINED) into an entry.
+    pub fn parse(
+        tag: u16,
+        field_type: FieldType,
+        value: &str,
+        byte_order: ByteOrder,
+    ) -> Result<Self, String> {
+        let invalid = |item: &str| format!("Invalid {:?} value: {}", field_type, item);
+        let data = match field_type {
+            FieldType::Ascii => value.bytes().chain([0]).collect(),
+            FieldType::Undefined => value.as_bytes().to_vec(),
+            _ => {
+                let mut data = Vec::new();
+                for item in value.split(',').map(str::trim) {
+                    match field_type {
+                        FieldType::Byte => {
+                            data.push(item.parse::<u8>().map_err(|_| invalid(item))?)
+                        }
+                        FieldType::SByte => {
+                            data.push(item.parse::<i8>().map_err(|_| invalid(item))? as u8)
+                        }
+                        FieldType::Short => data
+                            .extend(byte_order.u16_bytes(item.parse().map_err(|_| invalid(item))?)),
+                        FieldType::SShort => data.extend(
+                            byte_order
+                                .u16_bytes(item.parse::<i16>().map_err(|_| invalid(item))? as u16),
+                        ),
+                        FieldType::Long => data
+                            .extend(byte_order.u32_bytes(item.parse().map_err(|_| invalid(item))?)),
+                        FieldType::SLong => data.extend(
+                            byte_order
+                                .u32_bytes(item.parse::<i32>().map_err(|_| invalid(item))? as u32),
+                        ),
+                        FieldType::Rational | FieldType::SRational => {
+                            let (numerator, denominator) =
+                                item.split_once('/').unwrap_or((item, "1"));
+                            let parse = |n: &str| -> Result<u32, String> {
+                                if field_type == FieldType::Rational {
+                                    n.trim().parse::<u32>().map_err(|_| invalid(item))
+                                } else {
+                                    n.trim()
+                                        .parse::<i32>()
+                                        .map(|n| n as u32)
+                                        .map_err(|_| invalid(item))
+                                }
+                            };
+                            data.extend(byte_order.u32_bytes(parse(numerator)?));
+                            data.extend(byte_order.u32_bytes(parse(denominator)?));
+                        }
+                        FieldType::Float => {
+                            data.extend(byte_order.u32_bytes(
+                                item.parse::<f32>().map_err(|_| invalid(item))?.to_bits(),
+                            ))
+                        }
+                        FieldType::Double => {
+                            data.extend(byte_order.u64_bytes(
+                                item.parse::<f64>().map_err(|_| invalid(item))?.to_bits(),
+                            ))
+                        }
+                        FieldType::Ascii | FieldType::Undefined => unreachable!(),
+                    }
+                }
+                data
+            }
+        };
+
+        Ok(Entry {
+            tag,
+            field_type,
+            count: (data.len() / field_type.size()) as u32,
+            data,
+        })
+    }
+
+    /// The first value as an integer, for offsets and lengths.
+    fn as_u32(&self, byte_order: ByteOrder) -> Option<u32> {
+        match self.field_type {
+            FieldType::Short if self.data.len() >= 2 => Some(byte_order.u16(&self.data) as u32),
+            FieldType::Long if self.data.len() >= 4 => Some(byte_order.u32(&self.data)),
+            _ => None,
+        }
+    }
+
+    pub fn display_value(&self, byte_order: ByteOrder) -> String {
+        match self.field_type {
+            FieldType::Ascii => {
+                let text = self.data.strip_suffix(&[0]).unwrap_or(&self.data);
+                String::from_utf8_lossy(text).into_owned()
+            }
+            FieldType::Undefined
+                if !self.data.is_empty()
+                    && self.data.iter().all(|b| b.is_ascii_graphic() || *b == b' ') =>
+            {
+                String::from_utf8_lossy(&self.data).into_owned()
+            }
+            FieldType::Undefined if self.data.len() > 16 => format!("<{} bytes>", self.data.len()),
+            FieldType::Undefined => self
+                .data
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<Vec<_>>()
+                .join(" "),
+            field_type => self
+                .data
+                .chunks_exact(field_type.size())
+                .map(|b| match field_type {
+                    FieldType::Byte => b[0].to_string(),
+                    FieldType::SByte => (b[0] as i8).to_string(),
+                    FieldType::Short => byte_order.u16(b).to_string(),
+                    FieldType::SShort => (byte_order.u16(b) as i16).to_string(),
+                    FieldType::Long => byte_order.u32(b).to_string(),
+                    FieldType::SLong => (byte_order.u32(b) as i32).to_string(),
+                    FieldType::Rational => {
+                        format!("{}/{}", byte_order.u32(b), byte_order.u32(&b[4..]))
+                    }
+                    FieldType::SRational => format!(
+                        "{}/{}",
+                        byte_order.u32(b) as i32,
+                        byte_order.u32(&b[4..]) as i32
+                    ),
+                    FieldType::Float => f32::from_bits(byte_order.u32(b)).to_string(),
+                    FieldType::Double => f64::from_bits(byte_order.u64(b)).to_string(),
+                    FieldType::Ascii | FieldType::Undefined => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ifd {
+    pub kind: IfdKind,
+    /// Entries other than the pointers to sub-IFDs and the thumbnail, which are
+    /// recomputed when writing.
+    pub entries: Vec<Entry>,
+}
+
+/// Parsed contents of an eXIf chunk: a TIFF header followed by a chain of IFDs.
+///
+/// Writing lays the IFDs out afresh in the original byte order and recomputes every
+/// offset. Values are copied verbatim, so vendor MakerNote blocks that contain
+/// absolute offsets may no longer point at the right place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    pub byte_order: ByteOrder,
+    ifds: Vec<Ifd>,
+    thumbnail: Vec<u8>,
+}
+
+impl Exif {
+    pub fn new(byte_order: ByteOrder) -> Self {
+        Exif {
+            byte_order,
+            ifds: vec![Ifd {
+                kind: IfdKind::Primary,
+                entries: Vec::new(),
+            }],
+            thumbnail: Vec::new(),
+        }
+    }
+
+    pub fn ifds(&self) -> &[Ifd] {
+        &self.ifds
+    }
+
+    /// JPEG thumbnail referenced from IFD1, if any.
+    pub fn thumbnail(&self) -> &[u8] {
+        &self.thumbnail
+    }
+
+    pub fn ifd(&self, kind: IfdKind) -> Option<&Ifd> {
+        self.ifds.iter().find(|ifd| ifd.kind == kind)
+    }
+
+    pub fn get(&self, kind: IfdKind, tag: u16) -> Option<&Entry> {
+        self.ifd(kind)?
+            .entries
+            .iter()
+            .find(|entry| entry.tag == tag)
+    }
+
+    /// Adds the entry to the IFD, replacing an entry with the same tag.
+    pub fn set(&mut self, kind: IfdKind, entry: Entry) {
+        let index = match self.ifds.iter().position(|ifd| ifd.kind == kind) {
+            Some(index) => index,
+            None => {
+                self.ifds.push(Ifd {
+                    kind,
+                    entries: Vec::new(),
+                });
+                self.ifds.sort_by_key(|ifd| ifd.kind);
+                self.ifds.iter().position(|ifd| ifd.kind == kind).unwrap()
+            }
+        };
+        let entries = &mut self.ifds[index].entries;
+        match entries.iter_mut().find(|e| e.tag == entry.tag) {
+            Some(existing) => *existing = entry,
+            None => {
+                entries.push(entry);
+                entries.sort_by_key(|e| e.tag);
+            }
+        }
+    }
+
+    /// Sets a tag written as `[ifd:]tag` from its textual value. The field type of an
+    /// existing entry is kept; new entries use the type of the known tag, or ASCII.
+    pub fn set_tag(&mut self, spec: &str, value: &str) -> Result<(), String> {
+        let (kind, tag) = resolve_tag(spec)?;
+        let field_type = self
+            .get(kind, tag)
+            .map(|entry| entry.field_type)
+            .or_else(|| known_tag(kind, tag).map(|(_, field_type)| field_type))
+            .unwrap_or(FieldType::Ascii);
+        let entry = Entry::parse(tag, field_type, value, self.byte_order)?;
+        self.set(kind, entry);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, kind: IfdKind, tag: u16) -> Option<Entry> {
+        let ifd = self.ifds.iter_mut().find(|ifd| ifd.kind == kind)?;
+        let index = ifd.entries.iter().position(|entry| entry.tag == tag)?;
+        Some(ifd.entries.remove(index))
+    }
+
+    /// Removes a whole IFD, e.g. all GPS data. Removing the EXIF IFD also removes the
+    /// interoperability IFD it points to, and removing IFD1 drops the thumbnail.
+    /// IFD0 always exists, so removing it only clears its entries.
+    pub fn remove_ifd(&mut self, kind: IfdKind) -> bool {
+        if kind == IfdKind::Primary {
+            let primary = &mut self.ifds[0].entries;
+            let removed = !primary.is_empty();
+            primary.clear();
+            return removed;
+        }
+        if kind == IfdKind::Thumbnail {
+            self.thumbnail.clear();
+        }
+        let before = self.ifds.len();
+        self.ifds.retain(|ifd| {
+            ifd.kind != kind && !(kind == IfdKind::Exif && ifd.kind == IfdKind::Interop)
+        });
+        self.ifds.len() != before
+    }
+
+    fn has_entries(&self, kind: IfdKind) -> bool {
+        self.ifd(kind).is_some_and(|ifd| !ifd.entries.is_empty())
+    }
+
+    fn is_written(&self, kind: IfdKind) -> bool {
+        match kind {
+            IfdKind::Primary => true,
+            IfdKind::Exif => self.has_entries(IfdKind::Exif) || self.has_entries(IfdKind::Interop),
+            IfdKind::Thumbnail => self.has_entries(kind) || !self.thumbnail.is_empty(),
+            IfdKind::Gps | IfdKind::Interop => self.has_entries(kind),
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let order = self.byte_order;
+        let kinds: Vec<IfdKind> = IfdKind::ALL
+            .into_iter()
+            .filter(|&kind| self.is_written(kind))
+            .collect();
+        let own_entries = |kind: IfdKind| -> &[Entry] {
+            self.ifd(kind)
+                .map(|ifd| ifd.entries.as_slice())
+                .unwrap_or(&[])
+        };
+        let pointers = |parent: IfdKind| -> Vec<(u16, IfdKind)> {
+            kinds
+                .iter()
+                .filter_map(|kind| match kind.pointer() {
+                    Some((p, tag)) if p == parent => Some((tag, *kind)),
+                    _ => None,
+                })
+                .collect()
+        };
+        let has_thumbnail =
+            |kind: IfdKind| kind == IfdKind::Thumbnail && !self.thumbnail.is_empty();
+
+        // First pass: every IFD block is its entry table followed by its out-of-line values.
+        let mut offsets = Vec::new();
+        let mut cursor = 8;
+        for &kind in &kinds {
+            offsets.push(cursor);
+            let entries = own_entries(kind);
+            let count = entries.len() + pointers(kind).len() + has_thumbnail(kind) as usize;
+            let values: usize = entries
+                .iter()
+                .filter(|e| e.data.len() > 4)
+                .map(|e| e.data.len().next_multiple_of(2))
+                .sum();
+            cursor += 2 + 12 * count + 4 + values;
+        }
+        let offset_of = |kind: IfdKind| offsets[kinds.iter().position(|k| *k == kind).unwrap()];
+        let thumbnail_offset = cursor;
+
+        let mut bytes = match order {
+            ByteOrder::Little => b"II".to_vec(),
+            ByteOrder::Big => b"MM".to_vec(),
+        };
+        bytes.extend(order.u16_bytes(42));
+        bytes.extend(order.u32_bytes(8));
+
+        for &kind in &kinds {
+            let mut entries: Vec<Entry> = own_entries(kind).to_vec();
+            for (tag, child) in pointers(kind) {
+                entries.push(Entry {
+                    tag,
+                    field_type: FieldType::Long,
+                    count: 1,
+                    data: order.u32_bytes(offset_of(child) as u32).to_vec(),
+                });
+            }
+            if has_thumbnail(kind) {
+                entries.push(Entry {
+                    tag: THUMBNAIL_OFFSET,
+                    field_type: FieldType::Long,
+                    count: 1,
+                    data: order.u32_bytes(thumbnail_offset as u32).to_vec(),
+                });
+            }
+            entries.sort_by_key(|e| e.tag);
+
+            let mut values = Vec::new();
+            let values_offset = offset_of(kind) + 2 + 12 * entries.len() + 4;
+            bytes.extend(order.u16_bytes(entries.len() as u16));
+            for entry in &entries {
+                bytes.extend(order.u16_bytes(entry.tag));
+                bytes.extend(order.u16_bytes(entry.field_type.into()));
+                bytes.extend(order.u32_bytes(entry.count));
+                if entry.data.len() <= 4 {
+                    let mut inline = entry.data.clone();
+                    inline.resize(4, 0);
+                    bytes.extend(inline);
+                } else {
+                    bytes.extend(order.u32_bytes((values_offset + values.len()) as u32));
+                    values.extend_from_slice(&entry.data);
+                    if values.len() % 2 == 1 {
+                        values.push(0);
+                    }
+                }
+            }
+            let next = if kind == IfdKind::Primary && kinds.contains(&IfdKind::Thumbnail) {
+                offset_of(IfdKind::Thumbnail)
+            } else {
+                0
+            };
+            bytes.extend(order.u32_bytes(next as u32));
+            bytes.extend(values);
+        }
+
+        bytes.extend_from_slice(&self.thumbnail);
+        bytes
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    byte_order: ByteOrder,
+    visited: HashSet<usize>,
+}
+
+impl Reader<'_> {
+    fn slice(&self, offset: usize, length: usize) -> Result<&[u8], String> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| format!("EXIF offset {} is out of bounds", offset))
+    }
+
+    /// Reads the IFD at `offset`, returning its entries and the offset of the next IFD.
+    fn ifd(&mut self, offset: usize) -> Result<(Vec<Entry>, usize), String> {
+        if !self.visited.insert(offset) {
+            return Err("EXIF IFDs form a loop".to_string());
+        }
+        let order = self.byte_order;
+        let count = order.u16(self.slice(offset, 2)?) as usize;
+        let table = self.slice(offset + 2, 12 * count + 4)?;
+
+        let mut entries = Vec::with_capacity(count);
+        for raw in table.chunks_exact(12) {
+            let tag = order.u16(raw);
+            let field_type = FieldType::try_from(order.u16(&raw[2..]))
+                .map_err(|e| format!("{} in tag 0x{:04x}", e, tag))?;
+            let count = order.u32(&raw[4..]);
+            let length = (count as usize)
+                .checked_mul(field_type.size())
+                .ok_or_else(|| format!("EXIF tag 0x{:04x} is too large", tag))?;
+            let data = if length <= 4 {
+                raw[8..8 + length].to_vec()
+            } else {
+                self.slice(order.u32(&raw[8..]) as usize, length)?.to_vec()
+            };
+            entries.push(Entry {
+                tag,
+                field_type,
+                count,
+                data,
+            });
+        }
+
+        let next = order.u32(&table[12 * count..]) as usize;
+        Ok((entries, next))
+    }
+
+    /// Removes the pointer entry `tag` and reads the IFD it points to.
+    fn sub_ifd(
+        &mut self,
+        entries: &mut Vec<Entry>,
+        tag: u16,
+    ) -> Result<Option<Vec<Entry>>, String> {
+        let Some(offset) = take_u32(entries, tag, self.byte_order) else {
+            return Ok(None);
+        };
+        self.ifd(offset as usize).map(|(entries, _)| Some(entries))
+    }
+}
+
+fn take_u32(entries: &mut Vec<Entry>, tag: u16, byte_order: ByteOrder) -> Option<u32> {
+    let index = entries.iter().position(|entry| entry.tag == tag)?;
+    entries.remove(index).as_u32(byte_order)
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        // Some writers keep the "Exif\0\0" prefix of the JPEG APP1 segment.
+        let data = value.strip_prefix(b"Exif\0\0").unwrap_or(value);
+        if data.len() < 8 {
+            return Err("EXIF data is too short".to_string());
+        }
+        let byte_order = match &data[..2] {
+            b"II" => ByteOrder::Little,
+            b"MM" => ByteOrder::Big,
+            _ => return Err("Invalid EXIF byte order marker".to_string()),
+        };
+        if byte_order.u16(&data[2..]) != 42 {
+            return Err("Invalid TIFF magic number in EXIF data".to_string());
+        }
+
+        let mut reader = Reader {
+            data,
+            byte_order,
+            visited: HashSet::new(),
+        };
+        let mut ifds = Vec::new();
+        let mut thumbnail = Vec::new();
+
+        let (mut primary, next) = reader.ifd(byte_order.u32(&data[4..]) as usize)?;
+        if let Some(mut entries) = reader.sub_ifd(&mut primary, EXIF_POINTER)? {
+            if let Some(entries) = reader.sub_ifd(&mut entries, INTEROP_POINTER)? {
+                ifds.push(Ifd {
+                    kind: IfdKind::Interop,
+                    entries,
+                });
+            }
+            ifds.push(Ifd {
+                kind: IfdKind::Exif,
+                entries,
+            });
+        }
+        if let Some(entries) = reader.sub_ifd(&mut primary, GPS_POINTER)? {
+            ifds.push(Ifd {
+                kind: IfdKind::Gps,
+                entries,
+            });
+        }
+        ifds.push(Ifd {
+            kind: IfdKind::Primary,
+            entries: primary,
+        });
+
+        if next != 0 {
+            let (mut entries, _) = reader.ifd(next)?;
+            if let Some(offset) = take_u32(&mut entries, THUMBNAIL_OFFSET, byte_order) {
+                let length = entries
+                    .iter()
+                    .find(|entry| entry.tag == THUMBNAIL_LENGTH)
+                    .and_then(|entry| entry.as_u32(byte_order))
+                    .ok_or_else(|| "EXIF thumbnail has no length".to_string())?;
+                thumbnail = reader.slice(offset as usize, length as usize)?.to_vec();
+            }
+            ifds.push(Ifd {
+                kind: IfdKind::Thumbnail,
+                entries,
+            });
+        }
+
+        ifds.sort_by_key(|ifd| ifd.kind);
+        for ifd in &mut ifds {
+            ifd.entries.sort_by_key(|entry| entry.tag);
+        }
+        Ok(Exif {
+            byte_order,
+            ifds,
+            thumbnail,
+        })
+    }
+}
+
+impl fmt::Display for Exif {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "EXIF ({}):", self.byte_order)?;
+        for ifd in &self.ifds {
+            for entry in &ifd.entries {
+                writeln!(
+                    f,
+                    "  {}:{} = {}",
+                    ifd.kind,
+                    tag_name(ifd.kind, entry.tag),
+                    entry.display_value(self.byte_order)
+                )?;
+            }
+        }
+        if !self.thumbnail.is_empty() {
+            writeln!(f, "  ifd1: {} byte thumbnail", self.thumbnail.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IFD0 with Make and a pointer to an EXIF IFD holding ISOSpeedRatings.
+    const LITTLE_ENDIAN: &[u8] = &[
+        b'I', b'I', 42, 0, 8, 0, 0, 0, // header
+        2, 0, // IFD0 entry count
+        0x0F, 0x01, 2, 0, 6, 0, 0, 0, 38, 0, 0, 0, // Make, ASCII, 6, at 38
+        0x69, 0x87, 4, 0, 1, 0, 0, 0, 44, 0, 0, 0, // EXIF pointer, LONG, 1, 44
+        0, 0, 0, 0, // no next IFD
+        b'C', b'a', b'n', b'o', b'n', 0, // Make value
+        1, 0, // EXIF IFD entry count
+        0x27, 0x88, 3, 0, 1, 0, 0, 0, 100, 0, 0, 0, // ISOSpeedRatings, SHORT, 1, 100
+        0, 0, 0, 0, // no next IFD
+    ];
+
+    #[test]
+    fn test_parse_little_endian() {
+        let exif = Exif::try_from(LITTLE_ENDIAN).unwrap();
+        assert_eq!(exif.byte_order, ByteOrder::Little);
+        let make = exif.get(IfdKind::Primary, 0x010F).unwrap();
+        assert_eq!(make.display_value(exif.byte_order), "Canon");
+        let iso = exif.get(IfdKind::Exif, 0x8827).unwrap();
+        assert_eq!(iso.display_value(exif.byte_order), "100");
+        assert!(exif.get(IfdKind::Primary, EXIF_POINTER).is_none());
+    }
+
+    #[test]
+    fn test_write_keeps_layout() {
+        let exif = Exif::try_from(LITTLE_ENDIAN).unwrap();
+        assert_eq!(exif.as_bytes(), LITTLE_ENDIAN);
+    }
+
+    #[test]
+    fn test_app1_prefix() {
+        let data = [b"Exif\0\0".as_slice(), LITTLE_ENDIAN].concat();
+        assert_eq!(
+            Exif::try_from(data.as_slice()).unwrap().as_bytes(),
+            LITTLE_ENDIAN
+        );
+    }
+
+    #[test]
+    fn test_set_and_remove_gps() {
+        let mut exif = Exif::new(ByteOrder::Big);
+        exif.set_tag("Model", "PNGme").unwrap();
+        exif.set_tag("GPSLatitude", "51/1, 30/1, 1234/100").unwrap();
+        exif.set_tag("GPSLatitudeRef", "N").unwrap();
+
+        let parsed = Exif::try_from(exif.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed, exif);
+        assert_eq!(
+            parsed
+                .get(IfdKind::Gps, 2)
+                .unwrap()
+                .display_value(ByteOrder::Big),
+            "51/1, 30/1, 1234/100"
+        );
+
+        exif.remove_ifd(IfdKind::Gps);
+        let parsed = Exif::try_from(exif.as_bytes().as_slice()).unwrap();
+        assert!(parsed.ifd(IfdKind::Gps).is_none());
+        assert!(parsed.get(IfdKind::Primary, GPS_POINTER).is_none());
+        assert_eq!(parsed.ifds().len(), 1);
+    }
+
+    #[test]
+    fn test_thumbnail_survives_edit() {
+        let mut exif = Exif::new(ByteOrder::Little);
+        exif.thumbnail = vec![0xFF, 0xD8, 0xFF, 0xD9, 0x00];
+        exif.set(
+            IfdKind::Thumbnail,
+            Entry::parse(THUMBNAIL_LENGTH, FieldType::Long, "5", ByteOrder::Little).unwrap(),
+        );
+        exif.set_tag("Software", "a long software name").unwrap();
+
+        let parsed = Exif::try_from(exif.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.thumbnail(), exif.thumbnail());
+        assert_eq!(parsed, exif);
+    }
+
+    #[test]
+    fn test_resolve_tag() {
+        assert_eq!(resolve_tag("make").unwrap(), (IfdKind::Primary, 0x010F));
+        assert_eq!(
+            resolve_tag("ifd1:Make").unwrap(),
+            (IfdKind::Thumbnail, 0x010F)
+        );
+        assert_eq!(resolve_tag("gps:0x2").unwrap(), (IfdKind::Gps, 2));
+        assert_eq!(resolve_tag("LensModel").unwrap(), (IfdKind::Exif, 0xA434));
+        assert!(resolve_tag("gps:Make").is_err());
+        assert!(resolve_tag("Nonsense").is_err());
+    }
+
+    #[test]
+    fn test_invalid_data() {
+        assert!(Exif::try_from(&b"XX*\0\x08\0\0\0"[..]).is_err());
+        let mut looped = LITTLE_ENDIAN.to_vec();
+        looped[34] = 8; // next IFD points back to IFD0
+        assert!(Exif::try_from(looped.as_slice()).is_err());
+        let mut out_of_bounds = LITTLE_ENDIAN.to_vec();
+        out_of_bounds[18] = 200;
+        assert!(Exif::try_from(out_of_bounds.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::fmt;
+use std::str::FromStr;
+
+use regex::bytes::Regex;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+use crate::text::{TextChunk, TextKind};
+
+/// A chunk type pattern where `?` matches one letter and `*` any number of letters.
+/// Letter case is significant, since it carries the chunk type's properties.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypePattern(String);
+
+impl TypePattern {
+    pub fn matches(&self, chunk_type: &ChunkType) -> bool {
+        wildcard_match(self.0.as_bytes(), &chunk_type.bytes())
+    }
+}
+
+impl FromStr for TypePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty()
+            || !s
+                .bytes()
+                .all(|b| b.is_ascii_alphabetic() || b == b'?' || b == b'*')
+        {
+            return Err(format!(
+                "Invalid chunk type pattern '{}': use letters, '?' and '*'",
+                s
+            ));
+        }
+        Ok(TypePattern(s.to_string()))
+    }
+}
+
+impl fmt::Display for TypePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len()).any(|i| wildcard_match(rest, &text[i..])),
+        Some((&p, rest)) => match text.split_first() {
+            Some((&t, text)) => (p == b'?' || p == t) && wildcard_match(rest, text),
+            None => false,
+        },
+    }
+}
+
+/// Which chunks to look for. Every condition that is set must hold.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Chunk types to match; any type when empty.
+    pub types: Vec<TypePattern>,
+    pub critical: Option<bool>,
+    pub public: Option<bool>,
+    pub safe_to_copy: Option<bool>,
+    /// Pattern searched in the chunk data, or in the decoded text of tEXt, zTXt
+    /// and iTXt chunks.
+    pub content: Option<Regex>,
+}
+
+/// A chunk that satisfies a query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match {
+    /// Offset of the chunk's length field from the start of the file.
+    pub offset: usize,
+    pub chunk_type: ChunkType,
+    /// Keyword of a text chunk.
+    pub keyword: Option<String>,
+    /// The first piece of content that matched `Query::content`.
+    pub matched: Option<String>,
+    /// Offset of `matched` from the start of the file, when the content is
+    /// stored uncompressed: raw chunk data, tEXt and uncompressed iTXt.
+    pub matched_offset: Option<usize>,
+}
+
+impl Query {
+    fn matches_type(&self, chunk_type: &ChunkType) -> bool {
+        (self.types.is_empty() || self.types.iter().any(|p| p.matches(chunk_type)))
+            && self.critical.is_none_or(|c| chunk_type.is_critical() == c)
+            && self.public.is_none_or(|p| chunk_type.is_public() == p)
+            && self
+                .safe_to_copy
+                .is_none_or(|s| chunk_type.is_safe_to_copy() == s)
+    }
+
+    /// Lists the chunks of `png` that match, in file order.
+    pub fn search(&self, png: &Png) -> Vec<Match> {
+        let mut matches = Vec::new();
+        let mut offset = png.header().len();
+        for chunk in png.chunks() {
+            if self.matches_type(chunk.chunk_type()) {
+                if let Some(found) = self.search_chunk(chunk, offset) {
+                    matches.push(found);
+                }
+            }
+            offset += 12 + chunk.data().len();
+        }
+        matches
+    }
+
+    fn search_chunk(&self, chunk: &Chunk, offset: usize) -> Option<Match> {
+        let text = TextKind::from_chunk_type(chunk.chunk_type())
+            .and_then(|_| TextChunk::try_from(chunk).ok());
+        let mut found = Match {
+            offset,
+            chunk_type: chunk.chunk_type().clone(),
+            keyword: text.as_ref().map(|t| t.keyword.clone()),
+            matched: None,
+            matched_offset: None,
+        };
+        let Some(content) = &self.content else {
+            return Some(found);
+        };
+
+        match &text {
+            Some(text) => {
+                let m = content.find(text.text.as_bytes())?;
+                found.matched = Some(String::from_utf8_lossy(m.as_bytes()).into_owned());
+                found.matched_offset =
+                    stored_text_offset(chunk, text, m.start()).map(|at| offset + 8 + at);
+            }
+            None => {
+                let m = content.find(chunk.data())?;
+                found.matched = Some(String::from_utf8_lossy(m.as_bytes()).into_owned());
+                found.matched_offset = Some(offset + 8 + m.start());
+            }
+        }
+        Some(found)
+    }
+}
+
+/// Position in the chunk data of byte `index` of the decoded text, if the text
+/// is stored uncompressed. The text is the last field of tEXt and iTXt data; tEXt
+/// stores it as Latin-1, one byte per character.
+fn stored_text_offset(chunk: &Chunk, text: &TextChunk, index: usize) -> Option<usize> {
+    let before = text.text.get(..index)?;
+    let (stored_length, stored_before) = match text.kind {
+        TextKind::Text => (text.text.chars().count(), before.chars().count()),
+        TextKind::International if !text.compressed => (text.text.len(), index),
+        _ => return None,
+    };
+    Some(chunk.data().len() - stored_length + stored_before)
+}
+
+impl fmt::Display for Match {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.offset, self.chunk_type)?;
+        if let Some(keyword) = &self.keyword {
+            write!(f, " {:?}", keyword)?;
+        }
+        if let Some(matched) = &self.matched {
+            write!(f, ": {:?}", matched)?;
+            if let Some(matched_offset) = self.matched_offset {
+                write!(f, " at {}", matched_offset)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_type_pattern() {
+        let rust = ChunkType::from_str("ruSt").unwrap();
+        assert!(TypePattern::from_str("ruSt").unwrap().matches(&rust));
+        assert!(TypePattern::from_str("r?St").unwrap().matches(&rust));
+        assert!(TypePattern::from_str("*t").unwrap().matches(&rust));
+        assert!(TypePattern::from_str("*").unwrap().matches(&rust));
+        assert!(!TypePattern::from_str("RUST").unwrap().matches(&rust));
+        assert!(!TypePattern::from_str("ru?").unwrap().matches(&rust));
+        assert!(TypePattern::from_str("ru.t").is_err());
+    }
+
+    #[test]
+    fn test_search_flags_and_content() {
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("IDAT", b"marker"),
+            chunk("IEND", &[]),
+        ]);
+        png.append_chunk(chunk("prIv", b"xx marker yy"));
+        png.append_chunk(
+            TextChunk::new(TextKind::Compressed, "Comment", "hidden marker")
+                .to_chunk()
+                .unwrap(),
+        );
+
+        let query = Query {
+            critical: Some(false),
+            public: Some(false),
+            ..Query::default()
+        };
+        let found: Vec<String> = query
+            .search(&png)
+            .iter()
+            .map(|m| m.chunk_type.to_string())
+            .collect();
+        assert!(found.contains(&"prIv".to_string()));
+        assert!(!found.contains(&"zTXt".to_string()));
+
+        let query = Query {
+            content: Some(Regex::new("mark(er)?").unwrap()),
+            ..Query::default()
+        };
+        let found = query.search(&png);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].chunk_type.to_string(), "IDAT");
+        let found = &found[1..];
+        let raw = &found[0];
+        assert_eq!(raw.matched.as_deref(), Some("marker"));
+        let bytes = png.as_bytes();
+        let at = raw.matched_offset.unwrap();
+        assert_eq!(&bytes[at..at + 6], b"marker");
+        assert_eq!(&bytes[raw.offset + 4..raw.offset + 8], b"prIv");
+        assert_eq!(found[1].keyword.as_deref(), Some("Comment"));
+        assert_eq!(found[1].matched_offset, None);
+    }
+
+    #[test]
+    fn test_text_match_offset() {
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("IEND", &[]),
+        ]);
+        for kind in [TextKind::Text, TextKind::International] {
+            png.append_chunk(
+                TextChunk::new(kind, "Comment", "café marker")
+                    .to_chunk()
+                    .unwrap(),
+            );
+        }
+
+        let query = Query {
+            content: Some(Regex::new("marker").unwrap()),
+            ..Query::default()
+        };
+        let bytes = png.as_bytes();
+        let found = query.search(&png);
+        assert_eq!(found.len(), 2);
+        for m in found {
+            let at = m.matched_offset.unwrap();
+            assert_eq!(&bytes[at..at + 6], b"marker", "{}", m.chunk_type);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+
+use crate::chunk::Chunk;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    pub fn channels(&self) -> u8 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    pub fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            ColorType::Rgb | ColorType::GrayscaleAlpha | ColorType::Rgba => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(format!("Invalid color type: {}", value)),
+        }
+    }
+}
+
+impl From<ColorType> for u8 {
+    fn from(value: ColorType) -> Self {
+        match value {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+}
+
+impl fmt::Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "indexed",
+            ColorType::GrayscaleAlpha => "grayscale + alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub interlace: bool,
+}
+
+impl Ihdr {
+    pub const LENGTH: usize = 13;
+
+    pub fn bits_per_pixel(&self) -> usize {
+        self.bit_depth as usize * self.color_type.channels() as usize
+    }
+
+    /// Number of bytes in one unfiltered scanline, excluding the filter type byte.
+    pub fn row_bytes(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// Distance in bytes to the corresponding byte of the previous pixel, as used by the filters.
+    pub fn filter_stride(&self) -> usize {
+        self.bits_per_pixel().div_ceil(8)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Ihdr::LENGTH);
+        bytes.extend_from_slice(&self.width.to_be_bytes());
+        bytes.extend_from_slice(&self.height.to_be_bytes());
+        bytes.push(self.bit_depth);
+        bytes.push(self.color_type.into());
+        bytes.push(0); // compression method
+        bytes.push(0); // filter method
+        bytes.push(self.interlace as u8);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Ihdr {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if value.len() != Ihdr::LENGTH {
+            return Err(format!(
+                "IHDR data must be {} bytes, got {}",
+                Ihdr::LENGTH,
+                value.len()
+            ));
+        }
+
+        let width = u32::from_be_bytes([value[0], value[1], value[2], value[3]]);
+        let height = u32::from_be_bytes([value[4], value[5], value[6], value[7]]);
+        if width == 0 || height == 0 {
+            return Err("IHDR width and height must be non-zero".to_string());
+        }
+
+        let bit_depth = value[8];
+        let color_type = ColorType::try_from(value[9])?;
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(format!(
+                "Bit depth {} is not allowed for {} images",
+                bit_depth, color_type
+            ));
+        }
+
+        if value[10] != 0 {
+            return Err(format!("Unknown compression method: {}", value[10]));
+        }
+        if value[11] != 0 {
+            return Err(format!("Unknown filter method: {}", value[11]));
+        }
+        let interlace = match value[12] {
+            0 => false,
+            1 => true,
+            other => return Err(format!("Unknown interlace method: {}", other)),
+        };
+
+        Ok(Ihdr {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            interlace,
+        })
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = String;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        if value.chunk_type().to_string() != "IHDR" {
+            return Err(format!("Expected IHDR chunk, got {}", value.chunk_type()));
+        }
+        Ihdr::try_from(value.data())
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}, {}-bit {}",
+            self.width, self.height, self.bit_depth, self.color_type
+        )?;
+        if self.interlace {
+            write!(f, ", interlaced")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ihdr_bytes(bit_depth: u8, color_type: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&50u32.to_be_bytes());
+        bytes.extend_from_slice(&20u32.to_be_bytes());
+        bytes.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_ihdr() {
+        let ihdr = Ihdr::try_from(ihdr_bytes(8, 6).as_slice()).unwrap();
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 20);
+        assert_eq!(ihdr.color_type, ColorType::Rgba);
+        assert_eq!(ihdr.row_bytes(), 200);
+        assert_eq!(ihdr.filter_stride(), 4);
+    }
+
+    #[test]
+    fn test_sub_byte_row_bytes() {
+        let ihdr = Ihdr::try_from(ihdr_bytes(2, 3).as_slice()).unwrap();
+        assert_eq!(ihdr.row_bytes(), 13);
+        assert_eq!(ihdr.filter_stride(), 1);
+    }
+
+    #[test]
+    fn test_invalid_bit_depth() {
+        assert!(Ihdr::try_from(ihdr_bytes(16, 3).as_slice()).is_err());
+        assert!(Ihdr::try_from(ihdr_bytes(4, 2).as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_invalid_color_type() {
+        assert!(Ihdr::try_from(ihdr_bytes(8, 5).as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_ihdr_round_trip() {
+        let bytes = ihdr_bytes(4, 3);
+        let ihdr = Ihdr::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(ihdr.as_bytes(), bytes);
+    }
+}
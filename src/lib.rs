@@ -1,6 +1,36 @@
+pub mod analyze;
+pub mod ancillary;
+pub mod apng;
 pub mod args;
+pub mod atomic;
+pub mod batch;
+pub mod capacity;
 pub mod chunk;
 pub mod chunk_type;
+pub mod codec;
+pub mod color;
 pub mod commands;
 pub mod crypto;
+pub mod diff;
+pub mod embedder;
+pub mod exif;
+pub mod find;
+pub mod ihdr;
+pub mod listing;
+pub mod manifest;
+pub mod palette;
+pub mod palette_stego;
+pub mod pixels;
 pub mod png;
+pub mod repair;
+#[cfg(feature = "serde")]
+mod serde_impls;
+pub mod steganalysis;
+pub mod text;
+pub mod trailer;
+pub mod validate;
+pub mod xmp;
+pub mod zlib;
+
+pub use embedder::{Embedder, Method};
+pub use listing::Listing;
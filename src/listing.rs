@@ -0,0 +1,203 @@
+use crate::apng::{self, AnimationControl, FrameControl};
+use crate::chunk_type::ChunkType;
+use crate::codec::CodecRegistry;
+use crate::png::Png;
+use crate::xmp;
+
+/// One chunk of a [`Listing`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkEntry {
+    pub chunk_type: ChunkType,
+    pub length: u32,
+    pub crc: u32,
+    /// The chunk data, if it is valid UTF-8.
+    pub text: Option<String>,
+}
+
+/// One frame of an animated PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameEntry {
+    pub control: FrameControl,
+    /// Number of IDAT or fdAT chunks holding the frame.
+    pub chunks: usize,
+    pub is_default_image: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationEntry {
+    pub control: AnimationControl,
+    /// The frames, or why they could not be read.
+    pub frames: Result<Vec<FrameEntry>, String>,
+    /// Problems with the frame sequence numbers.
+    pub problems: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmpEntry {
+    /// Size of the packet in bytes.
+    pub length: usize,
+    /// The supported properties that are set, as name and value.
+    pub properties: Vec<(String, Result<String, String>)>,
+}
+
+/// Everything `print` shows about a file, decoded. A chunk that cannot be
+/// decoded does not stop the listing; its entry holds the error instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub chunks: Vec<ChunkEntry>,
+    /// Number of bytes after IEND.
+    pub trailer_length: usize,
+    pub animation: Option<Result<AnimationEntry, String>>,
+    /// Every chunk the registry decodes, as a name and a value, such as
+    /// `("Gamma", Ok("0.45455"))`, in file order.
+    pub properties: Vec<(String, Result<String, String>)>,
+    pub xmp: Option<Result<XmpEntry, String>>,
+}
+
+impl Listing {
+    /// Lists `png`, decoding metadata chunks with the codecs in `registry`.
+    pub fn for_png(png: &Png, registry: &CodecRegistry) -> Self {
+        let chunks = png
+            .chunks()
+            .iter()
+            .map(|chunk| ChunkEntry {
+                chunk_type: chunk.chunk_type().clone(),
+                length: chunk.length(),
+                crc: chunk.crc(),
+                text: chunk.data_as_string().ok(),
+            })
+            .collect();
+
+        let animation = png.animation_control().transpose().map(|control| {
+            control.map(|control| AnimationEntry {
+                control,
+                frames: png
+                    .frames()
+                    .map(|frame| {
+                        frame.map(|frame| FrameEntry {
+                            control: frame.control,
+                            chunks: frame.data.len(),
+                            is_default_image: frame.is_default_image,
+                        })
+                    })
+                    .collect(),
+                problems: apng::sequence_problems(png),
+            })
+        });
+
+        let mut properties = Vec::new();
+        for chunk in png.chunks() {
+            if let (Some(name), Some(value)) = (
+                registry.name(chunk.chunk_type()),
+                registry.display_in(chunk, png),
+            ) {
+                properties.push((name.to_string(), value));
+            }
+        }
+
+        let xmp = png.xmp().transpose().map(|packet| {
+            packet.map(|packet| XmpEntry {
+                length: packet.packet().len(),
+                properties: xmp::property_names()
+                    .filter_map(|name| {
+                        packet
+                            .get(name)
+                            .transpose()
+                            .map(|value| (name.to_string(), value))
+                    })
+                    .collect(),
+            })
+        });
+
+        Listing {
+            chunks,
+            trailer_length: png.trailer().len(),
+            animation,
+            properties,
+            xmp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::text::{TextChunk, TextKind};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_listing() {
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("gAMA", &45455u32.to_be_bytes()),
+            chunk("IDAT", &[0xff]),
+            chunk("IEND", &[]),
+        ]);
+        png.append_chunk(
+            TextChunk::new(TextKind::Text, "Title", "Sunset")
+                .to_chunk()
+                .unwrap(),
+        );
+        png.trailer = b"extra".to_vec();
+
+        let listing = Listing::for_png(&png, &CodecRegistry::standard());
+        let types: Vec<String> = listing
+            .chunks
+            .iter()
+            .map(|c| c.chunk_type.to_string())
+            .collect();
+        assert_eq!(types, ["IHDR", "gAMA", "IDAT", "tEXt", "IEND"]);
+        assert_eq!(listing.chunks[2].text, None);
+        assert_eq!(listing.chunks[3].length, 12);
+        assert_eq!(listing.trailer_length, 5);
+        assert_eq!(listing.animation, None);
+        assert_eq!(listing.properties.len(), 2);
+        assert_eq!(listing.xmp, None);
+    }
+
+    #[test]
+    fn test_palette_and_animation_are_properties() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]),
+            chunk("acTL", &[0, 0, 0, 1, 0, 0, 0, 0]),
+            chunk("PLTE", &[255, 0, 0, 0, 0, 255]),
+            chunk("tRNS", &[0]),
+            chunk(
+                "fcTL",
+                &[
+                    0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 10, 0, 0,
+                ],
+            ),
+            chunk("IDAT", &[0xff]),
+            chunk("IEND", &[]),
+        ]);
+
+        let listing = Listing::for_png(&png, &CodecRegistry::standard());
+        let names: Vec<&str> = listing.properties.iter().map(|p| p.0.as_str()).collect();
+        assert_eq!(names, ["Animation", "Palette", "Transparency", "Frame"]);
+        assert!(listing.properties.iter().all(|p| p.1.is_ok()));
+        assert_eq!(listing.animation.unwrap().unwrap().frames.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_chunk_is_recorded() {
+        let png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("gAMA", &[1, 2]),
+            chunk("tIME", &[7, 232, 1, 2, 3, 4, 5]),
+            chunk("IDAT", &[0xff]),
+            chunk("IEND", &[]),
+        ]);
+
+        let listing = Listing::for_png(&png, &CodecRegistry::standard());
+        assert_eq!(listing.chunks.len(), 5);
+        assert_eq!(listing.properties.len(), 2);
+        assert!(listing.properties[0].1.is_err());
+        assert!(listing.properties[1].1.is_ok());
+    }
+}
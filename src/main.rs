@@ -1,38 +1,332 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser;
 use pngme::{
-    args::{Args, Commands, Input},
+    args::{Args, Commands, ExifAction, IccAction, Input, Method, XmpAction},
+    codec::CodecRegistry,
+    find::{Query, TypePattern},
     *,
 };
+use regex::bytes::Regex;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// Exits with status 1 when a command ran but reported a failure.
+fn exit_on_failure(result: Result<bool>) -> Result<()> {
+    if !result? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Turns a pair of mutually exclusive filter flags into a required value.
+fn either(yes: bool, no: bool) -> Option<bool> {
+    match (yes, no) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+/// Takes the last `count` positionals of `encode` or `decode`, such as the chunk
+/// type and the message, off the list of inputs. clap only allows a variable
+/// number of values in the last two positionals, so the split happens here.
+fn split_trailing(input: &mut Vec<String>, count: usize, what: &str) -> Result<Vec<String>> {
+    if input.len() <= count {
+        anyhow::bail!("Expected one or more input files followed by {}", what);
+    }
+    Ok(input.split_off(input.len() - count))
+}
+
+fn local_path(input: &Input) -> Result<PathBuf> {
+    input
+        .path()
+        .map(PathBuf::from)
+        .context("This command only works on local files")
+}
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let options = &commands::WriteOptions {
+        touch: args.touch,
+        overwrite: args.overwrite,
+        backup: args.backup,
+    };
 
     match args.command {
         Commands::Encode {
-            input,
+            mut input,
             url,
-            chunk_type,
-            message,
             output_file,
             password,
+            method,
+            force,
+            manifest,
+            results,
+            batch,
         } => {
-            let input = Input::from_args(input, url);
-            commands::encode(input, chunk_type, message, output_file, password)
+            if let Some(manifest) = manifest {
+                if input.len() > 1 {
+                    anyhow::bail!("Only a chunk type can be given with --manifest");
+                }
+                return exit_on_failure(commands::encode_manifest(
+                    &manifest,
+                    results.as_deref(),
+                    method,
+                    input.pop(),
+                    &batch,
+                    force,
+                    options,
+                ));
+            }
+            let mut trailing = match method {
+                Method::Chunk => split_trailing(&mut input, 2, "a chunk type and a message")?,
+                _ => split_trailing(&mut input, 1, "a message")?,
+            };
+            let message = trailing.pop().unwrap_or_default();
+            let chunk_type = trailing.pop();
+            let embedder = commands::embedder(method, chunk_type, password)?.force(force);
+            exit_on_failure(commands::for_each_input(
+                input,
+                url,
+                &batch,
+                output_file.as_deref(),
+                |input, out| {
+                    commands::encode(
+                        input,
+                        &embedder,
+                        message.as_bytes(),
+                        output_file.clone(),
+                        options,
+                        out,
+                    )?;
+                    Ok(true)
+                },
+            ))
         }
         Commands::Decode {
-            input,
+            mut input,
             url,
-            chunk_type,
             password,
+            method,
+            lenient,
+            batch,
         } => {
-            let input = Input::from_args(input, url);
-            commands::decode(input, chunk_type, password)
+            let chunk_type = match method {
+                Method::Chunk => split_trailing(&mut input, 1, "a chunk type")?.pop(),
+                _ => None,
+            };
+            exit_on_failure(commands::for_each_input(
+                input,
+                url,
+                &batch,
+                None,
+                |input, out| {
+                    commands::decode(
+                        input,
+                        method,
+                        chunk_type.clone(),
+                        password.clone(),
+                        lenient,
+                        out,
+                    )
+                },
+            ))
         }
-        Commands::Remove { input, chunk_type } => commands::remove(input, chunk_type),
-        Commands::Print { input, url } => {
+        Commands::Remove {
+            input,
+            chunk_type,
+            force,
+            batch,
+        } => exit_on_failure(commands::for_each_input(
+            input,
+            false,
+            &batch,
+            None,
+            |input, out| {
+                let path = local_path(&input)?;
+                commands::remove(path, chunk_type.clone(), force, options, out)?;
+                Ok(true)
+            },
+        )),
+        Commands::Print {
+            input,
+            url,
+            lenient,
+            json,
+            batch,
+        } => {
+            let registry = CodecRegistry::standard();
+            exit_on_failure(commands::for_each_input(
+                input,
+                url,
+                &batch,
+                None,
+                |input, out| {
+                    commands::print(input, &registry, lenient, json, out)?;
+                    Ok(true)
+                },
+            ))
+        }
+        Commands::Strip {
+            input,
+            keep,
+            keep_trailer,
+            output_file,
+            dry_run,
+            batch,
+        } => exit_on_failure(commands::for_each_input(
+            input,
+            false,
+            &batch,
+            output_file.as_deref(),
+            |input, out| {
+                let path = local_path(&input)?;
+                commands::strip(
+                    path,
+                    keep.clone(),
+                    keep_trailer,
+                    output_file.clone(),
+                    dry_run,
+                    options,
+                    out,
+                )?;
+                Ok(true)
+            },
+        )),
+        Commands::Transplant {
+            source,
+            target,
+            output_file,
+            include_unsafe,
+        } => commands::transplant(source, target, output_file, include_unsafe, options),
+        // Like cmp and diff, 1 means the files differ and 2 means they could not be compared.
+        Commands::Diff { left, right } => match commands::diff(left, right) {
+            Ok(true) => Ok(()),
+            Ok(false) => std::process::exit(1),
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                std::process::exit(2);
+            }
+        },
+        Commands::Exif { action } => match action {
+            ExifAction::Get { input, url, tag } => {
+                let input = Input::from_args(input, url);
+                commands::exif_get(input, tag)
+            }
+            ExifAction::Set {
+                input,
+                tag,
+                value,
+                output_file,
+            } => commands::exif_set(input, tag, value, output_file, options),
+            ExifAction::Remove {
+                input,
+                tags,
+                output_file,
+            } => commands::exif_remove(input, tags, output_file, options),
+        },
+        Commands::Xmp { action } => match action {
+            XmpAction::Extract {
+                input,
+                url,
+                output_file,
+            } => {
+                let input = Input::from_args(input, url);
+                commands::xmp_extract(input, output_file, options)
+            }
+            XmpAction::Replace {
+                input,
+                packet,
+                output_file,
+            } => commands::xmp_replace(input, packet, output_file, options),
+            XmpAction::Get {
+                input,
+                url,
+                property,
+            } => {
+                let input = Input::from_args(input, url);
+                commands::xmp_get(input, property)
+            }
+            XmpAction::Set {
+                input,
+                property,
+                value,
+                output_file,
+            } => commands::xmp_set(input, property, value, output_file, options),
+        },
+        Commands::Icc { action } => match action {
+            IccAction::Extract { input, url, output } => {
+                let input = Input::from_args(input, url);
+                commands::icc_extract(input, output, options)
+            }
+            IccAction::Embed {
+                input,
+                profile,
+                name,
+                output_file,
+            } => commands::icc_embed(input, profile, name, output_file, options),
+        },
+        Commands::Set {
+            input,
+            dpi,
+            time,
+            output_file,
+        } => commands::set(input, dpi, time, output_file, options),
+        Commands::Validate { input, url, batch } => exit_on_failure(commands::for_each_input(
+            input,
+            url,
+            &batch,
+            None,
+            |input, out| commands::validate(input, out),
+        )),
+        Commands::Repair { input, output_file } => commands::repair(input, output_file, options),
+        Commands::Capacity { input, url, json } => {
             let input = Input::from_args(input, url);
-            commands::print(input)
+            commands::capacity(input, json)
+        }
+        Commands::Analyze {
+            input,
+            url,
+            json,
+            pixels,
+            batch,
+        } => exit_on_failure(commands::for_each_input(
+            input,
+            url,
+            &batch,
+            None,
+            |input, out| {
+                commands::analyze(input, json, pixels, out)?;
+                Ok(true)
+            },
+        )),
+        Commands::Find {
+            input,
+            types,
+            text,
+            critical,
+            ancillary,
+            public,
+            private,
+            safe_to_copy,
+            unsafe_to_copy,
+            batch,
+        } => {
+            let query = Query {
+                types: types
+                    .iter()
+                    .map(|t| TypePattern::from_str(t))
+                    .collect::<Result<_, _>>()
+                    .map_err(anyhow::Error::msg)?,
+                critical: either(critical, ancillary),
+                public: either(public, private),
+                safe_to_copy: either(safe_to_copy, unsafe_to_copy),
+                content: text
+                    .map(|pattern| Regex::new(&pattern))
+                    .transpose()
+                    .context("Invalid regular expression")?,
+            };
+            exit_on_failure(commands::find(input, &query, &batch))
         }
     }
 }
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Columns of an embedding plan. `input` and one of `message` or `payload` are
+/// required; other columns are ignored.
+const COLUMNS: [&str; 8] = [
+    "input",
+    "output",
+    "chunk_type",
+    "message",
+    "payload",
+    "method",
+    "recipient",
+    "password",
+];
+
+/// Columns of the result manifest.
+const RESULT_COLUMNS: [&str; 7] = [
+    "row",
+    "input",
+    "output",
+    "recipient",
+    "status",
+    "sha256",
+    "error",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    /// Picks the format from the file extension: `.jsonl`, `.ndjson` and `.json`
+    /// are JSON lines, anything else is CSV.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("jsonl" | "ndjson" | "json") => Format::JsonLines,
+            _ => Format::Csv,
+        }
+    }
+}
+
+/// What to embed: literal text, or the contents of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Payload(PathBuf),
+}
+
+impl Message {
+    /// The bytes to embed. Payload files may hold any data, not only text.
+    pub fn load(&self) -> Result<Vec<u8>, String> {
+        match self {
+            Message::Text(text) => Ok(text.clone().into_bytes()),
+            Message::Payload(path) => {
+                fs::read(path).map_err(|e| format!("Failed to read payload file {:?}: {}", path, e))
+            }
+        }
+    }
+}
+
+/// One embedding job from a plan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestRow {
+    /// 1-based position of the row among the data rows of the plan.
+    pub row: usize,
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub chunk_type: Option<String>,
+    pub message: Message,
+    pub method: Option<String>,
+    pub recipient: Option<String>,
+    /// Where to find the password: `env:NAME` or `file:PATH`.
+    pub password: Option<String>,
+}
+
+impl ManifestRow {
+    fn from_fields(row: usize, get: impl Fn(&str) -> Option<String>) -> Result<Self, String> {
+        let input = get("input").ok_or_else(|| format!("Row {}: missing input", row))?;
+        let message = match (get("message"), get("payload")) {
+            (Some(text), None) => Message::Text(text),
+            (None, Some(path)) => Message::Payload(PathBuf::from(path)),
+            (Some(_), Some(_)) => {
+                return Err(format!(
+                    "Row {}: give either message or payload, not both",
+                    row
+                ))
+            }
+            (None, None) => return Err(format!("Row {}: missing message or payload", row)),
+        };
+        Ok(ManifestRow {
+            row,
+            input: PathBuf::from(input),
+            output: get("output").map(PathBuf::from),
+            chunk_type: get("chunk_type"),
+            message,
+            method: get("method"),
+            recipient: get("recipient"),
+            password: get("password"),
+        })
+    }
+
+    /// Where the row writes its result: `output`, or the input itself.
+    pub fn output_path(&self) -> PathBuf {
+        self.output
+            .clone()
+            .unwrap_or_else(|| self.input.with_extension("png"))
+    }
+
+    /// Looks up the password this row refers to.
+    pub fn resolve_password(&self) -> Result<Option<String>, String> {
+        let Some(reference) = &self.password else {
+            return Ok(None);
+        };
+        if let Some(name) = reference.strip_prefix("env:") {
+            std::env::var(name)
+                .map(Some)
+                .map_err(|_| format!("Environment variable {} is not set", name))
+        } else if let Some(path) = reference.strip_prefix("file:") {
+            fs::read_to_string(path)
+                .map(|password| Some(password.trim_end_matches(['\r', '\n']).to_string()))
+                .map_err(|e| format!("Failed to read password file {:?}: {}", path, e))
+        } else {
+            Err("Password must be a reference such as env:NAME or file:PATH".to_string())
+        }
+    }
+}
+
+/// Parses an embedding plan. Empty cells count as missing. Rows that would
+/// write the same output file are rejected, since they run in parallel and
+/// only one result would survive.
+pub fn parse(text: &str, format: Format) -> Result<Vec<ManifestRow>, String> {
+    let rows = match format {
+        Format::Csv => parse_csv_plan(text)?,
+        Format::JsonLines => parse_json_lines_plan(text)?,
+    };
+    check_outputs(&rows)?;
+    Ok(rows)
+}
+
+fn check_outputs(rows: &[ManifestRow]) -> Result<(), String> {
+    let mut seen: HashMap<PathBuf, usize> = HashMap::new();
+    for row in rows {
+        let output = row.output_path();
+        let key = std::path::absolute(&output).unwrap_or_else(|_| output.clone());
+        if let Some(first) = seen.insert(key, row.row) {
+            return Err(format!(
+                "Rows {} and {} both write to {:?}",
+                first, row.row, output
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn parse_csv_plan(text: &str) -> Result<Vec<ManifestRow>, String> {
+    let mut records = parse_csv(text)?.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| "Manifest is empty".to_string())?;
+    let header: Vec<String> = header.iter().map(|h| h.trim().to_lowercase()).collect();
+    if !header.iter().any(|h| h == "input") {
+        return Err("Manifest header has no 'input' column".to_string());
+    }
+
+    records
+        .enumerate()
+        .map(|(i, record)| {
+            ManifestRow::from_fields(i + 1, |name| {
+                let column = header.iter().position(|h| h == name)?;
+                record.get(column).filter(|v| !v.is_empty()).cloned()
+            })
+        })
+        .collect()
+}
+
+fn parse_json_lines_plan(text: &str) -> Result<Vec<ManifestRow>, String> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .enumerate()
+        .map(|(i, line)| {
+            let row = i + 1;
+            let object: Map<String, Value> = serde_json::from_str(line)
+                .map_err(|e| format!("Row {}: invalid JSON object: {}", row, e))?;
+            for name in COLUMNS {
+                match object.get(name) {
+                    None | Some(Value::Null | Value::String(_)) => {}
+                    Some(_) => return Err(format!("Row {}: '{}' must be a string", row, name)),
+                }
+            }
+            ManifestRow::from_fields(row, |name| {
+                object
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .filter(|v| !v.is_empty())
+                    .map(String::from)
+            })
+        })
+        .collect()
+}
+
+/// Splits CSV text into records, following RFC 4180 quoting. Blank lines are skipped.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|f| !f.is_empty()) {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Manifest ends inside a quoted field".to_string());
+    }
+    record.push(field);
+    if record.iter().any(|f| !f.is_empty()) {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Outcome of one row: the SHA-256 of the written file, or why it failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowResult {
+    pub row: usize,
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub recipient: Option<String>,
+    pub outcome: Result<String, String>,
+}
+
+impl RowResult {
+    fn fields(&self) -> [(&'static str, String); 7] {
+        let (status, hash, error) = match &self.outcome {
+            Ok(hash) => ("ok", hash.clone(), String::new()),
+            Err(e) => ("failed", String::new(), e.clone()),
+        };
+        [
+            ("row", self.row.to_string()),
+            ("input", self.input.display().to_string()),
+            ("output", self.output.display().to_string()),
+            ("recipient", self.recipient.clone().unwrap_or_default()),
+            ("status", status.to_string()),
+            ("sha256", hash),
+            ("error", error),
+        ]
+    }
+}
+
+/// Formats results as a manifest in the same format as the plan.
+pub fn format_results(results: &[RowResult], format: Format) -> String {
+    let mut text = String::new();
+    match format {
+        Format::Csv => {
+            text.push_str(&RESULT_COLUMNS.join(","));
+            text.push('\n');
+            for result in results {
+                let fields: Vec<String> =
+                    result.fields().iter().map(|(_, v)| csv_field(v)).collect();
+                text.push_str(&fields.join(","));
+                text.push('\n');
+            }
+        }
+        Format::JsonLines => {
+            for result in results {
+                let object: Map<String, Value> = result
+                    .fields()
+                    .into_iter()
+                    .map(|(name, value)| match (name, value.is_empty()) {
+                        ("row", _) => (name.to_string(), Value::from(result.row)),
+                        (_, true) => (name.to_string(), Value::Null),
+                        (_, false) => (name.to_string(), Value::String(value)),
+                    })
+                    .collect();
+                text.push_str(&Value::Object(object).to_string());
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+/// Lowercase hex SHA-256 digest of `data`.
+pub fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::png::Png;
+    use crate::Embedder;
+
+    #[test]
+    fn test_parse_csv_plan() {
+        let text = "Input,output,chunk_type,message,recipient,notes\r\n\
+                    a.png,a-out.png,ruSt,\"Hello, \"\"Alice\"\"\",alice,x\r\n\
+                    \r\n\
+                    b.png,,ruSt,\"two\nlines\",,\n";
+        let rows = parse(text, Format::Csv).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].input, PathBuf::from("a.png"));
+        assert_eq!(rows[0].output, Some(PathBuf::from("a-out.png")));
+        assert_eq!(
+            rows[0].message,
+            Message::Text("Hello, \"Alice\"".to_string())
+        );
+        assert_eq!(rows[0].recipient.as_deref(), Some("alice"));
+        assert_eq!(rows[1].row, 2);
+        assert_eq!(rows[1].output, None);
+        assert_eq!(rows[1].message, Message::Text("two\nlines".to_string()));
+
+        assert!(parse("output\nx.png\n", Format::Csv).is_err());
+        assert!(parse("input,message\na.png\n", Format::Csv).is_err());
+        assert!(parse("input,message\na.png,\"open\n", Format::Csv).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_lines_plan() {
+        let text = r#"{"input": "a.png", "payload": "msg.txt", "password": "env:KEY"}
+
+{"input": "b.png", "message": "hi", "output": null}"#;
+        let rows = parse(text, Format::JsonLines).unwrap();
+        assert_eq!(rows[0].message, Message::Payload(PathBuf::from("msg.txt")));
+        assert_eq!(rows[0].password.as_deref(), Some("env:KEY"));
+        assert_eq!(rows[1].row, 2);
+
+        assert!(parse(r#"{"input": "a.png", "message": 5}"#, Format::JsonLines).is_err());
+        assert!(parse(
+            r#"{"input": "a.png", "message": "a", "payload": "b"}"#,
+            Format::JsonLines
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_duplicate_outputs() {
+        let text = "input,output,message\na.png,out.png,x\nb.png,,y\nc.png,out.png,z\n";
+        let error = parse(text, Format::Csv).unwrap_err();
+        assert_eq!(error, "Rows 1 and 3 both write to \"out.png\"");
+
+        let text = "input,output,message\na.png,,x\nb.png,a.png,y\n";
+        assert!(parse(text, Format::Csv).is_err());
+    }
+
+    #[test]
+    fn test_binary_payload() {
+        let path = std::env::temp_dir().join(format!("pngme-payload-{}", std::process::id()));
+        let payload = [0u8, 0xff, 0xfe, b'\n', 0x80];
+        fs::write(&path, payload).unwrap();
+        let loaded = Message::Payload(path.clone()).load();
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded, payload);
+
+        // What a row embeds from a binary file reads back byte for byte.
+        let chunk = |name: &str, data: &[u8]| Chunk::new(name.parse().unwrap(), data.to_vec());
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]);
+        let embedder = Embedder::chunk("ruSt".parse().unwrap()).password("secret");
+        embedder.embed_payload(&mut png, &loaded).unwrap();
+        let stego = Png::try_from(png.as_bytes().as_slice()).unwrap();
+        assert_eq!(embedder.extract_payload(&stego).unwrap().unwrap(), payload);
+    }
+
+    #[test]
+    fn test_password_reference() {
+        let mut row =
+            parse("input,message,password\na.png,hi,plain\n", Format::Csv).unwrap()[0].clone();
+        assert!(row.resolve_password().is_err());
+        row.password = Some("env:PNGME_MANIFEST_TEST_UNSET".to_string());
+        assert!(row.resolve_password().is_err());
+        row.password = None;
+        assert_eq!(row.resolve_password().unwrap(), None);
+    }
+
+    #[test]
+    fn test_format_results() {
+        let results = vec![
+            RowResult {
+                row: 1,
+                input: PathBuf::from("a.png"),
+                output: PathBuf::from("a.png"),
+                recipient: Some("alice".to_string()),
+                outcome: Ok(sha256_hex(b"abc")),
+            },
+            RowResult {
+                row: 2,
+                input: PathBuf::from("b.png"),
+                output: PathBuf::from("b.png"),
+                recipient: None,
+                outcome: Err("No such file, really".to_string()),
+            },
+        ];
+        let csv = format_results(&results, Format::Csv);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "row,input,output,recipient,status,sha256,error");
+        assert_eq!(
+            lines[1],
+            "1,a.png,a.png,alice,ok,ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad,"
+        );
+        assert_eq!(lines[2], "2,b.png,b.png,,failed,,\"No such file, really\"");
+
+        let json = format_results(&results, Format::JsonLines);
+        let second: Value = serde_json::from_str(json.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(second["row"], 2);
+        assert_eq!(second["status"], "failed");
+        assert!(second["sha256"].is_null());
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt;
+
+use crate::ihdr::{ColorType, Ihdr};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    pub const MAX_ENTRIES: usize = 256;
+
+    pub fn new(entries: Vec<[u8; 3]>) -> Result<Self, String> {
+        if entries.is_empty() || entries.len() > Palette::MAX_ENTRIES {
+            return Err(format!(
+                "Palette must have between 1 and {} entries, got {}",
+                Palette::MAX_ENTRIES,
+                entries.len()
+            ));
+        }
+        Ok(Palette { entries })
+    }
+
+    pub fn entries(&self) -> &[[u8; 3]] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: u8) -> Option<[u8; 3]> {
+        self.entries.get(index as usize).copied()
+    }
+
+    /// Checks the palette against the image header: PLTE is forbidden for grayscale
+    /// images and, for indexed images, may not hold more entries than the bit depth can address.
+    pub fn validate(&self, ihdr: &Ihdr) -> Result<(), String> {
+        match ihdr.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                Err("PLTE chunk must not appear in grayscale images".to_string())
+            }
+            ColorType::Indexed if self.len() > 1 << ihdr.bit_depth => Err(format!(
+                "Palette has {} entries but bit depth {} allows at most {}",
+                self.len(),
+                ihdr.bit_depth,
+                1 << ihdr.bit_depth
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Expands one palette index per byte into RGBA pixels, applying tRNS alpha when given.
+    pub fn to_rgba(
+        &self,
+        indices: &[u8],
+        transparency: Option<&Transparency>,
+    ) -> Result<Vec<u8>, String> {
+        let alphas = match transparency {
+            Some(Transparency::Indexed(alphas)) => alphas.as_slice(),
+            Some(_) => return Err("tRNS chunk does not hold palette alpha values".to_string()),
+            None => &[],
+        };
+
+        let mut rgba = Vec::with_capacity(indices.len() * 4);
+        for &index in indices {
+            let [r, g, b] = self
+                .get(index)
+                .ok_or_else(|| format!("Palette index {} is out of range", index))?;
+            let a = alphas.get(index as usize).copied().unwrap_or(255);
+            rgba.extend_from_slice(&[r, g, b, a]);
+        }
+        Ok(rgba)
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.entries.iter().flatten().copied().collect()
+    }
+}
+
+impl TryFrom<&[u8]> for Palette {
+    type Error = String;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        if !value.len().is_multiple_of(3) {
+            return Err(format!(
+                "PLTE length must be divisible by 3, got {}",
+                value.len()
+            ));
+        }
+        let entries = value.chunks(3).map(|c| [c[0], c[1], c[2]]).collect();
+        Palette::new(entries)
+    }
+}
+
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Palette with {} entries:", self.len())?;
+        for (i, [r, g, b]) in self.entries.iter().enumerate() {
+            writeln!(f, "  [{:3}] #{:02x}{:02x}{:02x}", i, r, g, b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Contents of a tRNS chunk, whose layout depends on the image color type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transparency {
+    /// One alpha value per palette entry; missing trailing entries are opaque.
+    Indexed(Vec<u8>),
+    /// Single gray sample value treated as fully transparent.
+    Grayscale(u16),
+    /// Single RGB sample value treated as fully transparent.
+    Rgb(u16, u16, u16),
+}
+
+impl Transparency {
+    pub fn parse(data: &[u8], ihdr: &Ihdr, palette: Option<&Palette>) -> Result<Self, String> {
+        match ihdr.color_type {
+            ColorType::Indexed => {
+                let palette =
+                    palette.ok_or_else(|| "tRNS chunk requires a PLTE chunk".to_string())?;
+                if data.len() > palette.len() {
+                    return Err(format!(
+                        "tRNS has {} entries but palette only has {}",
+                        data.len(),
+                        palette.len()
+                    ));
+                }
+                Ok(Transparency::Indexed(data.to_vec()))
+            }
+            ColorType::Grayscale => {
+                if data.len() != 2 {
+                    return Err("tRNS for grayscale images must be 2 bytes".to_string());
+                }
+                let gray = u16::from_be_bytes([data[0], data[1]]);
+                Transparency::check_sample(gray, ihdr.bit_depth)?;
+                Ok(Transparency::Grayscale(gray))
+            }
+            ColorType::Rgb => {
+                if data.len() != 6 {
+                    return Err("tRNS for RGB images must be 6 bytes".to_string());
+                }
+                let r = u16::from_be_bytes([data[0], data[1]]);
+                let g = u16::from_be_bytes([data[2], data[3]]);
+                let b = u16::from_be_bytes([data[4], data[5]]);
+                for sample in [r, g, b] {
+                    Transparency::check_sample(sample, ihdr.bit_depth)?;
+                }
+                Ok(Transparency::Rgb(r, g, b))
+            }
+            ColorType::GrayscaleAlpha | ColorType::Rgba => {
+                Err("tRNS chunk must not appear in images with an alpha channel".to_string())
+            }
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        match self {
+            Transparency::Indexed(alphas) => alphas.clone(),
+            Transparency::Grayscale(gray) => gray.to_be_bytes().to_vec(),
+            Transparency::Rgb(r, g, b) => [r, g, b].iter().flat_map(|s| s.to_be_bytes()).collect(),
+        }
+    }
+
+    fn check_sample(sample: u16, bit_depth: u8) -> Result<(), String> {
+        if (sample as u32) >> bit_depth != 0 {
+            return Err(format!(
+                "tRNS sample {} exceeds the range of bit depth {}",
+                sample, bit_depth
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Transparency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Transparency::Indexed(alphas) => {
+                write!(f, "Transparency for {} palette entries", alphas.len())
+            }
+            Transparency::Grayscale(gray) => write!(f, "Transparent gray: {}", gray),
+            Transparency::Rgb(r, g, b) => write!(f, "Transparent RGB: ({}, {}, {})", r, g, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexed_ihdr(bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width: 4,
+            height: 1,
+            bit_depth,
+            color_type: ColorType::Indexed,
+            interlace: false,
+        }
+    }
+
+    fn testing_palette() -> Palette {
+        Palette::try_from([255, 0, 0, 0, 255, 0, 0, 0, 255].as_slice()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_palette() {
+        let palette = testing_palette();
+        assert_eq!(palette.len(), 3);
+        assert_eq!(palette.get(1), Some([0, 255, 0]));
+        assert_eq!(palette.get(3), None);
+    }
+
+    #[test]
+    fn test_invalid_palette_length() {
+        assert!(Palette::try_from([1, 2, 3, 4].as_slice()).is_err());
+        assert!(Palette::try_from([].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_palette_exceeds_bit_depth() {
+        let palette = testing_palette();
+        assert!(palette.validate(&indexed_ihdr(2)).is_ok());
+        assert!(palette.validate(&indexed_ihdr(1)).is_err());
+    }
+
+    #[test]
+    fn test_palette_forbidden_in_grayscale() {
+        let mut ihdr = indexed_ihdr(8);
+        ihdr.color_type = ColorType::Grayscale;
+        assert!(testing_palette().validate(&ihdr).is_err());
+    }
+
+    #[test]
+    fn test_indexed_transparency() {
+        let palette = testing_palette();
+        let ihdr = indexed_ihdr(8);
+        let trns = Transparency::parse(&[0, 128], &ihdr, Some(&palette)).unwrap();
+        assert_eq!(trns, Transparency::Indexed(vec![0, 128]));
+
+        let too_long = Transparency::parse(&[0, 0, 0, 0], &ihdr, Some(&palette));
+        assert!(too_long.is_err());
+    }
+
+    #[test]
+    fn test_grayscale_transparency_range() {
+        let mut ihdr = indexed_ihdr(4);
+        ihdr.color_type = ColorType::Grayscale;
+        assert!(Transparency::parse(&[0, 15], &ihdr, None).is_ok());
+        assert!(Transparency::parse(&[0, 16], &ihdr, None).is_err());
+    }
+
+    #[test]
+    fn test_to_rgba() {
+        let palette = testing_palette();
+        let trns = Transparency::Indexed(vec![0]);
+        let rgba = palette.to_rgba(&[0, 2], Some(&trns)).unwrap();
+        assert_eq!(rgba, vec![255, 0, 0, 0, 0, 0, 255, 255]);
+
+        assert!(palette.to_rgba(&[3], None).is_err());
+    }
+}
@@ -0,0 +1,385 @@
+use std::str::FromStr;
+
+use crate::apng::{FrameControl, FrameData};
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::{ColorType, Ihdr};
+use crate::palette::{Palette, Transparency};
+use crate::pixels;
+use crate::png::Png;
+
+/// Number of whole payload bytes that fit in the ordering of `entries` palette entries.
+///
+/// The ordering carries `floor(log2(n!))` bits; one byte is reserved for the payload length.
+pub fn capacity(entries: usize) -> usize {
+    (permutation_bits(entries) / 8).saturating_sub(1)
+}
+
+/// Hides `payload` in the order of the PLTE entries and remaps IDAT, the fdAT frames of
+/// an animated image, tRNS, bKGD and hIST so that every pixel keeps its color.
+pub fn embed(png: &mut Png, payload: &[u8]) -> Result<(), String> {
+    let ihdr = png.ihdr()?;
+    if ihdr.color_type != ColorType::Indexed {
+        return Err(format!(
+            "Palette method requires an indexed image, got {}",
+            ihdr.color_type
+        ));
+    }
+    let palette = png
+        .palette()?
+        .ok_or_else(|| "Indexed image is missing a PLTE chunk".to_string())?;
+    let alphas = palette_alphas(png, &palette)?;
+
+    // A zero length byte leaves the palette in its canonical order, which is
+    // what an image without a payload looks like, so it could not be read back.
+    if payload.is_empty() {
+        return Err("Cannot hide an empty payload in palette order".to_string());
+    }
+
+    let n = palette.len();
+    let capacity = capacity(n);
+    if capacity == 0 || payload.len() > capacity {
+        return Err(format!(
+            "Payload of {} bytes exceeds palette capacity of {} bytes",
+            payload.len(),
+            capacity
+        ));
+    }
+
+    let canonical = canonical_order(&palette, &alphas)?;
+
+    let mut number = vec![0u8; permutation_bits(n) / 8];
+    number[0] = payload.len() as u8;
+    number[1..=payload.len()].copy_from_slice(payload);
+
+    // Factorial number system: successive remainders select from the remaining entries.
+    let mut remaining = canonical;
+    let mut order = Vec::with_capacity(n);
+    for radix in (1..=n as u32).rev() {
+        let digit = div_rem(&mut number, radix) as usize;
+        order.push(remaining.remove(digit));
+    }
+
+    // order[new] = old index; invert it to remap pixel data.
+    let mut old_to_new = vec![0u8; n];
+    for (new, &old) in order.iter().enumerate() {
+        old_to_new[old] = new as u8;
+    }
+
+    let idat = remap_indices(&ihdr, &png.image_data(), &old_to_new)?;
+    png.replace_image_data(idat);
+    remap_frames(png, &ihdr, &old_to_new)?;
+
+    let entries = order.iter().map(|&old| palette.entries()[old]).collect();
+    png.set_chunk(chunk("PLTE", Palette::new(entries)?.as_bytes()));
+
+    if png.chunk_by_type("tRNS").is_some() {
+        let mut reordered: Vec<u8> = order.iter().map(|&old| alphas[old]).collect();
+        while reordered.last() == Some(&255) {
+            reordered.pop();
+        }
+        png.set_chunk(chunk("tRNS", reordered));
+    }
+
+    if let Some(bkgd) = png.chunk_by_type("bKGD") {
+        if let Some(&index) = bkgd.data().first() {
+            let remapped = old_to_new.get(index as usize).copied().unwrap_or(index);
+            png.set_chunk(chunk("bKGD", vec![remapped]));
+        }
+    }
+
+    if let Some(hist) = png.chunk_by_type("hIST") {
+        if hist.data().len() == n * 2 {
+            let data = order
+                .iter()
+                .flat_map(|&old| [hist.data()[old * 2], hist.data()[old * 2 + 1]])
+                .collect();
+            png.set_chunk(chunk("hIST", data));
+        }
+    }
+
+    Ok(())
+}
+
+/// Recovers a payload previously hidden with [`embed`].
+pub fn extract(png: &Png) -> Result<Vec<u8>, String> {
+    let palette = png
+        .palette()?
+        .ok_or_else(|| "Image has no PLTE chunk".to_string())?;
+    let alphas = palette_alphas(png, &palette)?;
+    let n = palette.len();
+
+    let mut remaining = canonical_order(&palette, &alphas)?;
+    let mut digits = Vec::with_capacity(n);
+    for old in 0..n {
+        let position = remaining
+            .iter()
+            .position(|&i| i == old)
+            .expect("canonical order covers every entry");
+        digits.push((position as u32, (n - old) as u32));
+        remaining.remove(position);
+    }
+
+    let mut number = vec![0u8; permutation_bits(n) / 8];
+    for &(digit, radix) in digits.iter().rev() {
+        mul_add(&mut number, radix, digit)?;
+    }
+
+    let length = number.first().copied().unwrap_or(0) as usize;
+    if length == 0 || length >= number.len() {
+        return Err("No payload found in palette order".to_string());
+    }
+    Ok(number[1..=length].to_vec())
+}
+
+/// Decodes compressed image data, maps every palette index through `old_to_new` and
+/// compresses the result again.
+fn remap_indices(ihdr: &Ihdr, data: &[u8], old_to_new: &[u8]) -> Result<Vec<u8>, String> {
+    let raw = pixels::decode(ihdr, data)?;
+    let indices: Vec<u8> = pixels::unpack_samples(ihdr, &raw)
+        .into_iter()
+        .map(|i| old_to_new.get(i as usize).copied().unwrap_or(i))
+        .collect();
+    pixels::encode(ihdr, &pixels::pack_samples(ihdr, &indices))
+}
+
+/// Remaps the fdAT frames of an animated image. Each frame is decoded with the size
+/// from its fcTL and written back as a single fdAT chunk where its first one was.
+fn remap_frames(png: &mut Png, ihdr: &Ihdr, old_to_new: &[u8]) -> Result<(), String> {
+    if png.chunk_by_type("fdAT").is_none() {
+        return Ok(());
+    }
+
+    let mut chunks = Vec::with_capacity(png.chunks().len());
+    let mut frame_ihdr: Option<Ihdr> = None;
+    // Position of the frame's first fdAT in `chunks`, and the frame's data so far.
+    let mut frame: Option<(usize, Vec<u8>)> = None;
+    let flush = |chunks: &mut Vec<Chunk>,
+                 frame: &mut Option<(usize, Vec<u8>)>,
+                 frame_ihdr: &Option<Ihdr>|
+     -> Result<(), String> {
+        if let Some((position, data)) = frame.take() {
+            let frame_ihdr = frame_ihdr
+                .as_ref()
+                .ok_or_else(|| "fdAT chunk without a preceding fcTL chunk".to_string())?;
+            // The sequence number is rewritten by Png::renumber_sequence.
+            let mut fdat = vec![0; 4];
+            fdat.extend(remap_indices(frame_ihdr, &data, old_to_new)?);
+            chunks.insert(position, chunk("fdAT", fdat));
+        }
+        Ok(())
+    };
+
+    for original in png.chunks() {
+        match original.chunk_type().to_string().as_str() {
+            "fdAT" => {
+                let data = FrameData::try_from(original.data())?.data;
+                frame
+                    .get_or_insert_with(|| (chunks.len(), Vec::new()))
+                    .1
+                    .extend_from_slice(data);
+                continue;
+            }
+            "fcTL" => {
+                flush(&mut chunks, &mut frame, &frame_ihdr)?;
+                let control = FrameControl::try_from(original.data())?;
+                frame_ihdr = Some(Ihdr {
+                    width: control.width,
+                    height: control.height,
+                    ..ihdr.clone()
+                });
+            }
+            "IEND" => flush(&mut chunks, &mut frame, &frame_ihdr)?,
+            _ => {}
+        }
+        chunks.push(Chunk::new(
+            original.chunk_type().clone(),
+            original.data().to_vec(),
+        ));
+    }
+    flush(&mut chunks, &mut frame, &frame_ihdr)?;
+
+    let trailer = std::mem::take(&mut png.trailer);
+    *png = Png::from_chunks(chunks);
+    png.trailer = trailer;
+    png.renumber_sequence();
+    Ok(())
+}
+
+fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+    Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+}
+
+fn palette_alphas(png: &Png, palette: &Palette) -> Result<Vec<u8>, String> {
+    let mut alphas = vec![255; palette.len()];
+    if let Some(Transparency::Indexed(values)) = png.transparency()? {
+        alphas[..values.len()].copy_from_slice(&values);
+    }
+    Ok(alphas)
+}
+
+/// Palette indices sorted by color and alpha, the reference order both sides agree on.
+fn canonical_order(palette: &Palette, alphas: &[u8]) -> Result<Vec<usize>, String> {
+    let key = |i: usize| {
+        let [r, g, b] = palette.entries()[i];
+        [r, g, b, alphas[i]]
+    };
+    let mut order: Vec<usize> = (0..palette.len()).collect();
+    order.sort_by_key(|&i| key(i));
+    if order.windows(2).any(|w| key(w[0]) == key(w[1])) {
+        return Err("Palette contains duplicate entries, ordering is ambiguous".to_string());
+    }
+    Ok(order)
+}
+
+/// `floor(log2(n!))`, computed exactly on a big-endian byte integer.
+fn permutation_bits(entries: usize) -> usize {
+    let mut factorial = vec![0u8; 256];
+    *factorial.last_mut().unwrap() = 1;
+    for k in 2..=entries as u32 {
+        mul_add(&mut factorial, k, 0).expect("256! fits in 256 bytes");
+    }
+    match factorial.iter().position(|&b| b != 0) {
+        Some(i) => (factorial.len() - i) * 8 - factorial[i].leading_zeros() as usize - 1,
+        None => 0,
+    }
+}
+
+fn div_rem(number: &mut [u8], divisor: u32) -> u32 {
+    let mut remainder = 0u32;
+    for byte in number.iter_mut() {
+        let value = (remainder << 8) | *byte as u32;
+        *byte = (value / divisor) as u8;
+        remainder = value % divisor;
+    }
+    remainder
+}
+
+fn mul_add(number: &mut [u8], factor: u32, addend: u32) -> Result<(), String> {
+    let mut carry = addend;
+    for byte in number.iter_mut().rev() {
+        let value = *byte as u32 * factor + carry;
+        *byte = value as u8;
+        carry = value >> 8;
+    }
+    if carry != 0 {
+        return Err("Palette order does not encode a valid payload".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::Ihdr;
+
+    fn indexed_png(entries: usize, trns: Option<Vec<u8>>) -> Png {
+        let ihdr = Ihdr {
+            width: entries as u32,
+            height: 2,
+            bit_depth: 8,
+            color_type: ColorType::Indexed,
+            interlace: false,
+        };
+        let indices: Vec<u8> = (0..entries as u8).chain((0..entries as u8).rev()).collect();
+        let palette: Vec<u8> = (0..entries as u8)
+            .flat_map(|i| [i, 255 - i, i / 2])
+            .collect();
+
+        let mut chunks = vec![chunk("IHDR", ihdr.as_bytes()), chunk("PLTE", palette)];
+        if let Some(trns) = trns {
+            chunks.push(chunk("tRNS", trns));
+        }
+        chunks.push(chunk("IDAT", pixels::encode(&ihdr, &indices).unwrap()));
+        chunks.push(chunk("IEND", vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_capacity() {
+        assert_eq!(permutation_bits(1), 0);
+        assert_eq!(permutation_bits(4), 4);
+        assert_eq!(permutation_bits(256), 1683);
+        assert_eq!(capacity(256), 209);
+        assert_eq!(capacity(16), 4);
+        assert_eq!(capacity(8), 0);
+    }
+
+    #[test]
+    fn test_embed_extract_round_trip() {
+        let mut png = indexed_png(64, None);
+        let before = png.indexed_to_rgba().unwrap();
+
+        embed(&mut png, b"hidden in plain sight").unwrap();
+
+        assert_eq!(png.indexed_to_rgba().unwrap(), before);
+        assert_eq!(extract(&png).unwrap(), b"hidden in plain sight");
+    }
+
+    #[test]
+    fn test_embed_keeps_transparency() {
+        let mut png = indexed_png(32, Some(vec![0, 64, 128]));
+        let before = png.indexed_to_rgba().unwrap();
+
+        embed(&mut png, b"alpha").unwrap();
+
+        assert_eq!(png.indexed_to_rgba().unwrap(), before);
+        assert_eq!(extract(&png).unwrap(), b"alpha");
+    }
+
+    #[test]
+    fn test_embed_remaps_animation_frames() {
+        let entries = 32;
+        let mut png = indexed_png(entries, None);
+        let ihdr = png.ihdr().unwrap();
+        let frame: Vec<u8> = (0..entries as u8).rev().chain(0..entries as u8).collect();
+        let fctl = |sequence: u32| {
+            let mut data = sequence.to_be_bytes().to_vec();
+            for value in [ihdr.width, ihdr.height, 0, 0] {
+                data.extend(value.to_be_bytes());
+            }
+            data.extend([0, 1, 0, 10, 0, 0]);
+            chunk("fcTL", data)
+        };
+        let mut fdat = 2u32.to_be_bytes().to_vec();
+        fdat.extend(pixels::encode(&ihdr, &frame).unwrap());
+        let mut chunks: Vec<Chunk> = png
+            .chunks()
+            .iter()
+            .map(|c| Chunk::new(c.chunk_type().clone(), c.data().to_vec()))
+            .collect();
+        let iend = chunks.pop().unwrap();
+        chunks.insert(
+            2,
+            chunk("acTL", [2u32.to_be_bytes(), 0u32.to_be_bytes()].concat()),
+        );
+        chunks.insert(3, fctl(0));
+        chunks.extend([fctl(1), chunk("fdAT", fdat), iend]);
+        png = Png::from_chunks(chunks);
+
+        embed(&mut png, b"frames").unwrap();
+
+        let palette = png.palette().unwrap().unwrap();
+        let frames: Vec<_> = png.frames().collect::<Result<_, _>>().unwrap();
+        let raw = pixels::decode(&ihdr, &frames[1].data.concat()).unwrap();
+        let colors: Vec<[u8; 3]> = raw.iter().map(|&i| palette.entries()[i as usize]).collect();
+        let expected: Vec<[u8; 3]> = frame.iter().map(|&i| [i, 255 - i, i / 2]).collect();
+        assert_eq!(colors, expected);
+        assert!(crate::apng::sequence_problems(&png).is_empty());
+        assert_eq!(extract(&png).unwrap(), b"frames");
+    }
+
+    #[test]
+    fn test_payload_too_large() {
+        let mut png = indexed_png(16, None);
+        assert!(embed(&mut png, &[0u8; 32]).is_err());
+    }
+
+    #[test]
+    fn test_empty_payload() {
+        let mut png = indexed_png(16, None);
+        let before = png.as_bytes();
+        assert!(embed(&mut png, b"").is_err());
+        assert_eq!(png.as_bytes(), before);
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::ihdr::Ihdr;
+use crate::zlib;
+
+/// Inflates and unfilters concatenated IDAT data into raw scanlines without filter bytes.
+pub fn decode(ihdr: &Ihdr, idat: &[u8]) -> Result<Vec<u8>, String> {
+    if ihdr.interlace {
+        return Err("Interlaced images are not supported".to_string());
+    }
+
+    let row_bytes = ihdr.row_bytes();
+    let height = ihdr.height as usize;
+    let expected = (row_bytes + 1)
+        .checked_mul(height)
+        .ok_or_else(|| "Image dimensions are too large".to_string())?;
+
+    // IHDR bounds the output, so a small IDAT cannot inflate without limit, and
+    // nothing is allocated for the image beyond what the data actually holds.
+    let filtered = zlib::inflate(idat, expected)
+        .map_err(|e| format!("Failed to inflate image data: {}", e))?;
+    if filtered.len() < expected {
+        return Err("Image data is shorter than IHDR dimensions require".to_string());
+    }
+
+    let stride = ihdr.filter_stride();
+    let mut raw = vec![0u8; row_bytes * height];
+
+    for y in 0..height {
+        let line = &filtered[y * (row_bytes + 1)..(y + 1) * (row_bytes + 1)];
+        let filter = line[0];
+        let (done, rest) = raw.split_at_mut(y * row_bytes);
+        let prev = if y == 0 {
+            None
+        } else {
+            Some(&done[(y - 1) * row_bytes..])
+        };
+        let row = &mut rest[..row_bytes];
+
+        for x in 0..row_bytes {
+            let a = if x >= stride { row[x - stride] } else { 0 };
+            let b = prev.map_or(0, |p| p[x]);
+            let c = match prev {
+                Some(p) if x >= stride => p[x - stride],
+                _ => 0,
+            };
+            let value = line[x + 1];
+            row[x] = match filter {
+                0 => value,
+                1 => value.wrapping_add(a),
+                2 => value.wrapping_add(b),
+                3 => value.wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                4 => value.wrapping_add(paeth(a, b, c)),
+                other => return Err(format!("Unknown filter type {} on row {}", other, y)),
+            };
+        }
+    }
+
+    Ok(raw)
+}
+
+/// Filters raw scanlines with filter type None and deflates them into IDAT data.
+pub fn encode(ihdr: &Ihdr, raw: &[u8]) -> Result<Vec<u8>, String> {
+    let row_bytes = ihdr.row_bytes();
+    if raw.len() != row_bytes * ihdr.height as usize {
+        return Err("Raw image data does not match IHDR dimensions".to_string());
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in raw.chunks(row_bytes) {
+        encoder
+            .write_all(&[0])
+            .and_then(|_| encoder.write_all(row))
+            .map_err(|e| format!("Failed to deflate image data: {}", e))?;
+    }
+    encoder
+        .finish()
+        .map_err(|e| format!("Failed to deflate image data: {}", e))
+}
+
+/// Splits packed scanlines into one sample value per byte, dropping row padding bits.
+pub fn unpack_samples(ihdr: &Ihdr, raw: &[u8]) -> Vec<u8> {
+    let bit_depth = ihdr.bit_depth as usize;
+    if bit_depth >= 8 {
+        return raw.to_vec();
+    }
+
+    let samples_per_row = ihdr.width as usize * ihdr.color_type.channels() as usize;
+    let mask = (1u8 << bit_depth) - 1;
+    let mut samples = Vec::with_capacity(samples_per_row * ihdr.height as usize);
+    for row in raw.chunks(ihdr.row_bytes()) {
+        for i in 0..samples_per_row {
+            let bit = i * bit_depth;
+            let shift = 8 - bit_depth - bit % 8;
+            samples.push((row[bit / 8] >> shift) & mask);
+        }
+    }
+    samples
+}
+
+/// Inverse of [`unpack_samples`]: packs one sample per byte back into scanlines.
+pub fn pack_samples(ihdr: &Ihdr, samples: &[u8]) -> Vec<u8> {
+    let bit_depth = ihdr.bit_depth as usize;
+    if bit_depth >= 8 {
+        return samples.to_vec();
+    }
+
+    let samples_per_row = ihdr.width as usize * ihdr.color_type.channels() as usize;
+    let mut raw = Vec::with_capacity(ihdr.row_bytes() * ihdr.height as usize);
+    for row in samples.chunks(samples_per_row) {
+        let mut packed = vec![0u8; ihdr.row_bytes()];
+        for (i, sample) in row.iter().enumerate() {
+            let bit = i * bit_depth;
+            let shift = 8 - bit_depth - bit % 8;
+            packed[bit / 8] |= sample << shift;
+        }
+        raw.extend_from_slice(&packed);
+    }
+    raw
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ihdr::ColorType;
+
+    fn indexed_ihdr(bit_depth: u8) -> Ihdr {
+        Ihdr {
+            width: 5,
+            height: 3,
+            bit_depth,
+            color_type: ColorType::Indexed,
+            interlace: false,
+        }
+    }
+
+    #[test]
+    fn test_encode_decode_round_trip() {
+        let ihdr = indexed_ihdr(8);
+        let raw: Vec<u8> = (0..15).collect();
+        let idat = encode(&ihdr, &raw).unwrap();
+        assert_eq!(decode(&ihdr, &idat).unwrap(), raw);
+    }
+
+    #[test]
+    fn test_decode_sub_and_up_filters() {
+        let ihdr = indexed_ihdr(8);
+        #[rustfmt::skip]
+        let filtered = [
+            1, 1, 1, 1, 1, 1,   // Sub
+            2, 1, 1, 1, 1, 1,   // Up
+            0, 9, 9, 9, 9, 9,   // None
+        ];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&filtered).unwrap();
+        let idat = encoder.finish().unwrap();
+
+        let raw = decode(&ihdr, &idat).unwrap();
+        assert_eq!(raw, vec![1, 2, 3, 4, 5, 2, 3, 4, 5, 6, 9, 9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn test_decode_refuses_excess_data() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 1 << 20]).unwrap();
+        let bomb = encoder.finish().unwrap();
+
+        // 5x3 indexed pixels need 18 bytes with filter bytes; the rest is refused.
+        assert!(decode(&indexed_ihdr(8), &bomb).is_err());
+    }
+
+    #[test]
+    fn test_decode_huge_dimensions() {
+        let ihdr = Ihdr {
+            width: u32::MAX,
+            height: u32::MAX,
+            bit_depth: 16,
+            color_type: ColorType::Rgba,
+            interlace: false,
+        };
+        let idat = encode(&indexed_ihdr(8), &[0; 15]).unwrap();
+        assert!(decode(&ihdr, &idat).is_err());
+    }
+
+    #[test]
+    fn test_pack_unpack_samples() {
+        let ihdr = indexed_ihdr(2);
+        let samples = vec![0, 1, 2, 3, 1, 3, 2, 1, 0, 0, 2, 2, 2, 2, 2];
+        let raw = pack_samples(&ihdr, &samples);
+        assert_eq!(raw.len(), ihdr.row_bytes() * 3);
+        assert_eq!(unpack_samples(&ihdr, &raw), samples);
+    }
+}
@@ -1,20 +1,50 @@
 use std::fmt;
 
+use crate::ancillary::{
+    Background, Histogram, PhysicalDimensions, SignificantBits, SuggestedPalette, Time,
+};
+use crate::apng::{self, AnimationControl, Frames};
 use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::codec::ChunkCodec;
+use crate::color::{Chromaticities, CodePoints, Gamma, IccProfile, RenderingIntent};
+use crate::exif::Exif;
+use crate::ihdr::{ColorType, Ihdr};
+use crate::palette::{Palette, Transparency};
+use crate::pixels;
+use crate::text::TextChunk;
+use crate::xmp::{self, Xmp};
 
 pub struct Png {
     pub chunks: Vec<Chunk>,
+    /// Bytes following the IEND chunk, kept verbatim.
+    pub trailer: Vec<u8>,
 }
 
 impl Png {
     pub const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
+    /// Ancillary chunk types that stripping always keeps: without tRNS the
+    /// image looks different, and without the APNG chunks it stops animating.
+    pub const ESSENTIAL_ANCILLARY: [&'static str; 4] = ["tRNS", "acTL", "fcTL", "fdAT"];
+
     pub fn from_chunks(chunks: Vec<Chunk>) -> Self {
-        Png { chunks }
+        Png {
+            chunks,
+            trailer: Vec::new(),
+        }
     }
 
+    /// Adds a chunk at the end of the file, keeping IEND as the last chunk.
     pub fn append_chunk(&mut self, chunk: Chunk) {
-        self.chunks.push(chunk);
+        match self.chunks.last() {
+            Some(last) if last.chunk_type().to_string() == "IEND" => {
+                let pos = self.chunks.len() - 1;
+                self.chunks.insert(pos, chunk);
+            }
+            _ => self.chunks.push(chunk),
+        }
+        self.renumber_sequence();
     }
 
     pub fn remove_first_chunk(&mut self, chunk_type: &str) -> Result<Chunk, String> {
@@ -24,12 +54,147 @@ impl Png {
             .position(|c| c.chunk_type().to_string() == chunk_type)
         {
             let chunk = self.chunks.remove(pos);
+            self.renumber_sequence();
             Ok(chunk)
         } else {
             Err(format!("Chunk type '{}' not found", chunk_type))
         }
     }
 
+    /// Replaces the first chunk of the same type, or inserts the chunk before IDAT if absent.
+    pub fn set_chunk(&mut self, chunk: Chunk) {
+        let chunk_type = chunk.chunk_type().to_string();
+        if let Some(pos) = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type().to_string() == chunk_type)
+        {
+            self.chunks[pos] = chunk;
+        } else {
+            let pos = self.image_data_position();
+            self.chunks.insert(pos, chunk);
+        }
+        self.renumber_sequence();
+    }
+
+    /// Index of the first IDAT (or IEND) chunk, where chunks that must precede the image go.
+    fn image_data_position(&self) -> usize {
+        self.chunks
+            .iter()
+            .position(|c| matches!(c.chunk_type().to_string().as_str(), "IDAT" | "IEND"))
+            .unwrap_or(self.chunks.len())
+    }
+
+    /// Replaces all IDAT chunks with a single one holding `data`, at the first IDAT position.
+    pub fn replace_image_data(&mut self, data: Vec<u8>) {
+        let is_idat = |c: &Chunk| c.chunk_type().to_string() == "IDAT";
+        let pos = self
+            .chunks
+            .iter()
+            .position(is_idat)
+            .or_else(|| {
+                self.chunks
+                    .iter()
+                    .position(|c| c.chunk_type().to_string() == "IEND")
+            })
+            .unwrap_or(self.chunks.len());
+        self.chunks.retain(|c| !is_idat(c));
+        let idat_type = ChunkType::try_from(*b"IDAT").expect("IDAT is a valid chunk type");
+        self.chunks.insert(pos, Chunk::new(idat_type, data));
+        self.renumber_sequence();
+    }
+
+    /// Removes every ancillary chunk whose type is not in `keep` or
+    /// [`ESSENTIAL_ANCILLARY`](Self::ESSENTIAL_ANCILLARY) and returns the removed
+    /// chunks. The data after IEND is removed too, unless `keep_trailer` is set.
+    pub fn strip_ancillary(&mut self, keep: &[ChunkType], keep_trailer: bool) -> Vec<Chunk> {
+        let (kept, removed) = std::mem::take(&mut self.chunks).into_iter().partition(|c| {
+            c.chunk_type().is_critical()
+                || keep.contains(c.chunk_type())
+                || Png::ESSENTIAL_ANCILLARY.contains(&c.chunk_type().to_string().as_str())
+        });
+        self.chunks = kept;
+        if !keep_trailer {
+            self.trailer.clear();
+        }
+        self.renumber_sequence();
+        removed
+    }
+
+    /// Copies ancillary chunks from `source`, following the PNG rules for the safe-to-copy bit:
+    /// unsafe-to-copy chunks are only copied when both images have identical critical chunks.
+    /// Returns the number of chunks copied and the types of the chunks that were dropped.
+    pub fn copy_ancillary_from(&mut self, source: &Png) -> (usize, Vec<ChunkType>) {
+        let same_image = self.critical_chunks_match(source);
+        self.copy_ancillary(source, same_image)
+    }
+
+    /// Copies all ancillary chunks from `source`, including unsafe-to-copy ones.
+    /// Returns the number of chunks copied.
+    pub fn copy_all_ancillary_from(&mut self, source: &Png) -> usize {
+        self.copy_ancillary(source, true).0
+    }
+
+    fn critical_chunks_match(&self, other: &Png) -> bool {
+        let critical = |png: &Png| -> Vec<Vec<u8>> {
+            png.chunks
+                .iter()
+                .filter(|c| c.chunk_type().is_critical())
+                .map(|c| c.as_bytes())
+                .collect()
+        };
+        critical(self) == critical(other)
+    }
+
+    fn copy_ancillary(&mut self, source: &Png, include_unsafe: bool) -> (usize, Vec<ChunkType>) {
+        let mut copied = 0;
+        let mut dropped = Vec::new();
+        let mut before_image_data = true;
+
+        for chunk in &source.chunks {
+            let chunk_type = chunk.chunk_type();
+            if chunk_type.to_string() == "IDAT" {
+                before_image_data = false;
+            }
+            if chunk_type.is_critical() {
+                continue;
+            }
+            if !chunk_type.is_safe_to_copy() && !include_unsafe {
+                dropped.push(chunk_type.clone());
+                continue;
+            }
+            if self.chunks.iter().any(|c| c.as_bytes() == chunk.as_bytes()) {
+                continue;
+            }
+
+            let copy = Chunk::new(chunk_type.clone(), chunk.data().to_vec());
+            let existing = self
+                .chunks
+                .iter()
+                .position(|c| c.chunk_type() == chunk_type);
+            match existing {
+                Some(pos) if chunk_type.is_unique() => self.chunks[pos] = copy,
+                _ if before_image_data => {
+                    let pos = self.image_data_position();
+                    self.chunks.insert(pos, copy);
+                }
+                _ => self.append_chunk(copy),
+            }
+            copied += 1;
+        }
+
+        self.renumber_sequence();
+        (copied, dropped)
+    }
+
+    /// Keeps APNG fcTL/fdAT sequence numbers consecutive and the acTL frame count in sync.
+    /// Called by every mutating method; call it after editing `chunks` directly.
+    pub fn renumber_sequence(&mut self) {
+        if self.is_animated() {
+            apng::renumber(&mut self.chunks);
+        }
+    }
+
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
         self.chunks
             .iter()
@@ -42,6 +207,7 @@ impl Png {
         for chunk in &self.chunks {
             bytes.extend_from_slice(&chunk.as_bytes());
         }
+        bytes.extend_from_slice(&self.trailer);
         bytes
     }
 
@@ -52,6 +218,218 @@ impl Png {
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
+
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    pub fn ihdr(&self) -> Result<Ihdr, String> {
+        let chunk = self
+            .chunk_by_type("IHDR")
+            .ok_or_else(|| "Missing IHDR chunk".to_string())?;
+        Ihdr::try_from(chunk)
+    }
+
+    pub fn palette(&self) -> Result<Option<Palette>, String> {
+        let Some(chunk) = self.chunk_by_type("PLTE") else {
+            return Ok(None);
+        };
+        let palette = Palette::try_from(chunk.data())?;
+        palette.validate(&self.ihdr()?)?;
+        Ok(Some(palette))
+    }
+
+    pub fn transparency(&self) -> Result<Option<Transparency>, String> {
+        let Some(chunk) = self.chunk_by_type("tRNS") else {
+            return Ok(None);
+        };
+        let palette = self.palette()?;
+        Transparency::parse(chunk.data(), &self.ihdr()?, palette.as_ref()).map(Some)
+    }
+
+    pub fn animation_control(&self) -> Result<Option<AnimationControl>, String> {
+        self.chunk_by_type("acTL")
+            .map(|chunk| AnimationControl::try_from(chunk.data()))
+            .transpose()
+    }
+
+    pub fn exif(&self) -> Result<Option<Exif>, String> {
+        self.chunk_by_type("eXIf")
+            .map(|chunk| Exif::try_from(chunk.data()))
+            .transpose()
+    }
+
+    /// Replaces the eXIf chunk, or adds one before the image data.
+    pub fn set_exif(&mut self, exif: &Exif) {
+        let chunk_type = ChunkType::try_from(*b"eXIf").expect("eXIf is a valid chunk type");
+        self.set_chunk(Chunk::new(chunk_type, exif.as_bytes()));
+    }
+
+    /// The XMP packet stored in the iTXt chunk with keyword `XML:com.adobe.xmp`.
+    pub fn xmp(&self) -> Result<Option<Xmp>, String> {
+        self.xmp_position()
+            .map(|pos| Xmp::try_from(&self.chunks[pos]))
+            .transpose()
+    }
+
+    /// Replaces the XMP packet in place, or adds it before the image data.
+    pub fn set_xmp(&mut self, xmp: &Xmp) -> Result<(), String> {
+        let chunk = xmp.to_chunk()?;
+        match self.xmp_position() {
+            Some(pos) => self.chunks[pos] = chunk,
+            None => {
+                let pos = self.image_data_position();
+                self.chunks.insert(pos, chunk);
+            }
+        }
+        self.renumber_sequence();
+        Ok(())
+    }
+
+    fn xmp_position(&self) -> Option<usize> {
+        self.chunks.iter().position(|c| {
+            c.chunk_type().to_string() == "iTXt"
+                && TextChunk::try_from(c).is_ok_and(|text| text.keyword == xmp::KEYWORD)
+        })
+    }
+
+    pub fn gamma(&self) -> Result<Option<Gamma>, String> {
+        self.parse_chunk("gAMA")
+    }
+
+    pub fn chromaticities(&self) -> Result<Option<Chromaticities>, String> {
+        self.parse_chunk("cHRM")
+    }
+
+    pub fn rendering_intent(&self) -> Result<Option<RenderingIntent>, String> {
+        self.parse_chunk("sRGB")
+    }
+
+    pub fn icc_profile(&self) -> Result<Option<IccProfile>, String> {
+        self.parse_chunk("iCCP")
+    }
+
+    pub fn code_points(&self) -> Result<Option<CodePoints>, String> {
+        self.parse_chunk("cICP")
+    }
+
+    pub fn physical_dimensions(&self) -> Result<Option<PhysicalDimensions>, String> {
+        self.parse_chunk("pHYs")
+    }
+
+    pub fn time(&self) -> Result<Option<Time>, String> {
+        self.parse_chunk("tIME")
+    }
+
+    pub fn background(&self) -> Result<Option<Background>, String> {
+        let Some(chunk) = self.chunk_by_type("bKGD") else {
+            return Ok(None);
+        };
+        let palette = self.palette()?;
+        Background::parse(chunk.data(), &self.ihdr()?, palette.as_ref()).map(Some)
+    }
+
+    pub fn significant_bits(&self) -> Result<Option<SignificantBits>, String> {
+        let Some(chunk) = self.chunk_by_type("sBIT") else {
+            return Ok(None);
+        };
+        SignificantBits::parse(chunk.data(), &self.ihdr()?).map(Some)
+    }
+
+    pub fn histogram(&self) -> Result<Option<Histogram>, String> {
+        let Some(chunk) = self.chunk_by_type("hIST") else {
+            return Ok(None);
+        };
+        let palette = self
+            .palette()?
+            .ok_or_else(|| "hIST chunk without a PLTE chunk".to_string())?;
+        Histogram::parse(chunk.data(), &palette).map(Some)
+    }
+
+    /// All sPLT chunks; unlike most ancillary chunks, several may be present.
+    pub fn suggested_palettes(&self) -> Result<Vec<SuggestedPalette>, String> {
+        self.chunks
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "sPLT")
+            .map(|c| SuggestedPalette::try_from(c.data()))
+            .collect()
+    }
+
+    pub fn set_physical_dimensions(&mut self, phys: &PhysicalDimensions) {
+        let chunk_type = ChunkType::try_from(*b"pHYs").expect("pHYs is a valid chunk type");
+        self.set_chunk(Chunk::new(chunk_type, phys.as_bytes()));
+    }
+
+    pub fn set_time(&mut self, time: &Time) {
+        let chunk_type = ChunkType::try_from(*b"tIME").expect("tIME is a valid chunk type");
+        self.set_chunk(Chunk::new(chunk_type, time.as_bytes()));
+    }
+
+    /// Records the current time as the last modification time.
+    pub fn touch(&mut self) {
+        self.set_time(&Time::now());
+    }
+
+    /// Embeds an ICC profile. Since sRGB and iCCP must not both be present, an existing
+    /// sRGB chunk is removed.
+    pub fn set_icc_profile(&mut self, icc: &IccProfile) -> Result<(), String> {
+        let chunk_type = ChunkType::try_from(*b"iCCP").expect("iCCP is a valid chunk type");
+        self.set_chunk(Chunk::new(chunk_type, icc.as_bytes()?));
+        self.chunks.retain(|c| c.chunk_type().to_string() != "sRGB");
+        self.renumber_sequence();
+        Ok(())
+    }
+
+    /// Decodes the first chunk of the codec's type into its typed value.
+    pub fn decode<C: ChunkCodec>(&self, codec: &C) -> Result<Option<C::Value>, String> {
+        let chunk_type = codec.chunk_type().to_string();
+        self.chunk_by_type(&chunk_type)
+            .map(|chunk| codec.decode_in(chunk.data(), self))
+            .transpose()
+    }
+
+    fn parse_chunk<T>(&self, chunk_type: &str) -> Result<Option<T>, String>
+    where
+        T: for<'a> TryFrom<&'a [u8], Error = String>,
+    {
+        self.chunk_by_type(chunk_type)
+            .map(|chunk| T::try_from(chunk.data()))
+            .transpose()
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.chunk_by_type("acTL").is_some()
+    }
+
+    /// Iterates over the frames of an animated PNG; yields nothing for still images.
+    pub fn frames(&self) -> Frames<'_> {
+        Frames::new(self)
+    }
+
+    /// Concatenated contents of all IDAT chunks, i.e. the zlib stream of the image.
+    pub fn image_data(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "IDAT")
+            .flat_map(|c| c.data().iter().copied())
+            .collect()
+    }
+
+    /// Decodes an indexed-color image into RGBA pixels using PLTE and tRNS.
+    pub fn indexed_to_rgba(&self) -> Result<Vec<u8>, String> {
+        let ihdr = self.ihdr()?;
+        if ihdr.color_type != ColorType::Indexed {
+            return Err(format!("Expected indexed image, got {}", ihdr.color_type));
+        }
+        let palette = self
+            .palette()?
+            .ok_or_else(|| "Indexed image is missing a PLTE chunk".to_string())?;
+        let transparency = self.transparency()?;
+
+        let raw = pixels::decode(&ihdr, &self.image_data())?;
+        let indices = pixels::unpack_samples(&ihdr, &raw);
+        palette.to_rgba(&indices, transparency.as_ref())
+    }
 }
 
 impl TryFrom<&[u8]> for Png {
@@ -62,7 +440,7 @@ impl TryFrom<&[u8]> for Png {
             return Err("Input data too short to contain PNG header".into());
         }
 
-        if &value[..8] != Png::STANDARD_HEADER {
+        if value[..8] != Png::STANDARD_HEADER {
             return Err("Invalid PNG header".into());
         }
 
@@ -77,11 +455,25 @@ impl TryFrom<&[u8]> for Png {
                 return Err("Chunk extends beyond input length".into());
             }
 
+            let is_iend = chunk.chunk_type().to_string() == "IEND";
             chunks.push(chunk);
             offset += total_len;
+
+            if is_iend {
+                break;
+            }
         }
 
-        Ok(Png::from_chunks(chunks))
+        // Earlier versions appended message chunks after IEND; keep reading
+        // well-formed chunks there and treat whatever follows as the trailer.
+        while let Ok(chunk) = Chunk::try_from(&value[offset..]) {
+            offset += 12 + chunk.length() as usize;
+            chunks.push(chunk);
+        }
+
+        let mut png = Png::from_chunks(chunks);
+        png.trailer = value[offset..].to_vec();
+        Ok(png)
     }
 }
 
@@ -91,6 +483,9 @@ impl fmt::Display for Png {
         for chunk in &self.chunks {
             writeln!(f, "- Chunk type: {}", chunk.chunk_type())?;
         }
+        if !self.trailer.is_empty() {
+            writeln!(f, "- {} bytes after IEND", self.trailer.len())?;
+        }
         Ok(())
     }
 }
@@ -101,6 +496,7 @@ mod tests {
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
+    use std::str::FromStr;
 
     fn testing_chunks() -> Vec<Chunk> {
         vec![
@@ -223,6 +619,102 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_strip_ancillary() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let keep = [ChunkType::from_str("gAMA").unwrap()];
+
+        let removed = png.strip_ancillary(&keep, false);
+
+        let removed: Vec<String> = removed.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(removed, ["sRGB", "pHYs"]);
+        assert!(png.chunk_by_type("gAMA").is_some());
+        assert!(png
+            .chunks()
+            .iter()
+            .all(|c| c.chunk_type().is_critical() || c.chunk_type().to_string() == "gAMA"));
+    }
+
+    #[test]
+    fn test_strip_keeps_essential_chunks() {
+        let mut png = indexed_png(&[1, 2, 3], Some(&[0]), &[0]);
+        png.append_chunk(chunk_from_strings("tEXt", "Title\0x").unwrap());
+        png.trailer = b"hidden".to_vec();
+
+        let removed = png.strip_ancillary(&[], true);
+        assert_eq!(removed.len(), 1);
+        assert!(png.chunk_by_type("tRNS").is_some());
+        assert_eq!(png.trailer(), b"hidden");
+
+        png.strip_ancillary(&[], false);
+        assert!(png.trailer().is_empty());
+    }
+
+    #[test]
+    fn test_copy_ancillary_from_other_image() {
+        let source = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut source = Png::from_chunks(source.chunks);
+        source.append_chunk(chunk_from_strings("ruSt", "safe").unwrap());
+        source.append_chunk(chunk_from_strings("ruST", "unsafe").unwrap());
+
+        let mut target = indexed_png(&[1, 2, 3], None, &[0]);
+        let (copied, dropped) = target.copy_ancillary_from(&source);
+
+        assert_eq!(copied, 2);
+        let dropped: Vec<String> = dropped.iter().map(|t| t.to_string()).collect();
+        assert_eq!(dropped, ["sRGB", "gAMA", "ruST"]);
+        assert!(target.chunk_by_type("ruSt").is_some());
+        assert!(target.chunk_by_type("pHYs").is_some());
+        assert!(target.chunk_by_type("ruST").is_none());
+        assert_eq!(
+            target.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+        assert!(
+            target
+                .chunks()
+                .iter()
+                .position(|c| c.chunk_type().to_string() == "pHYs")
+                < target
+                    .chunks()
+                    .iter()
+                    .position(|c| c.chunk_type().to_string() == "IDAT")
+        );
+    }
+
+    #[test]
+    fn test_copy_ancillary_from_same_image() {
+        let mut source = Png::try_from(&PNG_FILE[..]).unwrap();
+        source.append_chunk(chunk_from_strings("ruST", "unsafe").unwrap());
+        let mut target = Png::try_from(&PNG_FILE[..]).unwrap();
+        target.strip_ancillary(&[], false);
+
+        let (copied, dropped) = target.copy_ancillary_from(&source);
+
+        assert_eq!(copied, 4);
+        assert!(dropped.is_empty());
+        assert_eq!(target.as_bytes(), source.as_bytes());
+    }
+
+    #[test]
+    fn test_copy_all_ancillary_from() {
+        let mut source = Png::try_from(&PNG_FILE[..]).unwrap();
+        source.append_chunk(chunk_from_strings("ruST", "unsafe").unwrap());
+        let mut target = indexed_png(&[1, 2, 3], None, &[0]);
+        target.append_chunk(Chunk::new(
+            ChunkType::from_str("pHYs").unwrap(),
+            vec![0, 0, 0, 1, 0, 0, 0, 1, 0],
+        ));
+        let before = target.chunks().len();
+
+        let copied = target.copy_all_ancillary_from(&source);
+
+        assert!(target.chunk_by_type("ruST").is_some());
+        // pHYs replaces the target's own, so the chunk count grows by one less.
+        assert_eq!(copied, 4);
+        assert_eq!(target.chunks().len(), before + 3);
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -255,6 +747,234 @@ mod tests {
         let _png_string = format!("{}", png);
     }
 
+    #[test]
+    fn test_trailing_data_after_iend() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(b"appended archive data");
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(png.trailer(), b"appended archive data");
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_chunks_after_iend_are_kept() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes.extend_from_slice(&chunk_from_strings("ruSt", "legacy").unwrap().as_bytes());
+        bytes.extend_from_slice(b"trailing bytes");
+
+        let png = Png::try_from(bytes.as_ref()).unwrap();
+
+        assert_eq!(
+            &png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "legacy"
+        );
+        assert_eq!(png.trailer(), b"trailing bytes");
+        assert_eq!(png.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_append_chunk_before_iend() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("TeSt", "Message").unwrap());
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .rev()
+            .take(2)
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, ["IEND", "TeSt"]);
+    }
+
+    #[test]
+    fn test_no_trailing_data() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.trailer().is_empty());
+    }
+
+    fn indexed_png(palette: &[u8], trns: Option<&[u8]>, indices: &[u8]) -> Png {
+        use crate::ihdr::Ihdr;
+
+        let ihdr = Ihdr {
+            width: indices.len() as u32,
+            height: 1,
+            bit_depth: 8,
+            color_type: ColorType::Indexed,
+            interlace: false,
+        };
+        let idat = pixels::encode(&ihdr, indices).unwrap();
+
+        let mut chunks = vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), ihdr.as_bytes()),
+            Chunk::new(ChunkType::from_str("PLTE").unwrap(), palette.to_vec()),
+        ];
+        if let Some(trns) = trns {
+            chunks.push(Chunk::new(
+                ChunkType::from_str("tRNS").unwrap(),
+                trns.to_vec(),
+            ));
+        }
+        chunks.push(Chunk::new(ChunkType::from_str("IDAT").unwrap(), idat));
+        chunks.push(Chunk::new(ChunkType::from_str("IEND").unwrap(), vec![]));
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_palette_and_transparency() {
+        let png = indexed_png(&[1, 2, 3, 4, 5, 6], Some(&[0]), &[0, 1]);
+        let palette = png.palette().unwrap().unwrap();
+        assert_eq!(palette.entries(), &[[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(
+            png.transparency().unwrap(),
+            Some(Transparency::Indexed(vec![0]))
+        );
+    }
+
+    #[test]
+    fn test_indexed_to_rgba() {
+        let png = indexed_png(&[1, 2, 3, 4, 5, 6], Some(&[0]), &[1, 0, 1]);
+        let rgba = png.indexed_to_rgba().unwrap();
+        assert_eq!(rgba, vec![4, 5, 6, 255, 1, 2, 3, 0, 4, 5, 6, 255]);
+    }
+
+    #[test]
+    fn test_indexed_to_rgba_rejects_truecolor() {
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.palette().unwrap().is_none());
+        assert!(png.indexed_to_rgba().is_err());
+    }
+
+    #[test]
+    fn test_set_xmp() {
+        use crate::text::{TextChunk, TextKind};
+        use crate::xmp::Xmp;
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let comment = TextChunk::new(TextKind::International, "Comment", "hi");
+        png.set_chunk(comment.to_chunk().unwrap());
+
+        let mut xmp = Xmp::new();
+        xmp.set("dc:rights", "CC0").unwrap();
+        png.set_xmp(&xmp).unwrap();
+        xmp.set("dc:rights", "CC BY 4.0").unwrap();
+        png.set_xmp(&xmp).unwrap();
+
+        assert_eq!(png.xmp().unwrap().unwrap(), xmp);
+        let itxt = png
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "iTXt")
+            .count();
+        assert_eq!(itxt, 2);
+    }
+
+    #[test]
+    fn test_metadata_setters_renumber_frames() {
+        use crate::color::IccProfile;
+        use crate::xmp::Xmp;
+
+        let sequence = |number: u32| number.to_be_bytes().to_vec();
+        let frames = || {
+            let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+            png.chunks.insert(
+                1,
+                Chunk::new(
+                    ChunkType::from_str("acTL").unwrap(),
+                    [sequence(1), sequence(0)].concat(),
+                ),
+            );
+            let fctl = [sequence(5), vec![0; 22]].concat();
+            png.chunks
+                .insert(2, Chunk::new(ChunkType::from_str("fcTL").unwrap(), fctl));
+            png
+        };
+
+        let mut png = frames();
+        png.set_xmp(&Xmp::new()).unwrap();
+        assert!(apng::sequence_problems(&png).is_empty());
+
+        let mut png = frames();
+        png.set_icc_profile(&IccProfile::new("Test", vec![1, 2, 3]))
+            .unwrap();
+        assert!(apng::sequence_problems(&png).is_empty());
+    }
+
+    #[test]
+    fn test_color_chunks() {
+        use crate::color::{Gamma, IccProfile, RenderingIntent};
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert_eq!(png.gamma().unwrap(), Some(Gamma::SRGB));
+        assert_eq!(
+            png.rendering_intent().unwrap(),
+            Some(RenderingIntent::Perceptual)
+        );
+        assert_eq!(png.icc_profile().unwrap(), None);
+
+        let icc = IccProfile::new("Test", vec![1, 2, 3]);
+        png.set_icc_profile(&icc).unwrap();
+        assert_eq!(png.icc_profile().unwrap(), Some(icc));
+        assert_eq!(png.rendering_intent().unwrap(), None);
+    }
+
+    #[test]
+    fn test_small_ancillary_chunks() {
+        use crate::ancillary::{PhysicalDimensions, Time};
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let phys = png.physical_dimensions().unwrap().unwrap();
+        assert_eq!(phys.to_string(), "96 dpi");
+        assert_eq!(png.time().unwrap(), None);
+        assert_eq!(png.background().unwrap(), None);
+
+        png.set_physical_dimensions(&PhysicalDimensions::from_dpi(300.0));
+        let time = Time::from_unix(0);
+        png.set_time(&time);
+
+        assert_eq!(
+            png.physical_dimensions().unwrap().unwrap().to_string(),
+            "300 dpi"
+        );
+        assert_eq!(png.time().unwrap(), Some(time));
+        assert_eq!(
+            png.chunks()
+                .iter()
+                .filter(|c| c.chunk_type().to_string() == "pHYs")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_set_exif() {
+        use crate::exif::{ByteOrder, Exif};
+
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        assert!(png.exif().unwrap().is_none());
+
+        let mut exif = Exif::new(ByteOrder::Little);
+        exif.set_tag("Artist", "PNGme").unwrap();
+        png.set_exif(&exif);
+
+        assert_eq!(png.exif().unwrap().unwrap(), exif);
+        let idat = png
+            .chunks()
+            .iter()
+            .position(|c| c.chunk_type().to_string() == "IDAT");
+        let exif_index = png
+            .chunks()
+            .iter()
+            .position(|c| c.chunk_type().to_string() == "eXIf");
+        assert!(exif_index < idat);
+    }
+
     // This is the raw bytes for a shrunken version of the `dice.png` image on Wikipedia
     const PNG_FILE: [u8; 4803] = [
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 50, 0, 0, 0, 50, 8,
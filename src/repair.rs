@@ -0,0 +1,320 @@
+use std::fmt;
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IssueKind {
+    BadCrc {
+        stored: u32,
+        computed: u32,
+    },
+    /// `kept` is set when the partial data was kept, which only happens for IDAT.
+    Truncated {
+        declared: u32,
+        available: usize,
+        kept: bool,
+    },
+    InvalidType([u8; 4]),
+}
+
+/// A problem found while parsing one chunk in lenient mode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIssue {
+    pub index: usize,
+    pub offset: usize,
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for ParseIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "chunk {} at offset {}: ", self.index, self.offset)?;
+        match &self.kind {
+            IssueKind::BadCrc { stored, computed } => write!(
+                f,
+                "CRC mismatch (stored {:08x}, computed {:08x})",
+                stored, computed
+            ),
+            IssueKind::Truncated {
+                declared,
+                available,
+                ..
+            } => write!(
+                f,
+                "truncated (declares {} data bytes, {} available)",
+                declared, available
+            ),
+            IssueKind::InvalidType(bytes) => {
+                write!(f, "invalid chunk type {:?}", String::from_utf8_lossy(bytes))
+            }
+        }
+    }
+}
+
+/// Parses a PNG, recording per-chunk problems instead of failing on the first one.
+///
+/// Chunks with a bad CRC are kept with their data; since chunks are rebuilt with
+/// [`Chunk::new`], the resulting `Png` serializes with correct CRCs. Chunks with an
+/// invalid type are skipped, and parsing stops at the first truncated chunk, whose
+/// partial data is kept only for IDAT. Well-formed chunks after IEND are kept as
+/// they are by [`Png::try_from`]. Only a wrong signature is a hard error.
+pub fn parse_lenient(bytes: &[u8]) -> Result<(Png, Vec<ParseIssue>), String> {
+    if bytes.len() < 8 || bytes[..8] != Png::STANDARD_HEADER {
+        return Err("Invalid PNG header".to_string());
+    }
+
+    let crc_calculator = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+    let mut chunks = Vec::new();
+    let mut issues = Vec::new();
+    let mut offset = 8;
+    let mut index = 0;
+
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        if rest.len() < 8 {
+            issues.push(ParseIssue {
+                index,
+                offset,
+                kind: IssueKind::Truncated {
+                    declared: 0,
+                    available: 0,
+                    kept: false,
+                },
+            });
+            offset = bytes.len();
+            break;
+        }
+
+        let declared = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]);
+        let type_bytes = [rest[4], rest[5], rest[6], rest[7]];
+        let chunk_type = ChunkType::try_from(type_bytes).ok();
+        // On 32-bit targets a huge declared length overflows; such a chunk
+        // cannot fit in the file either, so it counts as truncated.
+        let data_end = (declared as usize).checked_add(8).filter(|end| {
+            end.checked_add(4)
+                .is_some_and(|chunk_end| chunk_end <= rest.len())
+        });
+
+        let Some(data_end) = data_end else {
+            let available = rest.len().saturating_sub(8).min(declared as usize);
+            let idat_type = chunk_type.filter(|t| t.to_string() == "IDAT");
+            issues.push(ParseIssue {
+                index,
+                offset,
+                kind: IssueKind::Truncated {
+                    declared,
+                    available,
+                    kept: idat_type.is_some(),
+                },
+            });
+            if let Some(chunk_type) = idat_type {
+                chunks.push(Chunk::new(chunk_type, rest[8..8 + available].to_vec()));
+            }
+            offset = bytes.len();
+            break;
+        };
+
+        let Some(chunk_type) = chunk_type else {
+            issues.push(ParseIssue {
+                index,
+                offset,
+                kind: IssueKind::InvalidType(type_bytes),
+            });
+            offset += data_end + 4;
+            index += 1;
+            continue;
+        };
+
+        let data = &rest[8..data_end];
+        let stored = u32::from_be_bytes([
+            rest[data_end],
+            rest[data_end + 1],
+            rest[data_end + 2],
+            rest[data_end + 3],
+        ]);
+        let computed = crc_calculator.checksum(&rest[4..data_end]);
+        if stored != computed {
+            issues.push(ParseIssue {
+                index,
+                offset,
+                kind: IssueKind::BadCrc { stored, computed },
+            });
+        }
+
+        let is_iend = chunk_type.to_string() == "IEND";
+        chunks.push(Chunk::new(chunk_type, data.to_vec()));
+        offset += data_end + 4;
+        index += 1;
+
+        if is_iend {
+            break;
+        }
+    }
+
+    // Like the strict parser, keep well-formed chunks appended after IEND by
+    // earlier versions; whatever follows them is the trailer.
+    while let Ok(chunk) = Chunk::try_from(&bytes[offset..]) {
+        offset += 12 + chunk.length() as usize;
+        chunks.push(chunk);
+    }
+
+    let mut png = Png::from_chunks(chunks);
+    png.trailer = bytes[offset..].to_vec();
+    Ok((png, issues))
+}
+
+/// Repairs a damaged PNG and describes every change made.
+pub fn repair(bytes: &[u8]) -> Result<(Png, Vec<String>), String> {
+    let (mut png, issues) = parse_lenient(bytes)?;
+
+    let mut fixes: Vec<String> = issues
+        .iter()
+        .map(|issue| match &issue.kind {
+            IssueKind::BadCrc { stored, computed } => format!(
+                "Recomputed CRC of chunk {} ({:08x} -> {:08x})",
+                issue.index, stored, computed
+            ),
+            IssueKind::Truncated {
+                available,
+                kept: true,
+                ..
+            } => format!(
+                "Truncated IDAT chunk {} to its {} available bytes",
+                issue.index, available
+            ),
+            IssueKind::Truncated { kept: false, .. } => {
+                format!("Dropped truncated chunk {}", issue.index)
+            }
+            IssueKind::InvalidType(bytes) => format!(
+                "Dropped chunk {} with invalid type {:?}",
+                issue.index,
+                String::from_utf8_lossy(bytes)
+            ),
+        })
+        .collect();
+
+    let has_iend = png
+        .chunks()
+        .iter()
+        .any(|c| c.chunk_type().to_string() == "IEND");
+    if !has_iend {
+        let iend_type = ChunkType::try_from(*b"IEND").expect("IEND is a valid chunk type");
+        png.append_chunk(Chunk::new(iend_type, Vec::new()));
+        fixes.push("Added missing IEND chunk".to_string());
+    }
+
+    Ok((png, fixes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn png_bytes(chunks: &[Chunk]) -> Vec<u8> {
+        Png::STANDARD_HEADER
+            .iter()
+            .copied()
+            .chain(chunks.iter().flat_map(|c| c.as_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lenient_bad_crc() {
+        let mut bytes = png_bytes(&[chunk("ruSt", b"message"), chunk("IEND", b"")]);
+        // Corrupt the CRC of the first chunk.
+        bytes[8 + 8 + 7] ^= 0xff;
+
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+
+        let (png, issues) = parse_lenient(&bytes).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert!(matches!(issues[0].kind, IssueKind::BadCrc { .. }));
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "message"
+        );
+    }
+
+    #[test]
+    fn test_lenient_invalid_type() {
+        let mut bytes = png_bytes(&[chunk("ruSt", b"abc"), chunk("IEND", b"")]);
+        bytes[8 + 4] = b'1';
+
+        let (png, issues) = parse_lenient(&bytes).unwrap();
+        assert_eq!(issues[0].kind, IssueKind::InvalidType(*b"1uSt"));
+        assert_eq!(png.chunks().len(), 1);
+    }
+
+    #[test]
+    fn test_lenient_huge_length() {
+        let mut bytes = png_bytes(&[chunk("IHDR", &[0; 13]), chunk("IDAT", &[1; 4])]);
+        let length = 8 + 25;
+        bytes[length..length + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+
+        let (png, issues) = parse_lenient(&bytes).unwrap();
+        assert_eq!(
+            issues[0].kind,
+            IssueKind::Truncated {
+                declared: u32::MAX,
+                available: 8,
+                kept: true,
+            }
+        );
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().length(), 8);
+    }
+
+    #[test]
+    fn test_lenient_keeps_chunks_after_iend() {
+        let mut bytes = png_bytes(&[
+            chunk("IHDR", &[0; 13]),
+            chunk("IEND", b""),
+            chunk("ruSt", b"legacy"),
+        ]);
+        bytes.extend_from_slice(b"tail");
+
+        let strict = Png::try_from(bytes.as_ref()).unwrap();
+        let (png, issues) = parse_lenient(&bytes).unwrap();
+        assert!(issues.is_empty());
+        assert_eq!(png.as_bytes(), strict.as_bytes());
+        assert_eq!(
+            png.chunk_by_type("ruSt").unwrap().data_as_string().unwrap(),
+            "legacy"
+        );
+        assert_eq!(png.trailer(), b"tail");
+
+        let (_, fixes) = repair(&bytes).unwrap();
+        assert!(fixes.is_empty());
+    }
+
+    #[test]
+    fn test_repair_truncated_idat() {
+        let mut bytes = png_bytes(&[chunk("IHDR", &[0; 13]), chunk("IDAT", &[1; 20])]);
+        bytes.truncate(bytes.len() - 10);
+
+        let (png, fixes) = repair(&bytes).unwrap();
+
+        assert_eq!(png.chunk_by_type("IDAT").unwrap().length(), 14);
+        assert_eq!(
+            png.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+        assert_eq!(fixes.len(), 2);
+        assert!(Png::try_from(png.as_bytes().as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_repair_clean_file() {
+        let bytes = png_bytes(&[chunk("IHDR", &[0; 13]), chunk("IEND", b"")]);
+        let (png, fixes) = repair(&bytes).unwrap();
+        assert!(fixes.is_empty());
+        assert_eq!(png.as_bytes(), bytes);
+    }
+}
@@ -0,0 +1,222 @@
+//! Serialize and Deserialize for [`ChunkType`], [`Chunk`] and [`Png`], enabled by
+//! the `serde` feature.
+//!
+//! A chunk type is its four-letter string. Chunk data and the trailer after IEND
+//! are base64 strings in human-readable formats such as JSON and raw bytes in
+//! binary formats. Deserializing checks each chunk's CRC, so a value read back
+//! gives the same bytes from `as_bytes` as the one that was written.
+
+use std::fmt;
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+impl Serialize for ChunkType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ChunkType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        ChunkType::from_str(&text).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize)]
+struct ChunkRef<'a> {
+    #[serde(rename = "type")]
+    chunk_type: &'a ChunkType,
+    #[serde(serialize_with = "serialize_data")]
+    data: &'a [u8],
+    crc: u32,
+}
+
+#[derive(Deserialize)]
+struct ChunkRepr {
+    #[serde(rename = "type")]
+    chunk_type: ChunkType,
+    #[serde(deserialize_with = "deserialize_data")]
+    data: Vec<u8>,
+    crc: u32,
+}
+
+impl Serialize for Chunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ChunkRef {
+            chunk_type: self.chunk_type(),
+            data: self.data(),
+            crc: self.crc(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Chunk {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = ChunkRepr::deserialize(deserializer)?;
+        let chunk = Chunk::new(repr.chunk_type, repr.data);
+        if chunk.crc() != repr.crc {
+            return Err(de::Error::custom(format!(
+                "CRC mismatch in {} chunk: expected {:#010x}, got {:#010x}",
+                chunk.chunk_type(),
+                chunk.crc(),
+                repr.crc
+            )));
+        }
+        Ok(chunk)
+    }
+}
+
+#[derive(Serialize)]
+struct PngRef<'a> {
+    chunks: &'a [Chunk],
+    #[serde(serialize_with = "serialize_data")]
+    trailer: &'a [u8],
+}
+
+#[derive(Deserialize)]
+struct PngRepr {
+    chunks: Vec<Chunk>,
+    #[serde(default, deserialize_with = "deserialize_data")]
+    trailer: Vec<u8>,
+}
+
+impl Serialize for Png {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PngRef {
+            chunks: &self.chunks,
+            trailer: &self.trailer,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Png {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = PngRepr::deserialize(deserializer)?;
+        let mut png = Png::from_chunks(repr.chunks);
+        png.trailer = repr.trailer;
+        Ok(png)
+    }
+}
+
+fn serialize_data<S: Serializer>(data: &&[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&STANDARD.encode(data))
+    } else {
+        serializer.serialize_bytes(data)
+    }
+}
+
+fn deserialize_data<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(DataVisitor)
+    } else {
+        deserializer.deserialize_byte_buf(DataVisitor)
+    }
+}
+
+/// Accepts chunk data as base64 text, raw bytes or a sequence of byte values.
+struct DataVisitor;
+
+impl<'de> Visitor<'de> for DataVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "base64 text, bytes or a list of byte values")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        STANDARD
+            .decode(v)
+            .map_err(|e| E::custom(format!("Invalid base64 data: {}", e)))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut data = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            data.push(byte);
+        }
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_png() -> Png {
+        let chunk = |chunk_type: &str, data: &[u8]| {
+            Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+        };
+        let mut png = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk(
+                "IDAT",
+                &[0x78, 0x9c, 0x63, 0x60, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01],
+            ),
+            chunk("ruSt", b"This is where your secret message will be!"),
+            chunk("IEND", &[]),
+        ]);
+        png.trailer = vec![0, 255, 13, 10];
+        png
+    }
+
+    #[test]
+    fn test_chunk_type_as_string() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        assert_eq!(serde_json::to_string(&chunk_type).unwrap(), "\"ruSt\"");
+        let parsed: ChunkType = serde_json::from_str("\"ruSt\"").unwrap();
+        assert_eq!(parsed, chunk_type);
+        assert!(serde_json::from_str::<ChunkType>("\"ru5t\"").is_err());
+    }
+
+    #[test]
+    fn test_chunk_json() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"hi".to_vec());
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(json["type"], "ruSt");
+        assert_eq!(json["data"], "aGk=");
+        assert_eq!(json["crc"], chunk.crc());
+
+        let mut tampered = json.clone();
+        tampered["crc"] = serde_json::Value::from(0);
+        assert!(serde_json::from_value::<Chunk>(tampered).is_err());
+
+        let mut as_numbers = json;
+        as_numbers["data"] = serde_json::json!([104, 105]);
+        let parsed: Chunk = serde_json::from_value(as_numbers).unwrap();
+        assert_eq!(parsed.as_bytes(), chunk.as_bytes());
+    }
+
+    #[test]
+    fn test_png_round_trip_is_byte_exact() {
+        let png = testing_png();
+        let json = serde_json::to_string(&png).unwrap();
+        let parsed: Png = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.as_bytes(), png.as_bytes());
+
+        let packed = rmp_serde::to_vec(&png).unwrap();
+        let parsed: Png = rmp_serde::from_slice(&packed).unwrap();
+        assert_eq!(parsed.as_bytes(), png.as_bytes());
+
+        let parsed: Png = serde_json::from_str(r#"{"chunks":[]}"#).unwrap();
+        assert!(parsed.trailer.is_empty());
+    }
+}
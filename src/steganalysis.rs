@@ -0,0 +1,473 @@
+use std::fmt;
+
+use serde_json::{json, Value};
+
+use crate::ihdr::ColorType;
+use crate::pixels;
+use crate::png::Png;
+
+/// Number of equal steps in which [`sequential_fraction`] grows the sample prefix.
+const SEQUENTIAL_STEPS: usize = 100;
+
+/// Pairs of values expected fewer times than this are left out of the chi-square
+/// sum, where they would only add noise.
+const MIN_EXPECTED: f64 = 5.0;
+
+/// LSB steganalysis results for one color channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChannelAnalysis {
+    pub channel: &'static str,
+    pub samples: usize,
+    /// Chi-square statistic over the pairs of values 2k and 2k+1.
+    pub chi_square: f64,
+    /// Probability that the LSBs of the whole channel carry embedded data.
+    pub embedding_probability: f64,
+    /// Share of the channel, from its start, in which the chi-square attack
+    /// detects embedding. Catches messages written sequentially.
+    pub sequential_fraction: f64,
+    /// Share of LSBs carrying a message according to RS analysis, or `None`
+    /// when the image gives no usable estimate.
+    pub rs_fraction: Option<f64>,
+}
+
+/// Statistical analysis of the least significant bits of every color channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PixelAnalysis {
+    pub channels: Vec<ChannelAnalysis>,
+}
+
+impl PixelAnalysis {
+    /// Analyzes an 8-bit grayscale or truecolor image. Alpha is skipped, since
+    /// hiding data in it shows up in fully transparent or opaque areas.
+    pub fn for_png(png: &Png) -> Result<Self, String> {
+        let ihdr = png.ihdr()?;
+        let names: &[&'static str] = match ihdr.color_type {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => &["gray"],
+            ColorType::Rgb | ColorType::Rgba => &["red", "green", "blue"],
+            ColorType::Indexed => {
+                return Err("Pixel analysis needs a grayscale or truecolor image".to_string())
+            }
+        };
+        if ihdr.bit_depth != 8 {
+            return Err(format!(
+                "Pixel analysis needs 8 bits per sample, found {}",
+                ihdr.bit_depth
+            ));
+        }
+
+        let raw = pixels::decode(&ihdr, &png.image_data())?;
+        let stride = ihdr.color_type.channels() as usize;
+        let width = ihdr.width as usize;
+        let channels = names
+            .iter()
+            .enumerate()
+            .map(|(index, &channel)| {
+                let samples: Vec<u8> = raw.iter().skip(index).step_by(stride).copied().collect();
+                let (chi_square, embedding_probability) = chi_square(&samples);
+                ChannelAnalysis {
+                    channel,
+                    samples: samples.len(),
+                    chi_square,
+                    embedding_probability,
+                    sequential_fraction: sequential_fraction(&samples),
+                    rs_fraction: rs_fraction(&samples, width),
+                }
+            })
+            .collect();
+        Ok(PixelAnalysis { channels })
+    }
+
+    /// Average RS estimate over the channels that have one.
+    pub fn estimated_fraction(&self) -> Option<f64> {
+        let estimates: Vec<f64> = self.channels.iter().filter_map(|c| c.rs_fraction).collect();
+        if estimates.is_empty() {
+            return None;
+        }
+        Some(estimates.iter().sum::<f64>() / estimates.len() as f64)
+    }
+
+    /// Estimated message size in bytes, assuming one bit per modified sample.
+    pub fn estimated_bytes(&self) -> u64 {
+        self.channels
+            .iter()
+            .map(|c| (c.rs_fraction.unwrap_or(0.0) * c.samples as f64 / 8.0) as u64)
+            .sum()
+    }
+
+    pub fn to_value(&self) -> Value {
+        let channels: Vec<Value> = self
+            .channels
+            .iter()
+            .map(|c| {
+                json!({
+                    "channel": c.channel,
+                    "samples": c.samples,
+                    "chi_square": c.chi_square,
+                    "embedding_probability": c.embedding_probability,
+                    "sequential_fraction": c.sequential_fraction,
+                    "rs_fraction": c.rs_fraction,
+                })
+            })
+            .collect();
+        json!({
+            "channels": channels,
+            "estimated_fraction": self.estimated_fraction(),
+            "estimated_bytes": self.estimated_bytes(),
+        })
+    }
+
+    pub fn to_json(&self) -> String {
+        self.to_value().to_string()
+    }
+}
+
+impl fmt::Display for PixelAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<7} {:>12} {:>12} {:>11} {:>8}",
+            "Channel", "Chi-square", "Probability", "Sequential", "RS"
+        )?;
+        for c in &self.channels {
+            let rs = c
+                .rs_fraction
+                .map_or("n/a".to_string(), |r| format!("{:.1}%", r * 100.0));
+            writeln!(
+                f,
+                "{:<7} {:>12.1} {:>12.4} {:>10.0}% {:>8}",
+                c.channel,
+                c.chi_square,
+                c.embedding_probability,
+                c.sequential_fraction * 100.0,
+                rs
+            )?;
+        }
+        match self.estimated_fraction() {
+            Some(fraction) => writeln!(
+                f,
+                "Estimated payload: {:.1}% of LSBs (about {} bytes)",
+                fraction * 100.0,
+                self.estimated_bytes()
+            ),
+            None => writeln!(f, "Estimated payload: n/a"),
+        }
+    }
+}
+
+/// Westfeld and Pfitzmann's chi-square attack. Embedding random bits in the
+/// LSBs evens out how often the values 2k and 2k+1 occur; the returned
+/// probability approaches 1 when the counts are as even as embedding makes them.
+pub fn chi_square(samples: &[u8]) -> (f64, f64) {
+    let mut counts = [0usize; 256];
+    for &sample in samples {
+        counts[sample as usize] += 1;
+    }
+    chi_square_of(&counts)
+}
+
+fn chi_square_of(counts: &[usize; 256]) -> (f64, f64) {
+    let mut statistic = 0.0;
+    let mut categories = 0;
+    for pair in counts.chunks_exact(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        if expected < MIN_EXPECTED {
+            continue;
+        }
+        statistic += (pair[0] as f64 - expected).powi(2) / expected;
+        categories += 1;
+    }
+    if categories < 2 {
+        return (statistic, 0.0);
+    }
+    let degrees = (categories - 1) as f64;
+    (
+        statistic,
+        1.0 - regularized_gamma(degrees / 2.0, statistic / 2.0),
+    )
+}
+
+/// Runs the chi-square attack on growing prefixes of `samples` and returns the
+/// share of the samples, from the start, over which it keeps detecting embedding.
+pub fn sequential_fraction(samples: &[u8]) -> f64 {
+    let step = samples.len() / SEQUENTIAL_STEPS;
+    if step == 0 {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    let mut detected = 0;
+    for (i, prefix) in samples.chunks(step).take(SEQUENTIAL_STEPS).enumerate() {
+        for &sample in prefix {
+            counts[sample as usize] += 1;
+        }
+        if chi_square_of(&counts).1 <= 0.5 {
+            break;
+        }
+        detected = i + 1;
+    }
+    detected as f64 / SEQUENTIAL_STEPS as f64
+}
+
+/// Fridrich's RS analysis over groups of four horizontally adjacent samples.
+///
+/// Flipping LSBs makes smooth groups noisier ("regular") as often as it makes
+/// them smoother ("singular") only for LSBs that already carry random data,
+/// while shifted flipping (-1 to 0, 1 to 2, ...) is unaffected. Comparing both
+/// for the image and for the image with every LSB flipped gives the share of
+/// samples whose LSBs were replaced.
+pub fn rs_fraction(samples: &[u8], width: usize) -> Option<f64> {
+    if width < 4 {
+        return None;
+    }
+    let flipped: Vec<u8> = samples.iter().map(|s| s ^ 1).collect();
+    let (r, s, r_neg, s_neg) = rs_counts(samples, width)?;
+    let (r1, s1, r1_neg, s1_neg) = rs_counts(&flipped, width)?;
+
+    let d0 = r - s;
+    let d1 = r1 - s1;
+    let d0_neg = r_neg - s_neg;
+    let d1_neg = r1_neg - s1_neg;
+
+    let a = 2.0 * (d1 + d0);
+    let b = d0_neg - d1_neg - d1 - 3.0 * d0;
+    let c = d0 - d0_neg;
+    let x = if a.abs() < 1e-12 {
+        if b.abs() < 1e-12 {
+            return None;
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        let root = discriminant.sqrt();
+        let (x1, x2) = ((-b + root) / (2.0 * a), (-b - root) / (2.0 * a));
+        if x1.abs() < x2.abs() {
+            x1
+        } else {
+            x2
+        }
+    };
+    if (x - 0.5).abs() < 1e-12 {
+        return None;
+    }
+    Some((x / (x - 0.5)).clamp(0.0, 1.0))
+}
+
+/// Shares of regular and singular groups under the mask 0110 and its negation.
+fn rs_counts(samples: &[u8], width: usize) -> Option<(f64, f64, f64, f64)> {
+    const MASK: [i16; 4] = [0, 1, 1, 0];
+    let (mut regular, mut singular, mut regular_neg, mut singular_neg) = (0, 0, 0, 0);
+    let mut groups = 0;
+
+    for row in samples.chunks(width) {
+        for group in row.chunks_exact(4) {
+            let values = [
+                group[0] as i16,
+                group[1] as i16,
+                group[2] as i16,
+                group[3] as i16,
+            ];
+            let original = smoothness(&values);
+            let positive = smoothness(&apply_mask(&values, MASK));
+            let negative = smoothness(&apply_mask(&values, MASK.map(|m| -m)));
+            match positive.cmp(&original) {
+                std::cmp::Ordering::Greater => regular += 1,
+                std::cmp::Ordering::Less => singular += 1,
+                std::cmp::Ordering::Equal => {}
+            }
+            match negative.cmp(&original) {
+                std::cmp::Ordering::Greater => regular_neg += 1,
+                std::cmp::Ordering::Less => singular_neg += 1,
+                std::cmp::Ordering::Equal => {}
+            }
+            groups += 1;
+        }
+    }
+    if groups == 0 {
+        return None;
+    }
+    let share = |n: usize| n as f64 / groups as f64;
+    Some((
+        share(regular),
+        share(singular),
+        share(regular_neg),
+        share(singular_neg),
+    ))
+}
+
+fn smoothness(values: &[i16; 4]) -> i16 {
+    values.windows(2).map(|w| (w[1] - w[0]).abs()).sum()
+}
+
+fn apply_mask(values: &[i16; 4], mask: [i16; 4]) -> [i16; 4] {
+    let mut masked = *values;
+    for (value, m) in masked.iter_mut().zip(mask) {
+        *value = match m {
+            1 => *value ^ 1,
+            -1 => ((*value + 1) ^ 1) - 1,
+            _ => *value,
+        };
+    }
+    masked
+}
+
+/// Regularized lower incomplete gamma function P(a, x).
+fn regularized_gamma(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let log_prefix = a * x.ln() - x - ln_gamma(a);
+    if x < a + 1.0 {
+        // Series expansion.
+        let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (sum * log_prefix.exp()).min(1.0)
+    } else {
+        // Continued fraction for the upper function Q(a, x), by Lentz's method.
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (1.0 - log_prefix.exp() * h).max(0.0)
+    }
+}
+
+/// Natural logarithm of the gamma function, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, c) in COEFFICIENTS.iter().enumerate() {
+        series += c / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WIDTH: usize = 256;
+    const HEIGHT: usize = 256;
+
+    struct Random(u64);
+
+    impl Random {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    /// A smooth gradient with mild noise. With `comb`, even values are more
+    /// common than odd ones, like the histogram combs that editing leaves.
+    fn cover(comb: bool) -> Vec<u8> {
+        let mut random = Random(0x9e37_79b9_7f4a_7c15);
+        let mut samples = Vec::with_capacity(WIDTH * HEIGHT);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let base =
+                    60.0 + 40.0 * ((x as f64) / 23.0).sin() + 50.0 * ((y as f64) / 37.0).cos();
+                let noise = (random.next() % 5) as f64 - 2.0;
+                let sample = (base + noise).clamp(0.0, 255.0) as u8;
+                samples.push(if comb && random.next().is_multiple_of(4) {
+                    sample & !1
+                } else {
+                    sample
+                });
+            }
+        }
+        samples
+    }
+
+    /// Replaces the LSB of the given share of samples with random bits.
+    fn embed(samples: &[u8], fraction: f64, sequential: bool) -> Vec<u8> {
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        let limit = (samples.len() as f64 * fraction) as usize;
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| {
+                let chosen = if sequential {
+                    i < limit
+                } else {
+                    (random.next() % 1000) as f64 / 1000.0 < fraction
+                };
+                let bit = (random.next() >> 32) as u8 & 1;
+                if chosen {
+                    (s & !1) | bit
+                } else {
+                    s
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_regularized_gamma() {
+        // P(1, x) = 1 - e^-x
+        assert!((regularized_gamma(1.0, 2.0) - (1.0 - (-2.0f64).exp())).abs() < 1e-10);
+        assert!((regularized_gamma(5.0, 30.0) - 1.0).abs() < 1e-8);
+        assert!((regularized_gamma(3.0, 1.0) - 0.080_301_397_071_394_2).abs() < 1e-10);
+        assert!((ln_gamma(5.0) - 24.0f64.ln()).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_chi_square() {
+        let cover = cover(true);
+        assert!(chi_square(&cover).1 < 0.05);
+        assert!(chi_square(&embed(&cover, 1.0, false)).1 > 0.5);
+
+        let half = embed(&cover, 0.5, true);
+        let fraction = sequential_fraction(&half);
+        assert!((0.35..=0.55).contains(&fraction), "{}", fraction);
+        assert_eq!(sequential_fraction(&cover), 0.0);
+    }
+
+    #[test]
+    fn test_rs_fraction() {
+        let cover = cover(false);
+        let clean = rs_fraction(&cover, WIDTH).unwrap();
+        assert!(clean < 0.1, "{}", clean);
+
+        let stego = rs_fraction(&embed(&cover, 0.6, false), WIDTH).unwrap();
+        assert!((0.45..=0.75).contains(&stego), "{}", stego);
+        assert_eq!(rs_fraction(&cover, 3), None);
+    }
+}
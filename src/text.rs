@@ -0,0 +1,227 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::zlib;
+
+/// Largest decompressed text accepted from a zTXt or iTXt chunk.
+pub const MAX_INFLATED_LENGTH: usize = 16 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    /// tEXt: Latin-1 text, uncompressed.
+    Text,
+    /// zTXt: Latin-1 text, zlib-compressed.
+    Compressed,
+    /// iTXt: UTF-8 text with language tag, optionally compressed.
+    International,
+}
+
+impl TextKind {
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            TextKind::Text => "tEXt",
+            TextKind::Compressed => "zTXt",
+            TextKind::International => "iTXt",
+        }
+    }
+
+    pub fn from_chunk_type(chunk_type: &ChunkType) -> Option<Self> {
+        match &chunk_type.bytes() {
+            b"tEXt" => Some(TextKind::Text),
+            b"zTXt" => Some(TextKind::Compressed),
+            b"iTXt" => Some(TextKind::International),
+            _ => None,
+        }
+    }
+}
+
+/// Decoded contents of a tEXt, zTXt or iTXt chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextChunk {
+    pub kind: TextKind,
+    pub keyword: String,
+    pub text: String,
+    /// iTXt only: whether the text is stored compressed.
+    pub compressed: bool,
+    /// iTXt only: RFC 3066 language tag.
+    pub language: String,
+    /// iTXt only: keyword translated into `language`.
+    pub translated_keyword: String,
+}
+
+impl TextChunk {
+    pub fn new(kind: TextKind, keyword: &str, text: &str) -> Self {
+        TextChunk {
+            kind,
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            compressed: false,
+            language: String::new(),
+            translated_keyword: String::new(),
+        }
+    }
+
+    pub fn to_chunk(&self) -> Result<Chunk, String> {
+        if self.keyword.is_empty() || self.keyword.len() > 79 {
+            return Err("Text keyword must be 1 to 79 bytes long".to_string());
+        }
+
+        let mut data = Vec::new();
+        match self.kind {
+            TextKind::Text => {
+                data.extend(latin1_encode(&self.keyword)?);
+                data.push(0);
+                data.extend(latin1_encode(&self.text)?);
+            }
+            TextKind::Compressed => {
+                data.extend(latin1_encode(&self.keyword)?);
+                data.extend_from_slice(&[0, 0]);
+                data.extend(deflate(&latin1_encode(&self.text)?)?);
+            }
+            TextKind::International => {
+                data.extend(latin1_encode(&self.keyword)?);
+                data.extend_from_slice(&[0, self.compressed as u8, 0]);
+                data.extend_from_slice(self.language.as_bytes());
+                data.push(0);
+                data.extend_from_slice(self.translated_keyword.as_bytes());
+                data.push(0);
+                if self.compressed {
+                    data.extend(deflate(self.text.as_bytes())?);
+                } else {
+                    data.extend_from_slice(self.text.as_bytes());
+                }
+            }
+        }
+
+        let chunk_type = ChunkType::from_str(self.kind.chunk_type())?;
+        Ok(Chunk::new(chunk_type, data))
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = String;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        let kind = TextKind::from_chunk_type(value.chunk_type())
+            .ok_or_else(|| format!("{} is not a text chunk", value.chunk_type()))?;
+        let (keyword, rest) = split_null(value.data())?;
+        let keyword = latin1_decode(keyword);
+
+        match kind {
+            TextKind::Text => Ok(TextChunk {
+                text: latin1_decode(rest),
+                ..TextChunk::new(kind, &keyword, "")
+            }),
+            TextKind::Compressed => {
+                let (&method, compressed) = rest
+                    .split_first()
+                    .ok_or_else(|| "zTXt chunk is missing its compression method".to_string())?;
+                if method != 0 {
+                    return Err(format!("Unknown zTXt compression method: {}", method));
+                }
+                Ok(TextChunk {
+                    text: latin1_decode(&inflate(compressed)?),
+                    ..TextChunk::new(kind, &keyword, "")
+                })
+            }
+            TextKind::International => {
+                if rest.len() < 2 {
+                    return Err("iTXt chunk is missing its compression fields".to_string());
+                }
+                let compressed = rest[0] != 0;
+                let (language, rest) = split_null(&rest[2..])?;
+                let (translated_keyword, text) = split_null(rest)?;
+                let text = if compressed {
+                    inflate(text)?
+                } else {
+                    text.to_vec()
+                };
+                Ok(TextChunk {
+                    kind,
+                    keyword,
+                    text: String::from_utf8(text).map_err(|e| e.to_string())?,
+                    compressed,
+                    language: String::from_utf8_lossy(language).into_owned(),
+                    translated_keyword: String::from_utf8_lossy(translated_keyword).into_owned(),
+                })
+            }
+        }
+    }
+}
+
+fn split_null(data: &[u8]) -> Result<(&[u8], &[u8]), String> {
+    let pos = data
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| "Text chunk is missing a null separator".to_string())?;
+    Ok((&data[..pos], &data[pos + 1..]))
+}
+
+fn latin1_decode(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn latin1_encode(text: &str) -> Result<Vec<u8>, String> {
+    text.chars()
+        .map(|c| u8::try_from(c).map_err(|_| format!("Character {:?} is not Latin-1", c)))
+        .collect()
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    zlib::inflate(data, MAX_INFLATED_LENGTH).map_err(|e| format!("Failed to inflate text: {}", e))
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| format!("Failed to deflate text: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = TextChunk::new(TextKind::Text, "Comment", "caf\u{e9}");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.data(), b"Comment\0caf\xe9");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let text = TextChunk::new(TextKind::Compressed, "Description", "a long text");
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "zTXt");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_round_trip() {
+        let mut text = TextChunk::new(TextKind::International, "Title", "\u{65e5}\u{672c}");
+        text.compressed = true;
+        text.language = "ja".to_string();
+        text.translated_keyword = "\u{984c}".to_string();
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_not_latin1() {
+        let text = TextChunk::new(TextKind::Text, "Title", "\u{65e5}");
+        assert!(text.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_not_a_text_chunk() {
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"abc".to_vec());
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::png::Png;
+
+/// Marks the start of a payload record in the data after IEND.
+pub const MAGIC: &[u8; 6] = b"PNGme\0";
+
+/// Appends `payload` after IEND as `MAGIC | length (u32, big-endian) | payload`.
+///
+/// Existing trailing data is kept in front of the record, so polyglot files stay intact.
+/// A previous record written by this function is replaced.
+pub fn embed(png: &mut Png, payload: &[u8]) -> Result<(), String> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| "Payload is too large for a trailer record".to_string())?;
+
+    if let Some(start) = find_record(&png.trailer) {
+        png.trailer.truncate(start);
+    }
+    png.trailer.extend_from_slice(MAGIC);
+    png.trailer.extend_from_slice(&length.to_be_bytes());
+    png.trailer.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Returns the payload of the last trailer record written by [`embed`].
+pub fn extract(png: &Png) -> Result<Vec<u8>, String> {
+    let trailer = png.trailer();
+    let start = find_record(trailer).ok_or_else(|| "No payload found after IEND".to_string())?;
+
+    let header_end = start + MAGIC.len() + 4;
+    if trailer.len() < header_end {
+        return Err("Trailer record is truncated".to_string());
+    }
+    let length = u32::from_be_bytes(
+        trailer[start + MAGIC.len()..header_end]
+            .try_into()
+            .map_err(|_| "Failed to parse trailer length")?,
+    ) as usize;
+
+    trailer
+        .get(header_end..header_end + length)
+        .map(|payload| payload.to_vec())
+        .ok_or_else(|| "Trailer record is truncated".to_string())
+}
+
+fn find_record(trailer: &[u8]) -> Option<usize> {
+    trailer.windows(MAGIC.len()).rposition(|w| w == MAGIC)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png_with_trailer(trailer: &[u8]) -> Png {
+        let mut png = Png::from_chunks(Vec::new());
+        png.trailer = trailer.to_vec();
+        png
+    }
+
+    #[test]
+    fn test_embed_extract() {
+        let mut png = png_with_trailer(b"");
+        embed(&mut png, b"after the end").unwrap();
+        assert_eq!(extract(&png).unwrap(), b"after the end");
+    }
+
+    #[test]
+    fn test_embed_keeps_existing_trailer() {
+        let mut png = png_with_trailer(b"PK\x03\x04zip");
+        embed(&mut png, b"first").unwrap();
+        embed(&mut png, b"second").unwrap();
+
+        assert!(png.trailer().starts_with(b"PK\x03\x04zip"));
+        assert_eq!(extract(&png).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_extract_without_record() {
+        let png = png_with_trailer(b"unrelated bytes");
+        assert!(extract(&png).is_err());
+    }
+
+    #[test]
+    fn test_extract_truncated_record() {
+        let mut png = png_with_trailer(b"");
+        embed(&mut png, b"payload").unwrap();
+        png.trailer.truncate(png.trailer.len() - 2);
+        assert!(extract(&png).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use crate::apng;
+use crate::codec::CodecRegistry;
+use crate::color::{Chromaticities, Gamma};
+use crate::png::Png;
+
+/// Allowed deviation from the sRGB gAMA and cHRM values, in units of 1/100000.
+const SRGB_TOLERANCE: u32 = 1000;
+
+/// Checks a parsed PNG against the structural rules of the specification and
+/// returns a description of every problem found.
+pub fn validate(png: &Png) -> Vec<String> {
+    validate_with(png, &CodecRegistry::standard())
+}
+
+/// Like [`validate`], but decodes chunk data with the codecs in `registry`.
+pub fn validate_with(png: &Png, registry: &CodecRegistry) -> Vec<String> {
+    let mut problems = Vec::new();
+    let chunks = png.chunks();
+
+    match chunks.first() {
+        Some(first) if first.chunk_type().to_string() == "IHDR" => {
+            if let Err(e) = png.ihdr() {
+                problems.push(e);
+            }
+        }
+        _ => problems.push("IHDR is not the first chunk".to_string()),
+    }
+    if chunks
+        .last()
+        .is_none_or(|last| last.chunk_type().to_string() != "IEND")
+    {
+        problems.push("IEND is not the last chunk".to_string());
+    }
+    if png.chunk_by_type("IDAT").is_none() {
+        problems.push("No IDAT chunk".to_string());
+    }
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for chunk in chunks {
+        let chunk_type = chunk.chunk_type();
+        if !chunk_type.is_valid() {
+            problems.push(format!(
+                "Chunk type '{}' has the reserved bit set",
+                chunk_type
+            ));
+        }
+        if chunk_type.is_unique() {
+            *counts.entry(chunk_type.to_string()).or_default() += 1;
+        }
+    }
+    let mut duplicates: Vec<_> = counts.into_iter().filter(|(_, n)| *n > 1).collect();
+    duplicates.sort();
+    for (chunk_type, count) in duplicates {
+        problems.push(format!(
+            "'{}' appears {} times but is allowed only once",
+            chunk_type, count
+        ));
+    }
+
+    for chunk in chunks {
+        problems.extend(registry.validate_in(chunk, png));
+    }
+
+    problems.extend(apng::sequence_problems(png));
+    problems
+}
+
+/// Reports color metadata that is valid on its own but inconsistent, which makes
+/// viewers disagree on how the image should look.
+pub fn warnings(png: &Png) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let has_srgb = png.chunk_by_type("sRGB").is_some();
+
+    if has_srgb && png.chunk_by_type("iCCP").is_some() {
+        warnings.push("Both sRGB and iCCP are present; only one should be used".to_string());
+    }
+    if has_srgb {
+        if let Ok(Some(gamma)) = png.gamma() {
+            if gamma.0.abs_diff(Gamma::SRGB.0) > SRGB_TOLERANCE {
+                warnings.push(format!(
+                    "gAMA {} conflicts with sRGB, which implies {}",
+                    gamma,
+                    Gamma::SRGB
+                ));
+            }
+        }
+        if let Ok(Some(chrm)) = png.chromaticities() {
+            if !chrm.approx_eq(&Chromaticities::SRGB, SRGB_TOLERANCE) {
+                warnings.push("cHRM conflicts with the sRGB primaries".to_string());
+            }
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::ihdr::{ColorType, Ihdr};
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: Vec<u8>) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data)
+    }
+
+    fn ihdr() -> Chunk {
+        let ihdr = Ihdr {
+            width: 1,
+            height: 1,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            interlace: false,
+        };
+        chunk("IHDR", ihdr.as_bytes())
+    }
+
+    #[test]
+    fn test_valid_png() {
+        let png = Png::from_chunks(vec![ihdr(), chunk("IDAT", vec![]), chunk("IEND", vec![])]);
+        assert!(validate(&png).is_empty());
+    }
+
+    #[test]
+    fn test_structure_problems() {
+        let png = Png::from_chunks(vec![
+            chunk("gAMA", 45455u32.to_be_bytes().to_vec()),
+            ihdr(),
+            chunk("gAMA", 45455u32.to_be_bytes().to_vec()),
+        ]);
+        let problems = validate(&png);
+        assert_eq!(problems.len(), 4);
+        assert!(problems[3].contains("'gAMA' appears 2 times"));
+    }
+
+    #[test]
+    fn test_color_warnings() {
+        let srgb = || chunk("sRGB", vec![0]);
+        let png = Png::from_chunks(vec![
+            ihdr(),
+            srgb(),
+            chunk("gAMA", 45455u32.to_be_bytes().to_vec()),
+            chunk("IDAT", vec![]),
+            chunk("IEND", vec![]),
+        ]);
+        assert!(warnings(&png).is_empty());
+
+        let png = Png::from_chunks(vec![
+            ihdr(),
+            srgb(),
+            chunk("gAMA", 100000u32.to_be_bytes().to_vec()),
+            chunk("iCCP", b"p\0\0".to_vec()),
+            chunk("IDAT", vec![]),
+            chunk("IEND", vec![]),
+        ]);
+        let warnings = warnings(&png);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[1].contains("gAMA 1.00000 conflicts"));
+        // The truncated iCCP stream is an error, not a warning.
+        assert_eq!(validate(&png).len(), 1);
+    }
+
+    #[test]
+    fn test_apng_sequence_problems() {
+        let mut fctl = vec![0u8; 26];
+        fctl[7] = 1; // width
+        fctl[11] = 1; // height
+        let mut fdat = 3u32.to_be_bytes().to_vec();
+        fdat.push(0);
+
+        let png = Png::from_chunks(vec![
+            ihdr(),
+            chunk("acTL", [1u32.to_be_bytes(), 0u32.to_be_bytes()].concat()),
+            chunk("fcTL", fctl),
+            chunk("IDAT", vec![]),
+            chunk("fdAT", fdat),
+            chunk("IEND", vec![]),
+        ]);
+
+        let problems = validate(&png);
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("skips"));
+    }
+}
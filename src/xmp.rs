@@ -0,0 +1,444 @@
+use std::str::FromStr;
+
+use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer, XmlVersion};
+
+use crate::chunk::Chunk;
+use crate::text::{TextChunk, TextKind};
+
+/// iTXt keyword under which PNG files store their XMP packet.
+pub const KEYWORD: &str = "XML:com.adobe.xmp";
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const DC: &str = "http://purl.org/dc/elements/1.1/";
+const XMP_BASIC: &str = "http://ns.adobe.com/xap/1.0/";
+const XMP_RIGHTS: &str = "http://ns.adobe.com/xap/1.0/rights/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Container {
+    /// Plain text value.
+    Simple,
+    /// Ordered list (rdf:Seq), e.g. the authors in dc:creator.
+    Seq,
+    /// Language alternatives (rdf:Alt); the value is stored as the x-default item.
+    Alt,
+}
+
+/// Properties that can be read and set, with their namespace and value form.
+/// Prefixes are matched literally, so packets are expected to use the usual ones.
+const PROPERTIES: &[(&str, &str, Container)] = &[
+    ("dc:creator", DC, Container::Seq),
+    ("dc:rights", DC, Container::Alt),
+    ("dc:title", DC, Container::Alt),
+    ("dc:description", DC, Container::Alt),
+    ("xmp:CreatorTool", XMP_BASIC, Container::Simple),
+    ("xmpRights:Marked", XMP_RIGHTS, Container::Simple),
+    ("xmpRights:WebStatement", XMP_RIGHTS, Container::Simple),
+    ("xmpRights:UsageTerms", XMP_RIGHTS, Container::Alt),
+];
+
+fn property(name: &str) -> Result<(&'static str, &'static str, Container), String> {
+    PROPERTIES
+        .iter()
+        .find(|(property, _, _)| *property == name)
+        .copied()
+        .ok_or_else(|| {
+            let known: Vec<&str> = PROPERTIES.iter().map(|(name, _, _)| *name).collect();
+            format!(
+                "Unsupported XMP property: {} (supported: {})",
+                name,
+                known.join(", ")
+            )
+        })
+}
+
+pub fn property_names() -> impl Iterator<Item = &'static str> {
+    PROPERTIES.iter().map(|(name, _, _)| *name)
+}
+
+/// An XMP packet, checked to be well-formed XML containing an rdf:RDF element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Xmp {
+    packet: String,
+}
+
+impl Xmp {
+    /// An empty packet with a single rdf:Description.
+    pub fn new() -> Self {
+        let packet = format!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+             <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+             <rdf:RDF xmlns:rdf=\"{}\">\n  \
+             <rdf:Description rdf:about=\"\"/>\n \
+             </rdf:RDF>\n\
+             </x:xmpmeta>\n\
+             <?xpacket end=\"w\"?>",
+            RDF
+        );
+        Xmp { packet }
+    }
+
+    pub fn packet(&self) -> &str {
+        &self.packet
+    }
+
+    /// Returns the value of a property, taking the first item of lists and alternatives.
+    pub fn get(&self, name: &str) -> Result<Option<String>, String> {
+        let (name, _, container) = property(name)?;
+        let mut reader = Reader::from_str(&self.packet);
+        let mut depth = 0;
+        let mut description_depth = None;
+        let mut property_depth = None;
+        let mut collecting = false;
+        let mut value = String::new();
+
+        loop {
+            match reader.read_event().map_err(xml_error)? {
+                Event::Start(e) => {
+                    depth += 1;
+                    let tag = qname(&e);
+                    if tag == "rdf:Description" {
+                        if let Some(value) = attribute(&e, name)? {
+                            return Ok(Some(value));
+                        }
+                        description_depth = Some(depth);
+                    } else if property_depth.is_none()
+                        && tag == name
+                        && description_depth == Some(depth - 1)
+                    {
+                        property_depth = Some(depth);
+                        collecting = container == Container::Simple;
+                    } else if property_depth.is_some() && tag == "rdf:li" {
+                        collecting = true;
+                    }
+                }
+                Event::Empty(e) => {
+                    let tag = qname(&e);
+                    if tag == "rdf:Description" {
+                        if let Some(value) = attribute(&e, name)? {
+                            return Ok(Some(value));
+                        }
+                    } else if tag == name && description_depth == Some(depth) {
+                        return Ok(Some(String::new()));
+                    }
+                }
+                Event::Text(e) if collecting => value.push_str(&e.into_inner()),
+                Event::GeneralRef(e) if collecting => {
+                    let resolved = match e.resolve_char_ref().map_err(xml_error)? {
+                        Some(c) => c,
+                        None => match e.into_inner().as_ref() {
+                            "amp" => '&',
+                            "lt" => '<',
+                            "gt" => '>',
+                            "quot" => '"',
+                            "apos" => '\'',
+                            other => return Err(format!("Unknown XML entity: &{};", other)),
+                        },
+                    };
+                    value.push(resolved);
+                }
+                Event::End(e) => {
+                    let tag = e.name().0.to_string();
+                    if property_depth == Some(depth) || (collecting && tag == "rdf:li") {
+                        return Ok(Some(value.trim().to_string()));
+                    }
+                    if description_depth == Some(depth) {
+                        description_depth = None;
+                    }
+                    depth -= 1;
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+
+    /// Sets a property, replacing every existing occurrence of it. The new value is
+    /// added to the first rdf:Description, declaring its namespace there if needed.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let (name, namespace, container) = property(name)?;
+        let prefix = name.split_once(':').map(|(prefix, _)| prefix).unwrap();
+        let xmlns = format!("xmlns:{}", prefix);
+
+        let mut reader = Reader::from_str(&self.packet);
+        let mut writer = Writer::new(Vec::new());
+        let mut depth = 0;
+        // Namespace prefixes declared by each open element.
+        let mut declared: Vec<bool> = Vec::new();
+        let mut description_depth = None;
+        let mut target_depth = None;
+        let mut skip_depth = None;
+        let mut inserted = false;
+
+        loop {
+            let event = reader.read_event().map_err(xml_error)?;
+            if let Some(skip) = skip_depth {
+                match event {
+                    Event::Start(_) => depth += 1,
+                    Event::End(_) => {
+                        depth -= 1;
+                        if depth < skip {
+                            skip_depth = None;
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            match event {
+                Event::Start(e) if qname(&e) == "rdf:Description" => {
+                    depth += 1;
+                    let mut start = without_attribute(&e, name)?;
+                    let in_scope = declared.iter().any(|d| *d) || attribute(&e, &xmlns)?.is_some();
+                    if !inserted && target_depth.is_none() {
+                        if !in_scope {
+                            start.push_attribute((xmlns.as_str(), namespace));
+                        }
+                        target_depth = Some(depth);
+                    }
+                    description_depth = Some(depth);
+                    declared.push(in_scope);
+                    write(&mut writer, Event::Start(start))?;
+                }
+                Event::Empty(e) if qname(&e) == "rdf:Description" => {
+                    let mut start = without_attribute(&e, name)?;
+                    if inserted || target_depth.is_some() {
+                        write(&mut writer, Event::Empty(start))?;
+                        continue;
+                    }
+                    let in_scope = declared.iter().any(|d| *d) || attribute(&e, &xmlns)?.is_some();
+                    if !in_scope {
+                        start.push_attribute((xmlns.as_str(), namespace));
+                    }
+                    let end = start.to_end().into_owned();
+                    write(&mut writer, Event::Start(start))?;
+                    write_property(&mut writer, name, container, value)?;
+                    write(&mut writer, Event::End(end))?;
+                    inserted = true;
+                }
+                Event::Start(e) if qname(&e) == name && description_depth == Some(depth) => {
+                    depth += 1;
+                    skip_depth = Some(depth);
+                }
+                Event::Empty(e) if qname(&e) == name && description_depth == Some(depth) => {}
+                Event::Start(e) => {
+                    depth += 1;
+                    declared.push(attribute(&e, &xmlns)?.is_some());
+                    write(&mut writer, Event::Start(e))?;
+                }
+                Event::End(e) => {
+                    if target_depth == Some(depth) {
+                        write_property(&mut writer, name, container, value)?;
+                        target_depth = None;
+                        inserted = true;
+                    }
+                    if description_depth == Some(depth) {
+                        description_depth = None;
+                    }
+                    depth -= 1;
+                    declared.pop();
+                    write(&mut writer, Event::End(e))?;
+                }
+                Event::Eof => break,
+                event => write(&mut writer, event)?,
+            }
+        }
+
+        if !inserted {
+            return Err("XMP packet has no rdf:Description to hold the property".to_string());
+        }
+        let packet = String::from_utf8(writer.into_inner()).map_err(|e| e.to_string())?;
+        *self = Xmp::from_str(&packet)?;
+        Ok(())
+    }
+
+    /// Builds the uncompressed iTXt chunk that holds the packet.
+    pub fn to_chunk(&self) -> Result<Chunk, String> {
+        TextChunk::new(TextKind::International, KEYWORD, &self.packet).to_chunk()
+    }
+}
+
+impl Default for Xmp {
+    fn default() -> Self {
+        Xmp::new()
+    }
+}
+
+impl FromStr for Xmp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut reader = Reader::from_str(s);
+        let mut depth = 0usize;
+        let mut has_rdf = false;
+        loop {
+            match reader.read_event().map_err(xml_error)? {
+                Event::Start(e) => {
+                    has_rdf |= qname(&e) == "rdf:RDF";
+                    depth += 1;
+                }
+                Event::Empty(e) => has_rdf |= qname(&e) == "rdf:RDF",
+                Event::End(_) => depth -= 1,
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        if depth != 0 {
+            return Err("XMP packet has unclosed elements".to_string());
+        }
+        if !has_rdf {
+            return Err("XMP packet has no rdf:RDF element".to_string());
+        }
+        Ok(Xmp {
+            packet: s.to_string(),
+        })
+    }
+}
+
+impl TryFrom<&Chunk> for Xmp {
+    type Error = String;
+
+    fn try_from(value: &Chunk) -> Result<Self, Self::Error> {
+        let text = TextChunk::try_from(value)?;
+        if text.kind != TextKind::International || text.keyword != KEYWORD {
+            return Err(format!("Chunk is not an iTXt {} chunk", KEYWORD));
+        }
+        Xmp::from_str(&text.text)
+    }
+}
+
+fn xml_error(e: impl std::fmt::Display) -> String {
+    format!("Invalid XMP packet: {}", e)
+}
+
+fn qname(e: &BytesStart) -> String {
+    e.name().0.to_string()
+}
+
+fn attribute(e: &BytesStart, name: &str) -> Result<Option<String>, String> {
+    for attr in e.attributes() {
+        let attr = attr.map_err(xml_error)?;
+        if attr.key.0 == name {
+            return attr
+                .normalized_value(XmlVersion::Implicit1_0)
+                .map(|value| Some(value.into_owned()))
+                .map_err(xml_error);
+        }
+    }
+    Ok(None)
+}
+
+fn without_attribute(e: &BytesStart, name: &str) -> Result<BytesStart<'static>, String> {
+    let mut start = BytesStart::new(qname(e));
+    for attr in e.attributes() {
+        let attr = attr.map_err(xml_error)?;
+        if attr.key.0 != name {
+            start.push_attribute(attr);
+        }
+    }
+    Ok(start)
+}
+
+fn write(writer: &mut Writer<Vec<u8>>, event: Event) -> Result<(), String> {
+    writer.write_event(event).map_err(|e| e.to_string())
+}
+
+fn write_property(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    container: Container,
+    value: &str,
+) -> Result<(), String> {
+    write(writer, Event::Start(BytesStart::new(name)))?;
+    let list = match container {
+        Container::Simple => None,
+        Container::Seq => Some(("rdf:Seq", BytesStart::new("rdf:li"))),
+        Container::Alt => Some((
+            "rdf:Alt",
+            BytesStart::new("rdf:li").with_attributes([("xml:lang", "x-default")]),
+        )),
+    };
+    match list {
+        None => write(writer, Event::Text(BytesText::new(value)))?,
+        Some((list, item)) => {
+            write(writer, Event::Start(BytesStart::new(list)))?;
+            write(writer, Event::Start(item))?;
+            write(writer, Event::Text(BytesText::new(value)))?;
+            write(writer, Event::End(BytesEnd::new("rdf:li")))?;
+            write(writer, Event::End(BytesEnd::new(list)))?;
+        }
+    }
+    write(writer, Event::End(BytesEnd::new(name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:CreatorTool="Editor 1.0">
+  </rdf:Description>
+  <rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+   <dc:creator><rdf:Seq><rdf:li>Old Author</rdf:li><rdf:li>Second</rdf:li></rdf:Seq></dc:creator>
+   <dc:rights><rdf:Alt><rdf:li xml:lang="x-default">All rights &amp; reserved</rdf:li></rdf:Alt></dc:rights>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn test_get() {
+        let xmp = Xmp::from_str(PACKET).unwrap();
+        assert_eq!(xmp.get("dc:creator").unwrap().unwrap(), "Old Author");
+        assert_eq!(
+            xmp.get("dc:rights").unwrap().unwrap(),
+            "All rights & reserved"
+        );
+        assert_eq!(xmp.get("xmp:CreatorTool").unwrap().unwrap(), "Editor 1.0");
+        assert_eq!(xmp.get("dc:title").unwrap(), None);
+        assert!(xmp.get("dc:unknown").is_err());
+    }
+
+    #[test]
+    fn test_set_replaces_existing() {
+        let mut xmp = Xmp::from_str(PACKET).unwrap();
+        xmp.set("dc:creator", "Jane <Doe>").unwrap();
+        xmp.set("xmp:CreatorTool", "PNGme").unwrap();
+
+        assert_eq!(xmp.get("dc:creator").unwrap().unwrap(), "Jane <Doe>");
+        assert_eq!(xmp.get("xmp:CreatorTool").unwrap().unwrap(), "PNGme");
+        assert_eq!(xmp.packet().matches("dc:creator>").count(), 2);
+        assert!(!xmp.packet().contains("Old Author"));
+        assert!(!xmp.packet().contains("Second"));
+        assert!(xmp.packet().ends_with(r#"<?xpacket end="w"?>"#));
+    }
+
+    #[test]
+    fn test_set_on_new_packet() {
+        let mut xmp = Xmp::new();
+        xmp.set("dc:rights", "CC BY 4.0").unwrap();
+        xmp.set("dc:creator", "Jane Doe").unwrap();
+
+        assert_eq!(xmp.get("dc:rights").unwrap().unwrap(), "CC BY 4.0");
+        assert_eq!(xmp.get("dc:creator").unwrap().unwrap(), "Jane Doe");
+        assert_eq!(xmp.packet().matches("xmlns:dc=").count(), 1);
+    }
+
+    #[test]
+    fn test_chunk_round_trip() {
+        let xmp = Xmp::from_str(PACKET).unwrap();
+        let chunk = xmp.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_type().to_string(), "iTXt");
+        assert_eq!(Xmp::try_from(&chunk).unwrap(), xmp);
+    }
+
+    #[test]
+    fn test_malformed_packet() {
+        assert!(Xmp::from_str("<x:xmpmeta><rdf:RDF></x:xmpmeta>").is_err());
+        assert!(Xmp::from_str("<x:xmpmeta><rdf:RDF>").is_err());
+        assert!(Xmp::from_str("<x:xmpmeta/>").is_err());
+    }
+}
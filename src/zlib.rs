@@ -0,0 +1,40 @@
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+
+/// Inflates zlib `data`, failing if it holds more than `limit` bytes.
+///
+/// A few kilobytes of deflate stream can expand to gigabytes, so every
+/// compressed field read from a file goes through here with a bound that
+/// fits what the field can legitimately hold.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(data)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|e| e.to_string())?;
+    if out.len() > limit {
+        return Err(format!("data inflates to more than {} bytes", limit));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn test_inflate_limit() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 1 << 20]).unwrap();
+        let bomb = encoder.finish().unwrap();
+
+        assert_eq!(inflate(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        assert!(inflate(&bomb, 1000).is_err());
+        assert!(inflate(b"not zlib", 1000).is_err());
+    }
+}